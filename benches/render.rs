@@ -0,0 +1,64 @@
+//! Cost of rendering the table and the chart of a large data set, one frame
+//! at a time as the event loop does.
+
+use chrono::{Days, NaiveDate};
+use criterion::{Criterion, criterion_group, criterion_main};
+use ratatui::{Terminal, backend::TestBackend, layout::Rect};
+use weight_tracker::app::{App, ChartTimeFrame, FrameType};
+use weight_tracker::data::Entry;
+
+/// About 30 years of daily entries.
+const ENTRIES: u64 = 11_000;
+
+const WIDTH: u16 = 160;
+
+const HEIGHT: u16 = 45;
+
+fn app<'a>() -> App<'a> {
+    let first = NaiveDate::from_ymd_opt(1995, 1, 1).unwrap();
+    let last = first + Days::new(ENTRIES - 1);
+    let mut app = App::default();
+    *app.data.get_mut() = (0..ENTRIES)
+        .map(|i| {
+            let date = (first + Days::new(i)).format("%d-%m-%Y").to_string();
+            Entry::new(date, 80.0 + (i as f64 / 30.0).sin() * 5.0)
+        })
+        .collect();
+    app.today = Some(last);
+    app.selected_date_m = last;
+    app.selected_date_y = last;
+    app.selected_date_wy = last;
+    app.table_state.select(Some(ENTRIES as usize - 1));
+    app
+}
+
+fn render(c: &mut Criterion) {
+    let mut terminal = Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap();
+    let area = Rect::new(0, 0, WIDTH, HEIGHT);
+    let mut app = app();
+    c.bench_function("render_table", |b| {
+        b.iter(|| {
+            terminal.draw(|frame| app.render_table(area, frame)).unwrap();
+        })
+    });
+    app.current_frame = FrameType::Chart;
+    for (name, tf) in [
+        ("month", ChartTimeFrame::Month),
+        ("quarter", ChartTimeFrame::Quarter),
+        ("months", ChartTimeFrame::Months),
+        ("year", ChartTimeFrame::Year),
+        ("window_year", ChartTimeFrame::WindowYear),
+        ("year_over_year", ChartTimeFrame::YearOverYear),
+        ("overview", ChartTimeFrame::Overview),
+    ] {
+        app.current_tf = tf;
+        c.bench_function(&format!("render_chart_{}", name), |b| {
+            b.iter(|| {
+                terminal.draw(|frame| app.render_chart(area, frame)).unwrap();
+            })
+        });
+    }
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
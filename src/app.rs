@@ -0,0 +1,2268 @@
+use chrono::{Datelike, Days, Local, NaiveDate, Utc, Weekday};
+use ratatui::{DefaultTerminal, crossterm::event, widgets::TableState};
+use rust_xlsxwriter::{
+    Chart as XlsxChart, ChartType, ExcelDateTime, Format, Table as XlsxTable, TableColumn, Workbook,
+    XlsxError,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+use tui_textarea::TextArea;
+
+use crate::WtError;
+use crate::config::{
+    Config, DerivedColumn, RemoteKind, Theme, compile_derived, parse_challenges, parse_exclusions,
+    parse_input_fields, parse_quiet_days, parse_schedule, resolve_decimal_separator, set_config_value,
+    validate_lock,
+};
+use crate::data::{
+    Challenge, ChallengeResult, DateFormat, Entry, FLAG_ESTIMATED, FLAG_EXCLUDED, GoalEta, ImportReport, MergeReport,
+    SOURCE_MANUAL, VOLATILITY_DAYS, WEIGHT, challenge_progress, edit_entry, find_entry,
+    detect_date_formats, goal_etas, import_lines, import_source, insert_entry, last_entry_age, next_milestone, note_line,
+    read_import_source, schedule_streak, split_import_lines, TableFilter, update_note, volatility, week_label,
+    weekly_change, PendingChange, pending_changes, TrendShiftKind, trend_shift, goal_countdown,
+    GoalCountdown, Unit, format_stones, parse_stones, record_deletions, split_tombstones, with_tombstones,
+};
+use crate::export;
+use crate::remote;
+use crate::storage::{Change, MemoryStorage, Storage, backups, move_aside, read_entries, write_csv};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameType {
+    Table,
+    Chart,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChartTimeFrame {
+    Month,
+    /// Calendar quarter of the selected month.
+    Quarter,
+    /// `chart.window_months` months up to and including the selected month.
+    Months,
+    Year,
+    WindowYear,
+    YearOverYear,
+    /// The whole history, with weekly averages before the last
+    /// `chart.overview_days` days.
+    Overview,
+}
+
+/// Reference period of the month comparison view.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CompareMode {
+    PrevMonth,
+    PrevYear,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub enum WindowType {
+    ClosePopup,
+    InputPopup,
+    ImportPathPopup,
+    FilterPopup,
+    DiffPathPopup,
+    DiffView,
+    ImportPreview,
+    ImportProgress,
+    ImportSummary,
+    WeeklySummary,
+    AchievementUnlocked,
+    Achievements,
+    Correlation,
+    Challenges,
+    EntryDetails,
+    Trash,
+    StagedChanges,
+    Settings,
+    Heatmap,
+    MainWindow,
+}
+
+/// A box of the input popup. The date and weight always come first; the
+/// other metrics may be left blank.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputField {
+    Date,
+    Weight,
+    Rhr,
+    Device,
+}
+
+impl InputField {
+    /// Field of an optional metric named in `display.input_metrics`.
+    pub fn parse(name: &str) -> Option<InputField> {
+        match name {
+            "rhr" => Some(InputField::Rhr),
+            "device" => Some(InputField::Device),
+            _ => None,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            InputField::Date => " Date ",
+            InputField::Weight => " Weight ",
+            InputField::Rhr => " RHR ",
+            InputField::Device => " Device ",
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        match self {
+            InputField::Date => 15,
+            InputField::Weight => 11,
+            InputField::Rhr => 9,
+            InputField::Device => 12,
+        }
+    }
+}
+
+/// An option of the settings popup, written to the config file when changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Unit,
+    Theme,
+    DecimalSeparator,
+    GoalTarget,
+    Height,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 5] =
+        [Setting::Unit, Setting::Theme, Setting::DecimalSeparator, Setting::GoalTarget, Setting::Height];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::Unit => "Unit",
+            Setting::Theme => "Theme",
+            Setting::DecimalSeparator => "Decimal separator",
+            Setting::GoalTarget => "Goal weight",
+            Setting::Height => "Height (m)",
+        }
+    }
+
+    /// Key in the config file.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Setting::Unit => "display.unit",
+            Setting::Theme => "display.theme",
+            Setting::DecimalSeparator => "display.decimal_separator",
+            Setting::GoalTarget => "goal.target",
+            Setting::Height => "constants.height",
+        }
+    }
+
+    /// Values the setting is cycled through, empty for the ones that are typed in.
+    pub fn choices(&self) -> &'static [&'static str] {
+        match self {
+            Setting::Unit => &["kg", "lb"],
+            Setting::Theme => &["default", "monochrome"],
+            Setting::DecimalSeparator => &["auto", ".", ","],
+            Setting::GoalTarget | Setting::Height => &[],
+        }
+    }
+
+    /// Config value for the text `value`, with `.` as the decimal separator;
+    /// empty text unsets a typed-in setting. Goal weights follow the rules of
+    /// logged weights.
+    pub fn parse(&self, value: &str) -> Result<Option<toml::Value>, String> {
+        let value = value.trim();
+        if !self.choices().is_empty() {
+            return match self.choices().contains(&value) {
+                true => Ok(Some(toml::Value::String(value.to_string()))),
+                false => Err(format!("expected one of {}", self.choices().join(", "))),
+            };
+        }
+        if value.is_empty() {
+            return Ok(None);
+        }
+        if *self == Setting::GoalTarget {
+            return WEIGHT.validate(value).map(|x| Some(toml::Value::Float(x)));
+        }
+        let number = value.parse::<f64>().map_err(|_| format!("invalid number '{}'", value))?;
+        if !number.is_finite() || number <= 0.0 {
+            return Err(String::from("must be greater than 0"));
+        }
+        if number > 3.0 {
+            return Err(String::from("height is in metres, e.g. 1.80"));
+        }
+        Ok(Some(toml::Value::Float(number)))
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub enum TextMode {
+    Edit,
+    Append,
+}
+
+/// Severity of a message, in increasing priority.
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageType {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Messages for the message box, shown one at a time for `MSG_TIMEOUT` each.
+/// A message of higher priority is shown right away and the one it replaces
+/// waits for its turn; messages of the same priority are shown in order.
+#[derive(Default)]
+pub struct MessageQueue {
+    /// Message on screen and when it was first rendered.
+    current: Option<(String, MessageType, Option<Instant>)>,
+    pending: VecDeque<(String, MessageType)>,
+    /// Status of a running operation, shown whenever there is no message.
+    progress: Option<String>,
+}
+
+impl MessageQueue {
+    pub fn push(&mut self, text: impl Into<String>, kind: MessageType) {
+        let text = text.into();
+        match self.current.take() {
+            None => self.current = Some((text, kind, None)),
+            Some((shown, shown_kind, _)) if kind > shown_kind => {
+                self.pending.push_front((shown, shown_kind));
+                self.current = Some((text, kind, None));
+            }
+            current => {
+                self.current = current;
+                self.pending.push_back((text, kind));
+            }
+        }
+    }
+
+    /// Message or progress to show, if any.
+    pub fn current(&self) -> Option<(&str, MessageType)> {
+        match &self.current {
+            Some((text, kind, _)) => Some((text, *kind)),
+            None => self.progress.as_deref().map(|x| (x, MessageType::Info)),
+        }
+    }
+
+    /// Replaces the shown message with the next one of highest priority.
+    pub fn dismiss(&mut self) {
+        let next = self.pending.iter().map(|x| x.1).max();
+        let idx = self.pending.iter().position(|x| Some(x.1) == next);
+        self.current = idx
+            .and_then(|i| self.pending.remove(i))
+            .map(|(text, kind)| (text, kind, None));
+    }
+
+    /// Starts the timer of a newly shown message and dismisses it once it has
+    /// been on screen long enough. Returns whether a message was dismissed.
+    pub fn tick(&mut self) -> bool {
+        match &mut self.current {
+            Some((_, _, Some(shown))) if shown.elapsed() >= MSG_TIMEOUT => {
+                self.dismiss();
+                true
+            }
+            Some((_, _, shown)) => {
+                shown.get_or_insert_with(Instant::now);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_progress(&mut self, progress: Option<String>) {
+        self.progress = progress;
+    }
+
+    /// Removes and returns all messages, e.g. to print them on the command line.
+    pub fn take_all(&mut self) -> Vec<(String, MessageType)> {
+        let mut all = Vec::new();
+        while let Some((text, kind, _)) = self.current.take() {
+            all.push((text, kind));
+            self.dismiss();
+        }
+        all
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_none()
+    }
+}
+
+/// Changes made since the app was started, reported on exit.
+#[derive(Default, Clone)]
+pub struct SessionStats {
+    pub added: usize,
+    pub edited: usize,
+    pub deleted: usize,
+}
+
+/// Small state kept between runs next to the data file.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct State {
+    /// ISO week (`2024-W18`) whose weekly summary should not be shown again.
+    pub dismissed_week: Option<String>,
+    /// Unlocked achievement ids and the date they were unlocked.
+    pub achievements: BTreeMap<String, String>,
+    /// Results of challenges that have ended.
+    pub challenges: Vec<ChallengeResult>,
+    /// Where the last session left off.
+    pub view: Option<ViewState>,
+    /// Deleted entries that can still be restored, oldest deletion first.
+    pub trash: Vec<TrashedEntry>,
+}
+
+/// An entry moved to the trash by a delete.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashedEntry {
+    /// Day of the deletion, `dd-mm-yyyy`.
+    pub deleted: String,
+    /// The entry as written to the data file.
+    pub entry: String,
+}
+
+/// What a staged session started from, restored when its changes are
+/// aborted.
+pub struct Staging {
+    pub trash: Vec<TrashedEntry>,
+    pub session: SessionStats,
+}
+
+/// Table and chart position, restored on the next start.
+#[derive(Serialize, Deserialize)]
+pub struct ViewState {
+    /// Date of the selected row, so that rows added in between don't move it.
+    pub selected: Option<String>,
+    pub frame: FrameType,
+    pub timeframe: ChartTimeFrame,
+    /// Dates shown by the month, year and rolling year charts.
+    pub month: String,
+    pub year: String,
+    pub window_year: String,
+}
+
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 5] = [
+    Achievement {
+        id: "first_entry",
+        name: "First step",
+        description: "Log your first weight",
+    },
+    Achievement {
+        id: "streak_30",
+        name: "Creature of habit",
+        description: "Reach a 30 weigh-in streak",
+    },
+    Achievement {
+        id: "lost_5kg",
+        name: "Lighter",
+        description: "Weigh 5 kg less than your first entry",
+    },
+    Achievement {
+        id: "entries_100",
+        name: "Centurion",
+        description: "Log 100 entries",
+    },
+    Achievement {
+        id: "goal_reached",
+        name: "Goal!",
+        description: "Reach your goal weight",
+    },
+];
+
+pub struct ImportJob {
+    pub path: String,
+    /// Source recorded for the imported entries.
+    pub source: String,
+    pub lines: Vec<String>,
+    pub pos: usize,
+    pub report: ImportReport,
+    /// Outcome of a dry run over all lines, shown before importing.
+    pub preview: ImportReport,
+    pub format: DateFormat,
+    /// Date formats fitting the file equally well; the user picks one if
+    /// there are several.
+    pub formats: Vec<DateFormat>,
+}
+
+#[allow(dead_code)]
+pub struct App<'a> {
+    pub close: bool,
+    pub current_window: WindowType,
+    pub data: RefCell<Vec<Entry>>,
+    pub table_state: TableState,
+    pub current_frame: FrameType,
+    pub current_tf: ChartTimeFrame,
+    pub selected_date_wy: NaiveDate,
+    pub selected_date_y: NaiveDate,
+    pub selected_date_m: NaiveDate,
+    /// What each box of the input popup holds, in order.
+    pub input_fields: Vec<InputField>,
+    pub text_area: Vec<TextArea<'a>>,
+    pub text_is_valid: Vec<bool>,
+    pub selected_area: usize,
+    pub text_mode: Option<TextMode>,
+    /// Input popup texts of the entry being edited, as first shown.
+    pub input_original: Option<Vec<String>>,
+    /// Esc was pressed once on an edit with unsaved changes.
+    pub discard_confirm: bool,
+    /// The lock screen hides everything until the passphrase is entered.
+    pub locked: bool,
+    pub lock_area: TextArea<'a>,
+    /// The last passphrase entered was wrong.
+    pub lock_failed: bool,
+    /// Why the data file could not be read. The recovery screen is shown
+    /// until it is replaced by a backup or moved aside.
+    pub load_error: Option<WtError>,
+    /// Time of the last key press, for locking when idle.
+    pub last_input: Instant,
+    pub messages: MessageQueue,
+    /// Page of the key hints shown, reset when the window changes.
+    pub hint_page: usize,
+    pub rm_confirm: bool,
+    pub config: Config,
+    pub derived: Vec<DerivedColumn>,
+    pub path: String,
+    /// Data as last read from or written to `storage`.
+    pub saved_data: Vec<Entry>,
+    /// Tombstones of the deleted entries, written along with the data.
+    pub deleted: Vec<Entry>,
+    /// Tombstones as last read from or written to `storage`.
+    pub saved_deleted: Vec<Entry>,
+    pub storage: Box<dyn Storage>,
+    pub import_path_area: TextArea<'a>,
+    /// Restricts the rows of the table, set with `/`.
+    pub table_filter: Option<TableFilter>,
+    pub filter_area: TextArea<'a>,
+    /// Scroll position of the filtered table, whose rows don't line up with
+    /// `table_state`.
+    pub filter_offset: usize,
+    pub diff_path_area: TextArea<'a>,
+    /// File compared with the data by `D`, and how it differs.
+    pub diff: Option<(String, Vec<PendingChange>)>,
+    pub diff_state: TableState,
+    /// Rolling 7-day average of each entry, computed on first use and reset
+    /// whenever the data changes.
+    pub averages: Option<Vec<Option<f64>>>,
+    /// Weekly rate of change at each counted entry of `stats_data`, computed
+    /// on first use and reset along with `averages`.
+    pub rates: Option<Vec<(NaiveDate, f64)>>,
+    /// Weight cell of the selected row being edited in place (`i`).
+    pub inline_edit: Option<TextArea<'a>>,
+    pub import_job: Option<ImportJob>,
+    pub chart_inspect: bool,
+    /// Hypothetical weekly rate of the what-if simulator, when it is open.
+    pub simulated_rate: Option<f64>,
+    /// Days of the moving average drawn on the chart, one of
+    /// `SMOOTHING_WINDOWS`; `None` hides it.
+    pub smoothing_days: Option<u64>,
+    /// Plot the weekly change instead of the weight.
+    pub rate_view: bool,
+    pub achievements: BTreeMap<String, String>,
+    pub pending_achievements: VecDeque<usize>,
+    pub compare: Option<CompareMode>,
+    pub decimal_sep: char,
+    pub theme: Theme,
+    pub schedule: Vec<Weekday>,
+    /// Weekdays on which no weigh-in reminder is shown.
+    pub quiet_days: Vec<Weekday>,
+    pub exclusions: Vec<(NaiveDate, NaiveDate)>,
+    pub challenges: Vec<Challenge>,
+    pub challenge_results: Vec<ChallengeResult>,
+    pub session: SessionStats,
+    pub pending_count: Option<usize>,
+    pub rm_count: usize,
+    /// Date used instead of the system date, so that screens can be tested.
+    pub today: Option<NaiveDate>,
+    pub trash: Vec<TrashedEntry>,
+    pub trash_state: TableState,
+    /// The trash changed since it was last written to `state.toml`.
+    pub trash_changed: bool,
+    /// Set with `--staged`: edits are neither journaled nor saved until they
+    /// are committed in the review screen.
+    pub staging: Option<Staging>,
+    pub staged_state: TableState,
+    /// File the settings popup writes to.
+    pub config_path: String,
+    pub settings_state: TableState,
+    /// Editor of a typed-in setting, open while its value is being changed.
+    pub settings_area: Option<TextArea<'a>>,
+    /// Year shown by the heat map.
+    pub heatmap_year: i32,
+    /// Color the heat map by the change to the previous weigh-in instead of
+    /// whether there is one.
+    pub heatmap_deltas: bool,
+}
+
+/// `path` with a leading `~/` replaced by the home directory.
+fn expand_home(path: String) -> String {
+    match (path.strip_prefix("~/"), directories::BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest).to_string_lossy().into_owned(),
+        _ => path,
+    }
+}
+
+/// `label` and `value` on one line of exactly `width` characters, the value
+/// right-aligned; the label is cut short if both don't fit.
+fn report_row(label: &str, value: &str, width: usize) -> String {
+    let value_len = value.chars().count();
+    let label_max = width.saturating_sub(value_len + 1);
+    let label = label.chars().take(label_max).collect::<String>();
+    let gap = width.saturating_sub(label.chars().count() + value_len);
+    format!("{}{}{}", label, " ".repeat(gap), value)
+}
+
+/// `text` centered in `width` characters, cut short if it is too long.
+fn report_center(text: &str, width: usize) -> String {
+    let text = text.chars().take(width).collect::<String>();
+    let pad = (width - text.chars().count()) / 2;
+    format!("{}{}", " ".repeat(pad), text)
+}
+
+pub fn merge_message(merge: &MergeReport) -> String {
+    format!(
+        "Data file changed on disk: merged {} entries, resolved {} conflicts by most recent change",
+        merge.merged, merge.conflicts
+    )
+}
+
+const REPORT_RECENT_ENTRIES: usize = 7;
+
+const IMPORT_CHUNK_SIZE: usize = 500;
+
+/// Moving-average windows of the chart, stepped through with `[` and `]`.
+pub const SMOOTHING_WINDOWS: [u64; 4] = [3, 7, 14, 30];
+
+/// Days between reminders in maintenance with `schedule.adaptive`.
+const MAINTENANCE_DAYS: u64 = 7;
+
+const MSG_TIMEOUT: Duration = Duration::from_secs(3);
+
+impl Default for App<'_> {
+    fn default() -> Self {
+        let now = Local::now().date_naive();
+        App {
+            close: false,
+            current_window: WindowType::MainWindow,
+            data: RefCell::new(Vec::new()),
+            table_state: TableState::default(),
+            current_frame: FrameType::Table,
+            current_tf: ChartTimeFrame::Month,
+            selected_date_wy: now,
+            selected_date_y: now,
+            selected_date_m: now,
+            input_fields: vec![InputField::Date, InputField::Weight, InputField::Rhr],
+            text_area: vec![TextArea::default(); 3],
+            text_is_valid: vec![false, false, true],
+            selected_area: 1,
+            text_mode: None,
+            messages: MessageQueue::default(),
+            hint_page: 0,
+            rm_confirm: false,
+            config: Config::default(),
+            derived: Vec::new(),
+            path: String::new(),
+            saved_data: Vec::new(),
+            deleted: Vec::new(),
+            saved_deleted: Vec::new(),
+            storage: Box::new(MemoryStorage::default()),
+            import_path_area: TextArea::default(),
+            table_filter: None,
+            filter_area: TextArea::default(),
+            filter_offset: 0,
+            diff_path_area: TextArea::default(),
+            diff: None,
+            diff_state: TableState::default(),
+            import_job: None,
+            chart_inspect: false,
+            inline_edit: None,
+            averages: None,
+            rates: None,
+            simulated_rate: None,
+            smoothing_days: None,
+            rate_view: false,
+            achievements: BTreeMap::new(),
+            pending_achievements: VecDeque::new(),
+            compare: None,
+            decimal_sep: '.',
+            theme: Theme::Default,
+            schedule: Vec::new(),
+            quiet_days: Vec::new(),
+            exclusions: Vec::new(),
+            challenges: Vec::new(),
+            challenge_results: Vec::new(),
+            session: SessionStats::default(),
+            pending_count: None,
+            rm_count: 1,
+            today: None,
+            input_original: None,
+            discard_confirm: false,
+            locked: false,
+            lock_area: TextArea::default(),
+            lock_failed: false,
+            load_error: None,
+            last_input: Instant::now(),
+            trash: Vec::new(),
+            trash_state: TableState::default(),
+            trash_changed: false,
+            staging: None,
+            staged_state: TableState::default(),
+            config_path: String::new(),
+            settings_state: TableState::default(),
+            settings_area: None,
+            heatmap_year: now.year(),
+            heatmap_deltas: false,
+        }
+    }
+}
+
+impl App<'_> {
+    #[allow(dead_code)]
+    pub fn default_test() -> Self {
+        let now = Local::now().date_naive();
+        App {
+            close: false,
+            current_window: WindowType::MainWindow,
+            data: RefCell::new(vec![
+                Entry::new("26-04-2024".to_string(), 90.1),
+                Entry::new("27-04-2024".to_string(), 89.9),
+                Entry::new("01-05-2024".to_string(), 91.5),
+                Entry::new("02-05-2024".to_string(), 94.1),
+                Entry::new("03-05-2024".to_string(), 87.9),
+            ]),
+            table_state: TableState::default(),
+            current_frame: FrameType::Table,
+            current_tf: ChartTimeFrame::Month,
+            selected_date_wy: now,
+            selected_date_y: now,
+            selected_date_m: now,
+            input_fields: vec![InputField::Date, InputField::Weight, InputField::Rhr],
+            text_area: vec![TextArea::default(); 3],
+            text_is_valid: vec![false, false, true],
+            selected_area: 1,
+            text_mode: None,
+            messages: MessageQueue::default(),
+            hint_page: 0,
+            rm_confirm: false,
+            config: Config::default(),
+            derived: Vec::new(),
+            path: String::new(),
+            saved_data: Vec::new(),
+            deleted: Vec::new(),
+            saved_deleted: Vec::new(),
+            storage: Box::new(MemoryStorage::default()),
+            import_path_area: TextArea::default(),
+            table_filter: None,
+            filter_area: TextArea::default(),
+            filter_offset: 0,
+            diff_path_area: TextArea::default(),
+            diff: None,
+            diff_state: TableState::default(),
+            import_job: None,
+            chart_inspect: false,
+            inline_edit: None,
+            averages: None,
+            rates: None,
+            simulated_rate: None,
+            smoothing_days: None,
+            rate_view: false,
+            achievements: BTreeMap::new(),
+            pending_achievements: VecDeque::new(),
+            compare: None,
+            decimal_sep: '.',
+            theme: Theme::Default,
+            schedule: Vec::new(),
+            quiet_days: Vec::new(),
+            exclusions: Vec::new(),
+            challenges: Vec::new(),
+            challenge_results: Vec::new(),
+            session: SessionStats::default(),
+            pending_count: None,
+            rm_count: 1,
+            today: None,
+            input_original: None,
+            discard_confirm: false,
+            locked: false,
+            lock_area: TextArea::default(),
+            lock_failed: false,
+            load_error: None,
+            last_input: Instant::now(),
+            trash: Vec::new(),
+            trash_state: TableState::default(),
+            trash_changed: false,
+            staging: None,
+            staged_state: TableState::default(),
+            config_path: String::new(),
+            settings_state: TableState::default(),
+            settings_area: None,
+            heatmap_year: now.year(),
+            heatmap_deltas: false,
+        }
+    }
+
+    /// Selects the entry of `date` for `modify_data`, as picking it in the
+    /// table does.
+    pub fn select_entry(&mut self, date: NaiveDate) -> Result<usize, WtError> {
+        let idx = find_entry(self.data.get_mut(), date)
+            .map_err(|_| WtError::Validation(format!("no entry for {}", date.format("%d-%m-%Y"))))?;
+        self.table_state.select(Some(idx));
+        self.text_mode = Some(TextMode::Edit);
+        Ok(idx)
+    }
+
+    /// Adds or edits the entry with `(weight, resting heart rate)`, or deletes
+    /// the selected one if that is `None`.
+    pub fn modify_data(&mut self, element: (String, Option<(f64, Option<f64>)>)) -> bool {
+        let idx = self.table_state.selected_mut();
+        if idx.is_none() {
+            return false;
+        }
+        let idx = idx.unwrap();
+        self.averages = None;
+        self.rates = None;
+        let data_ref = self.data.get_mut();
+        if let (s, Some((num, rhr))) = element {
+            if self.text_mode == Some(TextMode::Edit) {
+                edit_entry(&mut data_ref[idx], num, rhr);
+                let date = data_ref[idx].date.clone();
+                self.journal(&[date]);
+                self.session.edited += 1;
+            } else if self.text_mode == Some(TextMode::Append) {
+                let mut entry = Entry::new(s.clone(), num);
+                entry.rhr = rhr;
+                entry.source = Some(String::from(SOURCE_MANUAL));
+                entry.device = self.config.devices.default.clone();
+                if !insert_entry(data_ref, entry) {
+                    self.messages.push("Cannot add element. Did you mean to edit?", MessageType::Error);
+                    return false;
+                }
+                self.journal(&[s]);
+                self.session.added += 1;
+            }
+        } else if let (_, None) = element {
+            let removed = data_ref.remove(idx);
+            self.trash.push(TrashedEntry {
+                deleted: self.today().format("%d-%m-%Y").to_string(),
+                entry: removed.to_fields(),
+            });
+            self.trash_changed = true;
+            self.journal(&[removed.date]);
+            self.session.deleted += 1;
+            self.rm_confirm = false;
+            self.messages.dismiss();
+        }
+        true
+    }
+
+    /// Loads the entries from storage, replaying changes a previous session
+    /// appended but never saved.
+    pub fn load(&mut self) -> Result<(), WtError> {
+        let mut loaded = self.storage.load()?;
+        let unit = self.config.display.unit;
+        let (mut saved, saved_deleted) = split_tombstones(std::mem::take(&mut loaded.saved));
+        saved.iter_mut().for_each(|x| x.normalize(unit));
+        let (mut data, mut deleted) = (saved.clone(), saved_deleted.clone());
+        for change in &loaded.pending {
+            change.apply(&mut data);
+        }
+        // The journal has no deletion times; replayed deletions count as now.
+        let replayed = loaded.pending.iter().map(|x| x.date().to_string()).collect::<Vec<_>>();
+        record_deletions(&mut deleted, &data, &replayed, Utc::now());
+        data.iter_mut().for_each(|x| x.normalize(unit));
+        info!(
+            "loaded {} entries from {}, replayed {} changes, {} corrupt",
+            saved.len(),
+            self.storage.location(),
+            loaded.pending.len(),
+            loaded.corrupt
+        );
+        self.saved_data = saved;
+        self.saved_deleted = saved_deleted;
+        self.deleted = deleted;
+        self.data = RefCell::new(data);
+        self.averages = None;
+        self.rates = None;
+        if let Some((version, backup)) = &loaded.migrated {
+            self.messages.push(
+                format!("Upgraded the data file from format {}, the original is in {}", version, backup),
+                MessageType::Info,
+            );
+        }
+        if loaded.corrupt > 0 {
+            self.messages.push(
+                format!(
+                    "Recovered {} unsaved changes, {} journal lines were corrupt",
+                    loaded.pending.len(),
+                    loaded.corrupt
+                ),
+                MessageType::Warning,
+            );
+        } else if !loaded.pending.is_empty() {
+            self.messages.push(
+                format!("Recovered {} unsaved changes from journal", loaded.pending.len()),
+                MessageType::Info,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn export_data(&self, file: &mut impl Write) -> io::Result<()> {
+        write_csv(&self.data.borrow(), file)
+    }
+
+    /// Writes the data as a formatted Excel table with a weight chart next to it.
+    pub fn export_xlsx(&self, path: &str) -> Result<(), WtError> {
+        let to_storage = |e: XlsxError| WtError::Storage(format!("{}: {}", path, e));
+        let data_ref = self.data.borrow();
+        let table_columns = self.derived.iter().filter(|d| d.table).collect::<Vec<_>>();
+        let date_format = Format::new().set_num_format("dd/mm/yyyy");
+        let weight_format = Format::new().set_num_format("0.0");
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Weight").map_err(to_storage)?;
+        for (i, entry) in data_ref.iter().enumerate() {
+            let row = i as u32 + 1;
+            let date = NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y").unwrap();
+            let date = ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)
+                .map_err(to_storage)?;
+            sheet.write_datetime_with_format(row, 0, &date, &date_format).map_err(to_storage)?;
+            sheet.write_number_with_format(row, 1, entry.weight, &weight_format).map_err(to_storage)?;
+            for (col, d) in table_columns.iter().enumerate() {
+                if let Some(v) = d.expr.eval(&|name| self.lookup_var(name, entry.weight)) {
+                    sheet
+                        .write_number_with_format(row, col as u16 + 2, v, &weight_format)
+                        .map_err(to_storage)?;
+                }
+            }
+            let rhr_col = table_columns.len() as u16 + 2;
+            if let Some(rhr) = entry.rhr {
+                sheet.write_number(row, rhr_col, rhr).map_err(to_storage)?;
+            }
+            let flags = [(entry.excluded, FLAG_EXCLUDED), (entry.estimated, FLAG_ESTIMATED)]
+                .iter()
+                .filter(|x| x.0)
+                .map(|x| x.1)
+                .collect::<Vec<_>>();
+            if !flags.is_empty() {
+                sheet.write_string(row, rhr_col + 1, flags.join(", ")).map_err(to_storage)?;
+            }
+        }
+        let mut columns = vec![TableColumn::new().set_header("Date"), TableColumn::new().set_header("Weight")];
+        columns.extend(table_columns.iter().map(|d| TableColumn::new().set_header(&d.name)));
+        columns.push(TableColumn::new().set_header("RHR"));
+        columns.push(TableColumn::new().set_header("Flags"));
+        let last_row = (data_ref.len() as u32).max(1);
+        let last_col = columns.len() as u16 - 1;
+        let table = XlsxTable::new().set_columns(&columns);
+        sheet.add_table(0, 0, last_row, last_col, &table).map_err(to_storage)?;
+        sheet.set_column_width(0, 12).map_err(to_storage)?;
+
+        let mut chart = XlsxChart::new(ChartType::Line);
+        chart.title().set_name("Weight");
+        chart
+            .add_series()
+            .set_name("Weight")
+            .set_categories(("Weight", 1, 0, last_row, 0))
+            .set_values(("Weight", 1, 1, last_row, 1));
+        chart.x_axis().set_date_axis(true).set_num_format("mmm yyyy");
+        chart.legend().set_hidden();
+        sheet.insert_chart(1, last_col + 2, &chart).map_err(to_storage)?;
+        workbook.save(path).map_err(to_storage)
+    }
+
+    pub fn start_import(&mut self) {
+        let path = self.import_path_area.lines()[0].trim().to_string();
+        match read_import_source(&path) {
+            Ok(text) => {
+                let lines = split_import_lines(&text);
+                let formats = detect_date_formats(&lines);
+                self.import_job = Some(ImportJob {
+                    source: import_source(&path, None),
+                    path,
+                    lines,
+                    pos: 0,
+                    report: ImportReport::default(),
+                    preview: ImportReport::default(),
+                    format: formats.first().copied().unwrap_or_default(),
+                    formats,
+                });
+                self.preview_import();
+                self.current_window = WindowType::ImportPreview;
+            }
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    /// Dry-runs the pending import with its current date format.
+    fn preview_import(&mut self) {
+        let Some(job) = self.import_job.as_mut() else {
+            return;
+        };
+        job.preview = ImportReport::default();
+        let mut data = self.data.get_mut().clone();
+        import_lines(&mut data, &job.lines, 0, &job.source, job.format, &mut job.preview);
+    }
+
+    /// Switches an ambiguous import to the next date format that fits it.
+    pub fn cycle_import_format(&mut self) {
+        let Some(job) = self.import_job.as_mut() else {
+            return;
+        };
+        let Some(idx) = job.formats.iter().position(|x| *x == job.format) else {
+            return;
+        };
+        job.format = job.formats[(idx + 1) % job.formats.len()];
+        self.preview_import();
+    }
+
+    /// Processes the next chunk of the running import, so the progress popup
+    /// keeps rendering between chunks.
+    fn step_import(&mut self) {
+        let Some(job) = self.import_job.as_mut() else {
+            return;
+        };
+        if self.current_window != WindowType::ImportProgress {
+            return;
+        }
+        let end = (job.pos + IMPORT_CHUNK_SIZE).min(job.lines.len());
+        self.averages = None;
+        self.rates = None;
+        let added = import_lines(
+            self.data.get_mut(),
+            &job.lines[job.pos..end],
+            job.pos,
+            &job.source,
+            job.format,
+            &mut job.report,
+        );
+        job.pos = end;
+        let unit = self.config.display.unit;
+        self.data.get_mut().iter_mut().for_each(|x| x.normalize(unit));
+        self.session.added += added.len();
+        let progress = format!("Importing {}: {}/{} lines", job.path, job.pos, job.lines.len());
+        self.messages.set_progress(Some(progress));
+        if job.pos >= job.lines.len() {
+            self.messages.set_progress(None);
+            let report = &job.report;
+            info!(
+                "imported {}: {} added, {} duplicates, {} failed",
+                job.path,
+                report.added,
+                report.duplicates,
+                report.failed.len()
+            );
+            self.current_window = WindowType::ImportSummary;
+        }
+        self.journal(&added);
+    }
+
+    /// Replaces the canonical `.` of a formatted number with the display separator.
+    pub fn localize(&self, text: String) -> String {
+        if self.decimal_sep == '.' {
+            text
+        } else {
+            text.replace('.', &self.decimal_sep.to_string())
+        }
+    }
+
+    /// Inverse of `localize` for user input. Both `.` and `,` are taken as
+    /// the decimal separator whatever the locale, as typing the other one out
+    /// of habit is common.
+    pub fn delocalize(&self, text: &str) -> String {
+        text.replace(',', ".")
+    }
+
+    pub fn stones(&self) -> bool {
+        self.config.display.stones && self.config.display.unit == Unit::Lb
+    }
+
+    /// A weight as shown in the table, popups and stats.
+    pub fn format_weight(&self, weight: f64) -> String {
+        if self.stones() {
+            self.localize(format_stones(weight))
+        } else {
+            self.localize(WEIGHT.format(weight))
+        }
+    }
+
+    /// A typed weight; in stones mode `12st6` is accepted next to pounds.
+    pub fn parse_weight(&self, text: &str) -> Result<f64, String> {
+        let text = self.delocalize(text);
+        match parse_stones(&text).filter(|_| self.stones()) {
+            Some(lb) => WEIGHT.validate(&format!("{}", lb)),
+            None => WEIGHT.validate(&text),
+        }
+    }
+
+    /// Validates `config` and makes it the active configuration. On error the
+    /// current configuration is kept.
+    pub fn apply_config(&mut self, config: Config) -> Result<(), WtError> {
+        let decimal_sep = resolve_decimal_separator(&config.display)?;
+        let theme = config.display.theme;
+        let derived = compile_derived(&config)?;
+        let schedule = parse_schedule(&config.schedule)?;
+        let quiet_days = parse_quiet_days(&config.schedule)?;
+        let exclusions = parse_exclusions(&config.exclude)?;
+        let challenges = parse_challenges(&config.challenge)?;
+        let input_fields = parse_input_fields(&config.display)?;
+        if config.chart.padding.value.is_some_and(|v| v.is_nan() || v < 0.0) {
+            return Err(WtError::Config(String::from("chart padding must not be negative")));
+        }
+        if config.chart.window_months == 0 {
+            return Err(WtError::Config(String::from("chart window_months must be at least 1")));
+        }
+        let alert = &config.trend_alert;
+        let invalid_rate = alert.min_rate.is_nan() || alert.min_rate < 0.0;
+        if alert.weeks == 0 || alert.ratio.is_nan() || alert.ratio <= 1.0 || invalid_rate {
+            return Err(WtError::Config(String::from(
+                "trend_alert needs weeks of at least 1, a ratio above 1 and a non-negative min_rate",
+            )));
+        }
+        validate_lock(&config.lock)?;
+        if let Some(note) = &config.daily_note {
+            if note.path.is_empty() {
+                return Err(WtError::Config(String::from("daily_note path is required")));
+            }
+            if !note.template.contains("{weight}") {
+                return Err(WtError::Config(String::from("daily_note template must contain {weight}")));
+            }
+        }
+        if config.auto_export.iter().any(|x| x.path.is_empty()) {
+            return Err(WtError::Config(String::from("auto_export path is required")));
+        }
+        if let Some(remote) = &config.remote {
+            if remote.url.is_empty() {
+                return Err(WtError::Config(String::from("remote url is required")));
+            }
+            if remote.kind == RemoteKind::S3 && remote.bucket.is_empty() {
+                return Err(WtError::Config(String::from("remote bucket is required for s3")));
+            }
+        }
+        self.derived = derived;
+        self.decimal_sep = decimal_sep;
+        self.theme = theme;
+        self.schedule = schedule;
+        self.quiet_days = quiet_days;
+        self.exclusions = exclusions;
+        // Exclusions and device offsets feed the rates.
+        self.rates = None;
+        self.challenges = challenges;
+        self.input_fields = input_fields;
+        if config.display.reduced_motion.is_some() {
+            let msg = "display.reduced_motion is no longer used: key hints are paged, ? shows the next page";
+            warn!("{}", msg);
+            self.messages.push(msg, MessageType::Warning);
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Current value of `setting` as shown and edited in the settings popup,
+    /// empty if it isn't set.
+    pub fn setting_value(&self, setting: Setting) -> String {
+        match setting {
+            Setting::Unit => self.config.display.unit.name().to_string(),
+            Setting::Theme => match self.config.display.theme {
+                Theme::Default => String::from("default"),
+                Theme::Monochrome => String::from("monochrome"),
+            },
+            Setting::DecimalSeparator => self.config.display.decimal_separator.clone(),
+            Setting::GoalTarget => {
+                self.config.goal.target.map(|x| self.localize(WEIGHT.format(x))).unwrap_or_default()
+            }
+            Setting::Height => {
+                let height = self.config.constants.get("height");
+                height.map(|x| self.localize(format!("{:.2}", x))).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Writes `value` for `setting` to the config file and applies it. Nothing
+    /// changes if the value is invalid or the config file wouldn't load with
+    /// it, e.g. without a height a derived column needs.
+    pub fn change_setting(&mut self, setting: Setting, value: &str) -> Result<(), WtError> {
+        let value = setting
+            .parse(&self.delocalize(value))
+            .map_err(|e| WtError::Validation(format!("{}: {}", setting.name(), e)))?;
+        let path = self.config_path.clone();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(WtError::Config(format!("cannot read {}: {}", path, e))),
+        };
+        let mut text = set_config_value(&text, setting.key(), value)?;
+        // Validated like on start, so that the next start doesn't fail.
+        let config = toml::from_str::<Config>(&text).map_err(|e| WtError::Config(e.message().to_string()))?;
+        let mut checked = App::default();
+        checked.apply_config(config)?;
+        if setting == Setting::Unit {
+            // The goal weights are in the display unit, so they move with it.
+            let (from, to) = (self.config.display.unit, checked.config.display.unit);
+            let goal = &mut checked.config.goal;
+            for (key, weight) in [("goal.target", &mut goal.target), ("goal.start", &mut goal.start)] {
+                if let Some(weight) = weight {
+                    *weight = from.convert(*weight, to);
+                    text = set_config_value(&text, key, Some(toml::Value::Float(*weight)))?;
+                }
+            }
+        }
+        let to_config = |e: io::Error| WtError::Config(format!("cannot write {}: {}", path, e));
+        if let Some(dir) = Path::new(&path).parent() {
+            fs::create_dir_all(dir).map_err(to_config)?;
+        }
+        fs::write(&path, text).map_err(to_config)?;
+        info!("set {} in {}", setting.key(), path);
+        let new = checked.config;
+        match setting {
+            Setting::Unit => {
+                let (old, unit) = (self.config.display.unit, new.display.unit);
+                self.config.display.unit = unit;
+                self.config.goal.target = new.goal.target;
+                self.config.goal.start = new.goal.start;
+                for entry in self.data.get_mut().iter_mut().chain(self.saved_data.iter_mut()) {
+                    // Rows without a unit were in the old one.
+                    entry.original.get_or_insert((old, entry.weight));
+                    entry.normalize(unit);
+                }
+                for trashed in self.trash.iter_mut() {
+                    let Some(mut entry) = Entry::from_fields(&trashed.entry) else {
+                        continue;
+                    };
+                    if entry.original.is_none() {
+                        entry.original = Some((old, entry.weight));
+                        trashed.entry = entry.to_fields();
+                        self.trash_changed = true;
+                    }
+                }
+                self.averages = None;
+                self.rates = None;
+            }
+            Setting::Theme => {
+                self.config.display.theme = new.display.theme;
+                self.theme = checked.theme;
+            }
+            Setting::DecimalSeparator => {
+                self.config.display.decimal_separator = new.display.decimal_separator;
+                self.decimal_sep = checked.decimal_sep;
+            }
+            Setting::GoalTarget => self.config.goal.target = new.goal.target,
+            Setting::Height => {
+                match new.constants.get("height") {
+                    Some(height) => self.config.constants.insert(String::from("height"), *height),
+                    None => self.config.constants.remove("height"),
+                };
+                self.derived = compile_derived(&self.config)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy of the data with entries inside an exclusion range marked as
+    /// excluded, for everything that computes statistics.
+    pub fn stats_data(&self) -> Vec<Entry> {
+        let mut data = self.data.borrow().clone();
+        if self.config.devices.normalize {
+            for entry in data.iter_mut() {
+                entry.weight -= self.device_offset(entry);
+            }
+        }
+        if self.exclusions.is_empty() {
+            return data;
+        }
+        for entry in data.iter_mut() {
+            let date = NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y").unwrap();
+            if self.exclusions.iter().any(|(from, to)| *from <= date && date <= *to) {
+                entry.excluded = true;
+            }
+        }
+        data
+    }
+
+    /// How much the scale of `entry` reads above the true weight.
+    pub fn device_offset(&self, entry: &Entry) -> f64 {
+        let offsets = &self.config.devices.offsets;
+        entry.device.as_ref().and_then(|x| offsets.get(x)).copied().unwrap_or_default()
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.today.unwrap_or_else(|| Local::now().date_naive())
+    }
+
+    /// Whether `entry` passes the table filter.
+    pub fn shows_row(&self, entry: &Entry) -> bool {
+        self.table_filter.as_ref().is_none_or(|x| x.matches(entry))
+    }
+
+    /// The active table filter written back as bounds, e.g. " ≥ 90.0 · ≤ 01-06-2024 ".
+    pub fn filter_label(&self) -> Option<String> {
+        let filter = self.table_filter.as_ref()?;
+        let weight = |x: f64| self.format_weight(x);
+        let date = |x: NaiveDate| x.format("%d-%m-%Y").to_string();
+        let bounds = [
+            filter.min_weight.map(|x| format!("≥ {}", weight(x))),
+            filter.max_weight.map(|x| format!("≤ {}", weight(x))),
+            filter.from.map(|x| format!("≥ {}", date(x))),
+            filter.to.map(|x| format!("≤ {}", date(x))),
+        ];
+        Some(format!(" {} ", bounds.into_iter().flatten().collect::<Vec<_>>().join(" · ")))
+    }
+
+    pub fn is_scheduled(&self, date: NaiveDate) -> bool {
+        self.schedule.is_empty() || self.schedule.contains(&date.weekday())
+    }
+
+    /// Whether the trend hasn't reached the goal yet. Without a goal, or once
+    /// the trend is there, the app is in maintenance.
+    pub fn goal_active(&self) -> bool {
+        self.config.goal.target.is_some_and(|target| {
+            goal_etas(&self.stats_data(), target, self.config.goal.trend_alpha)
+                .is_some_and(|(_, trend)| !matches!(trend, GoalEta::Reached(_)))
+        })
+    }
+
+    /// Whether to remind about a weigh-in on `date`. Quiet days never remind.
+    /// With `schedule.adaptive` a reminder is due every day while the goal is
+    /// active, and in maintenance once no entry was logged in the week before.
+    pub fn reminder_due(&self, date: NaiveDate) -> bool {
+        if self.quiet_days.contains(&date.weekday()) {
+            return false;
+        }
+        if !self.config.schedule.adaptive {
+            return self.is_scheduled(date);
+        }
+        if self.goal_active() {
+            return true;
+        }
+        let week_ago = date - Days::new(MAINTENANCE_DAYS);
+        !self
+            .data
+            .borrow()
+            .iter()
+            .filter_map(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok())
+            .any(|x| x > week_ago && x < date)
+    }
+
+    /// Summary printed to stdout after the terminal has been restored.
+    pub fn session_summary(&self) -> String {
+        let data_ref = self.data.borrow();
+        let session = &self.session;
+        let mut ret = format!(
+            "This session: {} added, {} edited, {} deleted\n",
+            session.added, session.edited, session.deleted
+        );
+        if let Some(change) = weekly_change(&self.stats_data()) {
+            ret.push_str(&self.localize(format!("7-day change: {:+.1}\n", change)));
+        }
+        if let (Some(target), Some(last)) = (self.config.goal.target, data_ref.last()) {
+            let milestone = next_milestone(last.weight, target, self.config.goal.milestone_step);
+            ret.push_str(&self.localize(format!(
+                "Next milestone: {:.1} ({:.1} to go, goal {:.1})\n",
+                milestone,
+                (last.weight - milestone).abs(),
+                target
+            )));
+        }
+        ret
+    }
+
+    /// Plain-text summary at most `width` characters wide.
+    pub fn report(&self, width: usize, plain: bool) -> String {
+        let rule = if plain { "-" } else { "─" }.repeat(width);
+        let today = self.today();
+        let data = self.stats_data();
+        let mut lines = vec![
+            report_center("WEIGHT TRACKER", width),
+            report_center(&format!("Report {}", today.format("%d-%m-%Y")), width),
+            rule.clone(),
+        ];
+        let Some(last) = data.last() else {
+            lines.push(String::from("No entries yet"));
+            return lines.join("\n") + "\n";
+        };
+        let fmt = |x: f64| self.format_weight(x);
+        lines.push(report_row("Last entry", &last.date, width));
+        lines.push(report_row("Weight", &fmt(last.weight), width));
+        if let Some(change) = weekly_change(&data) {
+            lines.push(report_row("7-day change", &self.localize(format!("{:+.1}", change)), width));
+        }
+        if let Some(deviation) = volatility(&data) {
+            let sign = if plain { "+/-" } else { "±" };
+            let value = self.localize(format!("{}{:.1}", sign, deviation));
+            lines.push(report_row(&format!("{}-day volatility", VOLATILITY_DAYS), &value, width));
+        }
+        let month_ago = today - Days::new(30);
+        let recent = data
+            .iter()
+            .filter(|x| !x.excluded)
+            .filter(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").is_ok_and(|d| d > month_ago))
+            .map(|x| x.weight)
+            .collect::<Vec<_>>();
+        if !recent.is_empty() {
+            let avg = recent.iter().sum::<f64>() / recent.len() as f64;
+            lines.push(report_row("30-day average", &self.localize(format!("{:.1}", avg)), width));
+        }
+        let streak = schedule_streak(&self.data.borrow(), &self.schedule, today);
+        lines.push(report_row("Streak", &streak.to_string(), width));
+        if let Some(target) = self.config.goal.target {
+            lines.push(report_row("Goal", &fmt(target), width));
+            if let Some(countdown) = self.goal_countdown() {
+                lines.push(report_row("To go", &self.countdown_text(&countdown), width));
+            }
+            if let Some((scale, trend)) = goal_etas(&data, target, self.config.goal.trend_alpha) {
+                lines.push(report_row("Scale goal", &scale.describe(), width));
+                lines.push(report_row("Trend goal", &trend.describe(), width));
+            }
+        }
+        lines.push(report_row("Entries", &data.len().to_string(), width));
+        lines.push(rule.clone());
+        lines.push(String::from("Last entries"));
+        for entry in data.iter().rev().take(REPORT_RECENT_ENTRIES) {
+            let value = if entry.excluded {
+                format!("({})", fmt(entry.weight))
+            } else {
+                fmt(entry.weight)
+            };
+            lines.push(report_row(&entry.date, &value, width));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    pub fn newest_backup(&self) -> Option<String> {
+        backups(&self.storage.location()).ok()?.pop()
+    }
+
+    /// Moves the unreadable data file aside and loads `backup` in its place,
+    /// or starts without entries.
+    pub fn recover(&mut self, backup: Option<&str>) -> Result<(), WtError> {
+        let path = self.storage.location();
+        if let Some(backup) = backup {
+            // Checked first, so that a broken backup leaves everything in place.
+            read_entries(backup)?;
+        }
+        let aside = move_aside(&path)?;
+        info!("moved unreadable {} to {}", path, aside);
+        if let Some(backup) = backup {
+            fs::copy(backup, &path)?;
+        }
+        self.load()?;
+        self.load_error = None;
+        let msg = format!("The unreadable data file was moved to {}", aside);
+        self.messages.push(msg, MessageType::Warning);
+        Ok(())
+    }
+
+    pub fn show_error(&mut self, e: WtError) {
+        warn!("{}", e);
+        self.messages.push(e.to_string(), MessageType::Error);
+    }
+
+    pub fn is_stale(&self) -> bool {
+        last_entry_age(&self.data.borrow(), self.today())
+            .is_some_and(|age| age > self.config.staleness.days)
+    }
+
+    /// Startup message about a significant change of the weight trend. It is
+    /// a warning if the change works against the goal, or without a goal if
+    /// the trend reversed.
+    pub fn trend_alert(&self) -> Option<(String, MessageType)> {
+        let alert = &self.config.trend_alert;
+        if !alert.enabled {
+            return None;
+        }
+        let data = self.stats_data();
+        let shift = trend_shift(&data, self.today(), alert.weeks, alert.min_rate, alert.ratio)?;
+        let unit = self.config.display.unit.name();
+        let rate = |x: f64| self.localize(format!("{:.1}", x.abs()));
+        let direction = |x: f64| if x < 0.0 { "loss" } else { "gain" };
+        let weeks = format!("{} week{}", alert.weeks, if alert.weeks == 1 { "" } else { "s" });
+        let (msg, heading) = match shift.kind {
+            TrendShiftKind::Reversed => (
+                format!(
+                    "Trend reversed over the past {}: {} of {} {}/week after {} of {}",
+                    weeks,
+                    direction(shift.now),
+                    rate(shift.now),
+                    unit,
+                    direction(shift.before),
+                    rate(shift.before)
+                ),
+                shift.now,
+            ),
+            TrendShiftKind::Slower => (
+                format!(
+                    "Rate of {} slowed from {} to {} {}/week over the past {}",
+                    direction(shift.before),
+                    rate(shift.before),
+                    rate((shift.now * shift.before.signum()).max(0.0)),
+                    unit,
+                    weeks
+                ),
+                // Slowing down amounts to heading the other way.
+                -shift.before,
+            ),
+            TrendShiftKind::Faster => (
+                format!(
+                    "Rate of {} sped up from {} to {} {}/week over the past {}",
+                    direction(shift.now),
+                    rate((shift.before * shift.now.signum()).max(0.0)),
+                    rate(shift.now),
+                    unit,
+                    weeks
+                ),
+                shift.now,
+            ),
+        };
+        let current = data.iter().rfind(|x| !x.excluded)?.weight;
+        let against_goal = match self.config.goal.target {
+            Some(target) => (target - current) * heading < 0.0,
+            None => shift.kind == TrendShiftKind::Reversed,
+        };
+        Some((msg, if against_goal { MessageType::Warning } else { MessageType::Info }))
+    }
+
+    pub fn goal_countdown(&self) -> Option<GoalCountdown> {
+        let goal = &self.config.goal;
+        goal_countdown(&self.stats_data(), goal.start, goal.target?, goal.trend_alpha)
+    }
+
+    /// What is left to the goal, e.g. "12.9 kg (71%)" of the planned change,
+    /// or "reached" once the trend got there.
+    pub fn countdown_text(&self, countdown: &GoalCountdown) -> String {
+        if countdown.remaining <= 0.0 {
+            return String::from("reached");
+        }
+        let remaining = self.localize(WEIGHT.format(countdown.remaining));
+        format!("{} {} ({:.0}%)", remaining, self.config.display.unit.name(), countdown.percent)
+    }
+
+    pub fn is_modified(&self) -> bool {
+        *self.data.borrow() != self.saved_data
+    }
+
+    /// Stages every following edit until `commit_staged` or `abort_staged`.
+    pub fn start_staging(&mut self) {
+        self.staging = Some(Staging { trash: self.trash.clone(), session: self.session.clone() });
+    }
+
+    pub fn pending_changes(&self) -> Vec<PendingChange> {
+        pending_changes(&self.saved_data, &self.data.borrow())
+    }
+
+    /// Compares the data with the file at `path`: what merging in that copy
+    /// would add, remove or change.
+    pub fn open_diff(&mut self, path: &str) -> Result<(), WtError> {
+        let mut theirs = read_entries(path)?;
+        theirs.iter_mut().for_each(|x| x.normalize(self.config.display.unit));
+        let changes = pending_changes(&self.data.borrow(), &theirs);
+        info!("compared with {}: {} differences", path, changes.len());
+        self.diff_state.select((!changes.is_empty()).then_some(0));
+        self.diff = Some((path.to_string(), changes));
+        Ok(())
+    }
+
+    /// Saves the staged changes and keeps staging the next ones.
+    pub fn commit_staged(&mut self) -> Result<Option<MergeReport>, WtError> {
+        let count = self.pending_changes().len();
+        let merge = self.save()?;
+        self.start_staging();
+        info!("committed {} staged changes", count);
+        self.messages.push(format!("Committed {} changes", count), MessageType::Info);
+        Ok(merge)
+    }
+
+    /// Drops the staged changes, putting back the data and trash as last saved.
+    pub fn abort_staged(&mut self) {
+        let Some(staging) = &self.staging else {
+            return;
+        };
+        let count = self.pending_changes().len();
+        let selected = self.table_state.selected();
+        let selected = selected.and_then(|i| self.data.borrow().get(i).map(|x| x.date.clone()));
+        self.trash = staging.trash.clone();
+        self.session = staging.session.clone();
+        *self.data.get_mut() = self.saved_data.clone();
+        self.deleted = self.saved_deleted.clone();
+        self.averages = None;
+        self.rates = None;
+        let data_ref = self.data.borrow();
+        let row = selected.and_then(|date| data_ref.iter().position(|x| x.date == date));
+        let row = row.or(data_ref.len().checked_sub(1));
+        drop(data_ref);
+        self.table_state.select(row);
+        self.messages.push(format!("Discarded {} staged changes", count), MessageType::Info);
+    }
+
+    /// Writes the data to storage, first merging in changes another device
+    /// made to it since it was loaded or last saved.
+    pub fn save(&mut self) -> Result<Option<MergeReport>, WtError> {
+        let changed = *self.data.borrow() != self.saved_data;
+        self.data.get_mut().iter_mut().for_each(Entry::assign_id);
+        let mut rows = with_tombstones(&self.data.borrow(), &self.deleted);
+        let base = with_tombstones(&self.saved_data, &self.saved_deleted);
+        let merge = self.storage.save(&mut rows, &base)?;
+        (*self.data.get_mut(), self.deleted) = split_tombstones(rows);
+        if merge.is_some() {
+            // Rows merged from disk are read as stored.
+            let unit = self.config.display.unit;
+            self.data.get_mut().iter_mut().for_each(|x| x.normalize(unit));
+            self.averages = None;
+            self.rates = None;
+        }
+        self.saved_data = self.data.borrow().clone();
+        self.saved_deleted = self.deleted.clone();
+        if self.trash_changed {
+            let mut state = self.load_state()?;
+            state.trash = self.trash.clone();
+            self.save_state(&state)?;
+            self.trash_changed = false;
+        }
+        if (changed || merge.is_some()) && self.config.remote.as_ref().is_some_and(|x| x.on_save) {
+            // The local save stands even if the upload fails.
+            if let Err(e) = self.upload(&self.storage.location()) {
+                warn!("remote backup failed: {}", e);
+                self.messages.push(format!("Remote backup failed: {}", e), MessageType::Error);
+            }
+        }
+        self.write_auto_exports();
+        Ok(merge)
+    }
+
+    /// Rewrites the `[[auto_export]]` copies of the data. A copy that can't
+    /// be written is reported, the save stands.
+    fn write_auto_exports(&mut self) {
+        let today = self.today();
+        let mut failed = Vec::new();
+        for export in &self.config.auto_export {
+            let path = expand_home(export.path.clone());
+            let text = export::render(export.format, &self.data.borrow(), self.config.display.unit, today);
+            let dir = Path::new(&path).parent().map_or(Ok(()), fs::create_dir_all);
+            match dir.and_then(|()| fs::write(&path, text)) {
+                Ok(()) => info!("exported to {}", path),
+                Err(e) => failed.push(format!("Auto-export to {} failed: {}", path, e)),
+            }
+        }
+        for msg in failed {
+            warn!("{}", msg);
+            self.messages.push(msg, MessageType::Error);
+        }
+    }
+
+    /// Uploads the file at `path` to the configured remote under its file
+    /// name and returns where it went.
+    pub fn upload(&self, path: &str) -> Result<String, WtError> {
+        let Some(remote) = &self.config.remote else {
+            return Err(WtError::Config(String::from("no [remote] is configured")));
+        };
+        let body = fs::read(path).map_err(|e| WtError::Storage(format!("cannot read {}: {}", path, e)))?;
+        let name = Path::new(path).file_name().map_or(path.into(), |x| x.to_string_lossy());
+        remote::upload(remote, &name, &body)
+    }
+
+    /// Writes the weight logged on `date` into that day's note and returns
+    /// the path of the note.
+    pub fn write_daily_note(&self, date: NaiveDate) -> Result<String, WtError> {
+        let Some(note) = &self.config.daily_note else {
+            return Err(WtError::Config(String::from("no [daily_note] is configured")));
+        };
+        let data_ref = self.data.borrow();
+        let Ok(idx) = find_entry(&data_ref, date) else {
+            return Err(WtError::Validation(format!("No entry for {}", date.format("%d-%m-%Y"))));
+        };
+        let weight = self.format_weight(data_ref[idx].weight);
+        let line = note_line(&note.template, date, &weight, self.config.display.unit);
+        let path = expand_home(date.format(&note.path).to_string());
+        let to_storage = |e: io::Error| WtError::Storage(format!("cannot write {}: {}", path, e));
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(to_storage(e)),
+        };
+        if let Some(dir) = Path::new(&path).parent() {
+            fs::create_dir_all(dir).map_err(to_storage)?;
+        }
+        fs::write(&path, update_note(&text, &note.template, &line)).map_err(to_storage)?;
+        info!("wrote {} to {}", line, path);
+        Ok(path)
+    }
+
+    /// Puts the trashed entry at `idx` back into the data, unless an entry
+    /// for its date has been added since.
+    pub fn restore_trashed(&mut self, idx: usize) -> Result<(), WtError> {
+        let Some(trashed) = self.trash.get(idx) else {
+            return Err(WtError::Validation(String::from("No entry is selected.")));
+        };
+        let mut entry = Entry::from_fields(&trashed.entry)
+            .ok_or_else(|| WtError::Validation(format!("Cannot read trashed entry '{}'", trashed.entry)))?;
+        entry.normalize(self.config.display.unit);
+        let (date, data_ref) = (entry.date.clone(), self.data.get_mut());
+        if !insert_entry(data_ref, entry) {
+            return Err(WtError::Validation(format!("An entry for {} already exists", date)));
+        }
+        let row = data_ref.iter().position(|x| x.date == date);
+        self.table_state.select(row);
+        self.trash.remove(idx);
+        self.trash_changed = true;
+        self.averages = None;
+        self.rates = None;
+        self.journal(std::slice::from_ref(&date));
+        self.messages.push(format!("Restored {}", date), MessageType::Info);
+        Ok(())
+    }
+
+    pub fn empty_trash(&mut self) {
+        info!("emptying trash with {} entries", self.trash.len());
+        self.trash.clear();
+        self.trash_state.select(None);
+        self.trash_changed = true;
+    }
+
+    /// Drops trashed entries deleted more than `trash.retention_days` ago.
+    pub fn purge_trash(&mut self) {
+        let days = self.config.trash.retention_days;
+        if days <= 0 {
+            return;
+        }
+        let today = self.today();
+        let before = self.trash.len();
+        self.trash.retain(|x| {
+            NaiveDate::parse_from_str(&x.deleted, "%d-%m-%Y").is_ok_and(|d| (today - d).num_days() <= days)
+        });
+        if self.trash.len() != before {
+            info!("purged {} entries from the trash", before - self.trash.len());
+            self.trash_changed = true;
+        }
+    }
+
+    fn state_path(&self) -> PathBuf {
+        Path::new(&self.path).with_file_name("state.toml")
+    }
+
+    pub fn load_state(&self) -> Result<State, WtError> {
+        let path = self.state_path();
+        if !path.exists() {
+            return Ok(State::default());
+        }
+        let text = fs::read_to_string(&path)?;
+        toml::from_str(&text).map_err(|e| WtError::Storage(format!("{}: {}", path.display(), e.message())))
+    }
+
+    fn save_state(&self, state: &State) -> Result<(), WtError> {
+        let text = toml::to_string(state).map_err(|e| WtError::Storage(e.to_string()))?;
+        fs::write(self.state_path(), text)?;
+        Ok(())
+    }
+
+    pub fn view_state(&self) -> ViewState {
+        let selected = self.table_state.selected();
+        let format = |date: NaiveDate| date.format("%d-%m-%Y").to_string();
+        ViewState {
+            selected: selected.and_then(|idx| Some(self.data.borrow().get(idx)?.date.clone())),
+            frame: self.current_frame,
+            timeframe: self.current_tf,
+            month: format(self.selected_date_m),
+            year: format(self.selected_date_y),
+            window_year: format(self.selected_date_wy),
+        }
+    }
+
+    /// Restores a saved view. The row closest to the saved date is selected
+    /// if that entry has been deleted since.
+    pub fn restore_view(&mut self, view: &ViewState) {
+        let parse = |date: &str| NaiveDate::parse_from_str(date, "%d-%m-%Y").ok();
+        let len = self.data.borrow().len();
+        if let Some(date) = view.selected.as_deref().and_then(parse) {
+            let idx = match find_entry(&self.data.borrow(), date) {
+                Ok(idx) | Err(idx) => idx.min(len.saturating_sub(1)),
+            };
+            self.table_state.select((len > 0).then_some(idx));
+        }
+        self.current_frame = view.frame;
+        self.current_tf = view.timeframe;
+        self.selected_date_m = parse(&view.month).unwrap_or(self.selected_date_m);
+        self.selected_date_y = parse(&view.year).unwrap_or(self.selected_date_y);
+        self.selected_date_wy = parse(&view.window_year).unwrap_or(self.selected_date_wy);
+    }
+
+    /// Remembers the current view for the next start.
+    pub fn save_view(&self) -> Result<(), WtError> {
+        let mut state = self.load_state()?;
+        state.view = Some(self.view_state());
+        self.save_state(&state)
+    }
+
+    /// Closes the weekly summary and remembers not to show it again this week.
+    pub fn dismiss_weekly_summary(&mut self) {
+        self.current_window = WindowType::MainWindow;
+        self.hint_page = 0;
+        let week = week_label(self.today().iso_week());
+        let ret = self.load_state().and_then(|mut state| {
+            state.dismissed_week = Some(week);
+            self.save_state(&state)
+        });
+        if let Err(e) = ret {
+            self.show_error(e);
+        }
+    }
+
+    /// Archives the result of every challenge that has ended since the last run.
+    pub fn archive_challenges(&mut self) {
+        let today = self.today();
+        let ret = self.load_state().and_then(|mut state| {
+            let data = self.stats_data();
+            let mut archived = false;
+            for challenge in self.challenges.iter().filter(|c| c.end < today) {
+                let start = challenge.start.format("%d-%m-%Y").to_string();
+                if state.challenges.iter().any(|r| r.name == challenge.name && r.start == start) {
+                    continue;
+                }
+                let change = challenge_progress(challenge, &data, today).change();
+                info!("challenge '{}' ended with change {:?}", challenge.name, change);
+                state.challenges.push(ChallengeResult {
+                    name: challenge.name.clone(),
+                    start,
+                    end: challenge.end.format("%d-%m-%Y").to_string(),
+                    target: challenge.change,
+                    change,
+                    success: change.is_some_and(|c| challenge.reached(c)),
+                });
+                archived = true;
+            }
+            if archived {
+                self.save_state(&state)?;
+            }
+            Ok(state.challenges)
+        });
+        match ret {
+            Ok(results) => self.challenge_results = results,
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    fn is_earned(&self, achievement: &Achievement) -> bool {
+        let data = self.stats_data();
+        let included = data.iter().filter(|x| !x.excluded).collect::<Vec<_>>();
+        let (Some(first), Some(last)) = (included.first(), included.last()) else {
+            return false;
+        };
+        match achievement.id {
+            "first_entry" => true,
+            "streak_30" => {
+                schedule_streak(&data, &self.schedule, self.today()) >= 30
+            }
+            "lost_5kg" => {
+                let five_kg = Unit::Kg.convert(5.0, self.config.display.unit);
+                included.iter().any(|x| first.weight - x.weight >= five_kg)
+            }
+            "entries_100" => data.len() >= 100,
+            "goal_reached" => self.config.goal.target.is_some_and(|target| {
+                if first.weight > target {
+                    last.weight <= target
+                } else {
+                    last.weight >= target
+                }
+            }),
+            _ => false,
+        }
+    }
+
+    /// Unlocks newly earned achievements, queues their popups and persists them.
+    fn check_achievements(&mut self) {
+        let today = self.today().format("%d-%m-%Y").to_string();
+        let mut unlocked = false;
+        for (i, achievement) in ACHIEVEMENTS.iter().enumerate() {
+            if !self.achievements.contains_key(achievement.id) && self.is_earned(achievement) {
+                self.achievements.insert(achievement.id.to_string(), today.clone());
+                self.pending_achievements.push_back(i);
+                unlocked = true;
+            }
+        }
+        if !unlocked {
+            return;
+        }
+        let ret = self.load_state().and_then(|mut state| {
+            state.achievements = self.achievements.clone();
+            self.save_state(&state)
+        });
+        if let Err(e) = ret {
+            self.show_error(e);
+        }
+    }
+
+    pub fn dismiss_achievement(&mut self) {
+        self.pending_achievements.pop_front();
+        if self.pending_achievements.is_empty() {
+            self.current_window = WindowType::MainWindow;
+            self.hint_page = 0;
+        }
+    }
+
+    /// Stamps the entries dated `dates` as modified now, or records their
+    /// deletion, and appends their current state to the journal.
+    pub fn journal(&mut self, dates: &[String]) {
+        let now = Utc::now();
+        for entry in self.data.get_mut().iter_mut().filter(|x| dates.contains(&x.date)) {
+            entry.touch(now);
+        }
+        record_deletions(&mut self.deleted, &self.data.borrow(), dates, now);
+        if !self.config.storage.journal || self.staging.is_some() || dates.is_empty() {
+            return;
+        }
+        let data_ref = self.data.borrow();
+        let changes = dates.iter().map(|date| Change::of(&data_ref, date)).collect::<Vec<_>>();
+        drop(data_ref);
+        if let Err(e) = self.storage.append(&changes) {
+            self.show_error(e);
+        }
+    }
+
+    pub fn run(&mut self, term: &mut DefaultTerminal) -> Result<(), WtError> {
+        let tick_rate = Duration::from_micros(16667);
+        let mut now = Instant::now();
+        self.check_achievements();
+        while !self.close {
+            self.lock_if_idle();
+            self.step_import();
+            if self.current_window == WindowType::MainWindow && !self.pending_achievements.is_empty() {
+                self.current_window = WindowType::AchievementUnlocked;
+            }
+            term.draw(|f| self.draw(f))?;
+            let timeout = tick_rate.saturating_add(now.elapsed());
+            if event::poll(timeout)? {
+                match self.handle_events() {
+                    Ok(()) => self.check_achievements(),
+                    Err(e @ WtError::Storage(_)) => return Err(e),
+                    Err(e) => self.show_error(e),
+                }
+            }
+            if now.elapsed() >= tick_rate {
+                now = Instant::now();
+            }
+        }
+        Ok(())
+    }
+
+    /// Value of a variable in a derived column expression for a row with the given weight.
+    pub fn lookup_var(&self, name: &str, weight: f64) -> Option<f64> {
+        if name == "weight" {
+            Some(weight)
+        } else {
+            self.config.constants.get(name).copied()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AutoExportConfig, ExportFormat};
+    use crate::data::Entry;
+    use crate::storage::{CsvStorage, MemoryStorage};
+
+    fn entries(items: &[(&str, f64)]) -> Vec<Entry> {
+        items.iter().map(|(d, w)| Entry::new(d.to_string(), *w)).collect()
+    }
+
+    #[test]
+    fn report_row_fills_width() {
+        assert_eq!(report_row("Weight", "79.5", 16), "Weight      79.5");
+    }
+
+    #[test]
+    fn stones_only_apply_to_pounds() {
+        let mut app = App::default_test();
+        app.config.display.stones = true;
+        assert_eq!(app.format_weight(174.4), "174.4");
+        assert!(app.parse_weight("12st6").is_err());
+        app.config.display.unit = Unit::Lb;
+        app.decimal_sep = ',';
+        assert_eq!(app.format_weight(174.4), "12 st 6,4 lb");
+        assert_eq!(app.parse_weight("12 st 6,4"), Ok(174.4));
+        assert_eq!(app.parse_weight("174,4"), Ok(174.4));
+        assert!(app.parse_weight("12st6.45").is_err());
+    }
+
+    #[test]
+    fn delocalize_accepts_either_separator() {
+        let mut app = App::default_test();
+        for sep in ['.', ','] {
+            app.decimal_sep = sep;
+            assert_eq!(WEIGHT.validate(&app.delocalize("82.4")), Ok(82.4));
+            assert_eq!(WEIGHT.validate(&app.delocalize("82,4")), Ok(82.4));
+        }
+    }
+
+    #[test]
+    fn select_entry_edits_and_deletes_by_date() {
+        let mut app = App::default_test();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        assert!(app.select_entry(day(4)).is_err());
+        assert_eq!(app.select_entry(day(2)).unwrap(), 3);
+        assert!(app.modify_data((String::from("02-05-2024"), Some((84.0, None)))));
+        assert_eq!(app.data.borrow()[3].weight, 84.0);
+        app.select_entry(day(2)).unwrap();
+        assert!(app.modify_data((String::from("02-05-2024"), None)));
+        assert_eq!(app.data.borrow().len(), 4);
+        assert_eq!(app.trash.len(), 1);
+        assert_eq!((app.session.edited, app.session.deleted), (1, 1));
+    }
+
+    #[test]
+    fn reminders_adapt_to_the_goal() {
+        let mut app = App::default_test();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        app.schedule = vec![Weekday::Mon];
+        assert!(!app.reminder_due(day(4)));
+        app.config.schedule.adaptive = true;
+        app.config.goal.target = Some(80.0);
+        assert!(app.goal_active());
+        assert!(app.reminder_due(day(4)));
+
+        // Maintenance: weekly, counted from the last entry on 03-05.
+        app.config.goal.target = None;
+        assert!(!app.goal_active());
+        assert!(!app.reminder_due(day(9)));
+        assert!(app.reminder_due(day(10)));
+        app.quiet_days = vec![Weekday::Fri];
+        assert!(!app.reminder_due(day(10)));
+        assert!(app.reminder_due(day(11)));
+    }
+
+    #[test]
+    fn device_offsets_are_taken_out_of_the_stats() {
+        let mut app = App::default_test();
+        app.config.devices.offsets.insert(String::from("gym"), 0.6);
+        app.data.get_mut()[3].device = Some(String::from("gym"));
+        app.data.get_mut()[4].device = Some(String::from("home"));
+        assert_eq!(app.stats_data()[3].weight, 94.1);
+        app.config.devices.normalize = true;
+        let stats = app.stats_data();
+        assert!((stats[3].weight - 93.5).abs() < 1e-9);
+        assert_eq!((stats[2].weight, stats[4].weight), (91.5, 87.9));
+        assert_eq!(app.data.borrow()[3].weight, 94.1);
+
+        app.config.devices.default = Some(String::from("home"));
+        app.table_state.select(Some(0));
+        app.text_mode = Some(TextMode::Append);
+        app.modify_data((String::from("04-05-2024"), Some((88.0, None))));
+        assert_eq!(app.data.borrow()[5].device.as_deref(), Some("home"));
+    }
+
+    #[test]
+    fn report_row_truncates_long_label() {
+        assert_eq!(report_row("7-day change", "-0.5", 10), "7-day -0.5");
+    }
+
+    #[test]
+    fn app_replays_appended_changes_until_saved() {
+        let mut app = App::default_test();
+        app.config.storage.journal = true;
+        app.storage = Box::new(MemoryStorage {
+            saved: entries(&[("01-01-2024", 80.0), ("02-01-2024", 79.5)]),
+            ..Default::default()
+        });
+        app.load().unwrap();
+        assert!(!app.is_modified());
+
+        app.data.get_mut().remove(0);
+        app.data.get_mut().push(Entry::new(String::from("03-01-2024"), 79.0));
+        app.journal(&[String::from("01-01-2024"), String::from("03-01-2024")]);
+
+        // A session that did not exit cleanly picks the changes back up.
+        let expected = entries(&[("02-01-2024", 79.5), ("03-01-2024", 79.0)]);
+        app.data = RefCell::new(Vec::new());
+        app.load().unwrap();
+        assert!(*app.data.borrow() == expected);
+        assert!(app.is_modified());
+        assert!(!app.messages.is_empty());
+
+        app.save().unwrap();
+        let loaded = app.storage.load().unwrap();
+        assert!(loaded.pending.is_empty());
+        let (saved, deleted) = split_tombstones(loaded.saved);
+        assert!(saved == expected);
+        assert_eq!(deleted.iter().map(|x| x.date.as_str()).collect::<Vec<_>>(), ["01-01-2024"]);
+        assert!(!app.is_modified());
+    }
+
+    #[test]
+    fn staged_edits_wait_for_a_commit() {
+        let mut app = App::default_test();
+        app.config.storage.journal = true;
+        app.storage = Box::new(MemoryStorage {
+            saved: entries(&[("01-01-2024", 80.0), ("02-01-2024", 79.5)]),
+            ..Default::default()
+        });
+        app.load().unwrap();
+        app.start_staging();
+        app.select_entry(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()).unwrap();
+        app.modify_data((String::new(), Some((80.5, None))));
+        app.select_entry(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()).unwrap();
+        app.modify_data((String::new(), None));
+        assert!(app.storage.load().unwrap().pending.is_empty());
+        assert_eq!(app.pending_changes().len(), 2);
+        assert_eq!((app.trash.len(), app.session.deleted), (1, 1));
+
+        app.abort_staged();
+        assert!(!app.is_modified());
+        assert_eq!((app.trash.len(), app.session.deleted), (0, 0));
+        // Keeps the commit below from writing `state.toml`.
+        app.trash_changed = false;
+
+        app.select_entry(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()).unwrap();
+        app.modify_data((String::new(), Some((79.0, None))));
+        app.commit_staged().unwrap();
+        let weights = app.storage.load().unwrap().saved.iter().map(|x| x.weight).collect::<Vec<_>>();
+        assert_eq!(weights, [80.0, 79.0]);
+        assert!(app.pending_changes().is_empty() && app.staging.is_some());
+    }
+
+    #[test]
+    fn message_queue_shows_errors_first_then_in_order() {
+        let mut queue = MessageQueue::default();
+        queue.set_progress(Some(String::from("Importing")));
+        assert_eq!(queue.current(), Some(("Importing", MessageType::Info)));
+        queue.push("saved", MessageType::Info);
+        queue.push("disk full", MessageType::Error);
+        queue.push("marker: dot", MessageType::Info);
+        queue.push("stale", MessageType::Warning);
+        assert_eq!(queue.current(), Some(("disk full", MessageType::Error)));
+        queue.dismiss();
+        assert_eq!(queue.current(), Some(("stale", MessageType::Warning)));
+        let rest = queue.take_all().into_iter().map(|x| x.0).collect::<Vec<_>>();
+        assert_eq!(rest, ["stale", "saved", "marker: dot"]);
+        assert_eq!(queue.current(), Some(("Importing", MessageType::Info)));
+    }
+
+    #[test]
+    fn restore_view_selects_nearest_row() {
+        let mut app = App::default_test();
+        app.table_state.select(Some(2));
+        app.current_frame = FrameType::Chart;
+        app.current_tf = ChartTimeFrame::Year;
+        app.selected_date_y = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        let view = toml::to_string(&app.view_state()).unwrap();
+        let view = toml::from_str::<ViewState>(&view).unwrap();
+
+        let mut app = App::default_test();
+        app.data.get_mut().remove(2);
+        app.restore_view(&view);
+        assert_eq!(app.table_state.selected(), Some(2));
+        assert_eq!(app.data.borrow()[2].date, "02-05-2024");
+        assert!(app.current_frame == FrameType::Chart && app.current_tf == ChartTimeFrame::Year);
+        assert_eq!(app.selected_date_y, NaiveDate::from_ymd_opt(2023, 6, 1).unwrap());
+    }
+
+    #[test]
+    fn deleted_entries_can_be_restored_until_purged() {
+        let mut app = App::default_test();
+        app.today = NaiveDate::from_ymd_opt(2024, 5, 10);
+        app.data.get_mut()[1].rhr = Some(58.0);
+        app.table_state.select(Some(1));
+        app.modify_data((String::new(), None));
+        app.table_state.select(Some(0));
+        app.modify_data((String::new(), None));
+        assert_eq!(app.data.borrow().len(), 3);
+        assert_eq!(app.trash[0].deleted, "10-05-2024");
+
+        app.restore_trashed(0).unwrap();
+        let data_ref = app.data.borrow();
+        assert_eq!(data_ref[0].date, "27-04-2024");
+        assert_eq!(data_ref[0].rhr, Some(58.0));
+        assert_eq!(app.table_state.selected(), Some(0));
+        drop(data_ref);
+
+        app.data.get_mut().insert(0, Entry::new(String::from("26-04-2024"), 90.0));
+        assert!(app.restore_trashed(0).is_err());
+        assert_eq!(app.trash.len(), 1);
+
+        app.today = NaiveDate::from_ymd_opt(2024, 6, 9);
+        app.purge_trash();
+        assert_eq!(app.trash.len(), 1);
+        app.today = NaiveDate::from_ymd_opt(2024, 6, 10);
+        app.purge_trash();
+        assert!(app.trash.is_empty());
+    }
+
+    #[test]
+    fn trend_alerts_depend_on_the_goal() {
+        // Losing 0.1 a day for four weeks, then changing by `late` a day.
+        let data = |late: f64| {
+            let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+            let weight = |day: u64| 90.0 - 0.1 * day.min(28) as f64 + late * day.saturating_sub(28) as f64;
+            let date = |day: u64| (start + Days::new(day)).format("%d-%m-%Y").to_string();
+            (1..=42).map(|day| Entry::new(date(day - 1), weight(day))).collect::<Vec<_>>()
+        };
+        let mut app = App::default_test();
+        *app.data.get_mut() = data(-0.03);
+        app.today = NaiveDate::from_ymd_opt(2024, 2, 11);
+        let msg = String::from("Rate of loss slowed from 0.7 to 0.2 kg/week over the past 2 weeks");
+        assert_eq!(app.trend_alert(), Some((msg, MessageType::Info)));
+        app.config.goal.target = Some(80.0);
+        assert_eq!(app.trend_alert().unwrap().1, MessageType::Warning);
+        *app.data.get_mut() = data(0.05);
+        let msg = String::from("Trend reversed over the past 2 weeks: gain of 0.4 kg/week after loss of 0.7");
+        assert_eq!(app.trend_alert(), Some((msg, MessageType::Warning)));
+        app.config.trend_alert.enabled = false;
+        assert_eq!(app.trend_alert(), None);
+    }
+
+    #[test]
+    fn saving_rewrites_the_auto_exports() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-auto-export-{}", std::process::id()));
+        let mut app = App::default_test();
+        let exports = [(ExportFormat::Json, "weight.json"), (ExportFormat::Markdown, "sub/report.md")];
+        for (format, name) in exports {
+            let path = dir.join(name).to_string_lossy().into_owned();
+            app.config.auto_export.push(AutoExportConfig { format, path });
+        }
+        app.storage = Box::new(MemoryStorage::default());
+        app.save().unwrap();
+        assert!(fs::read_to_string(dir.join("weight.json")).unwrap().contains("\"date\": \"2024-05-03\""));
+        assert!(fs::read_to_string(dir.join("sub/report.md")).unwrap().contains("| 03-05-2024 | 87.9 |"));
+        assert!(app.messages.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_data_file_is_recovered() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-recovery-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("weight-tracker.csv").to_string_lossy().into_owned();
+        let mut backup = Vec::new();
+        write_csv(&App::default_test().data.borrow(), &mut backup).unwrap();
+        fs::write(format!("{}.20240101-000000.bak", path), b"Date,Weight\n").unwrap();
+        fs::write(format!("{}.20240502-000000.bak", path), backup).unwrap();
+        fs::write(&path, b"\xff\xfe garbage").unwrap();
+        let mut app = App {
+            storage: Box::new(CsvStorage::new(path.clone(), 0)),
+            ..Default::default()
+        };
+        assert!(matches!(app.load(), Err(WtError::Storage(_))));
+
+        let newest = app.newest_backup().unwrap();
+        assert!(newest.ends_with("20240502-000000.bak"));
+        assert!(app.recover(Some(&format!("{}.missing.bak", path))).is_err());
+        app.recover(Some(&newest)).unwrap();
+        assert_eq!(app.data.borrow().len(), 5);
+        let broken = |dir: &std::path::Path| {
+            let names = fs::read_dir(dir).unwrap().map(|x| x.unwrap().file_name());
+            names.filter(|x| x.to_string_lossy().ends_with(".broken")).count()
+        };
+        assert_eq!(broken(&dir), 1);
+
+        fs::write(&path, [0x1f, 0x8b, 0, 0]).unwrap();
+        assert!(app.load().is_err());
+        app.recover(None).unwrap();
+        assert!(app.data.borrow().is_empty());
+        assert!(!std::path::Path::new(&path).exists());
+        assert_eq!(broken(&dir), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn garbage_data_file_reaches_recovery_unchanged() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-garbage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("weight-tracker.csv").to_string_lossy().into_owned();
+        let garbage = "not, a, data file\n01-01-2024; 80\n";
+        fs::write(&path, garbage).unwrap();
+        let mut app = App {
+            storage: Box::new(CsvStorage::new(path.clone(), 0)),
+            ..Default::default()
+        };
+        assert!(matches!(app.load(), Err(WtError::Parse { .. })));
+        assert_eq!(fs::read_to_string(&path).unwrap(), garbage);
+        assert!(app.newest_backup().is_none());
+        app.recover(None).unwrap();
+        assert!(app.data.borrow().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn losing_5kg_is_counted_in_the_display_unit() {
+        let mut app = App::default_test();
+        app.config.display.unit = Unit::Lb;
+        let lighter = ACHIEVEMENTS.iter().find(|x| x.id == "lost_5kg").unwrap();
+        *app.data.get_mut() = entries(&[("01-01-2024", 200.0), ("01-02-2024", 194.0)]);
+        assert!(!app.is_earned(lighter));
+        *app.data.get_mut() = entries(&[("01-01-2024", 200.0), ("01-02-2024", 188.0)]);
+        assert!(app.is_earned(lighter));
+    }
+
+    #[test]
+    fn settings_are_written_to_the_config_file() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-settings-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "[display]\naverage_column = true\n").unwrap();
+        let mut app = App::default_test();
+        app.config_path = path.to_string_lossy().into_owned();
+        app.change_setting(Setting::Theme, "monochrome").unwrap();
+        app.change_setting(Setting::GoalTarget, "82,5").unwrap();
+        assert!(app.theme == Theme::Monochrome && app.config.goal.target == Some(82.5));
+        assert!(app.change_setting(Setting::Height, "180").is_err());
+        assert!(app.change_setting(Setting::GoalTarget, "0,01").is_err());
+        assert!(app.change_setting(Setting::GoalTarget, "5000").is_err());
+        assert!(app.change_setting(Setting::DecimalSeparator, ";").is_err());
+
+        let text = fs::read_to_string(&path).unwrap();
+        let config = toml::from_str::<Config>(&text).unwrap();
+        assert!(config.display.average_column && config.display.theme == Theme::Monochrome);
+        assert_eq!(config.goal.target, Some(82.5));
+        app.change_setting(Setting::GoalTarget, "").unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("target"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_reduced_motion_setting_warns() {
+        let mut app = App::default_test();
+        let config = toml::from_str::<Config>("[display]\nreduced_motion = true\n").unwrap();
+        app.apply_config(config).unwrap();
+        let (msg, msg_type) = app.messages.current().unwrap();
+        assert!(msg.contains("reduced_motion") && msg_type == MessageType::Warning);
+    }
+
+    #[test]
+    fn changing_the_unit_converts_weights_and_goal() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-unit-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "[goal]\ntarget = 80.0\n").unwrap();
+        let mut app = App::default_test();
+        app.config_path = path.to_string_lossy().into_owned();
+        let trashed = app.data.borrow().last().unwrap().clone();
+        app.table_state.select(Some(app.data.borrow().len() - 1));
+        app.modify_data((String::new(), None));
+        app.saved_data = app.data.borrow().clone();
+        app.change_setting(Setting::Unit, "lb").unwrap();
+        assert!((app.data.borrow()[0].weight - 198.6).abs() < 0.1);
+        assert!((app.saved_data[0].weight - 198.6).abs() < 0.1);
+        assert!((app.config.goal.target.unwrap() - 176.4).abs() < 0.1);
+        let config = toml::from_str::<Config>(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert!((config.goal.target.unwrap() - 176.4).abs() < 0.1);
+
+        app.change_setting(Setting::Unit, "kg").unwrap();
+        assert!((app.data.borrow()[0].weight - 90.1).abs() < 1e-9);
+        assert!((app.config.goal.target.unwrap() - 80.0).abs() < 1e-9);
+
+
+        // Trashed rows were written without a unit, in the one they had.
+        app.change_setting(Setting::Unit, "lb").unwrap();
+        app.restore_trashed(0).unwrap();
+        let restored = app.data.borrow().iter().find(|x| x.date == trashed.date).unwrap().weight;
+        assert!((restored - Unit::Kg.convert(trashed.weight, Unit::Lb)).abs() < 1e-9);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
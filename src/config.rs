@@ -0,0 +1,986 @@
+use chrono::{Days, NaiveDate, Weekday};
+use directories::BaseDirs;
+use ratatui::{symbols::Marker, widgets::GraphType};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+use tracing::{debug, info};
+
+use crate::WtError;
+use crate::app::{ChartTimeFrame, InputField};
+use crate::data::{Challenge, Unit};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Named constants usable in derived column expressions (e.g. `height = 1.80`).
+    pub constants: BTreeMap<String, f64>,
+    pub derived: Vec<DerivedConfig>,
+    pub outliers: OutlierConfig,
+    pub storage: StorageConfig,
+    pub display: DisplayConfig,
+    pub chart: ChartConfig,
+    pub schedule: ScheduleConfig,
+    pub goal: GoalConfig,
+    pub staleness: StalenessConfig,
+    pub trend_alert: TrendAlertConfig,
+    pub trash: TrashConfig,
+    pub devices: DevicesConfig,
+    /// Off-site copy of the data file, uploaded on save and by `backup --remote`.
+    pub remote: Option<RemoteConfig>,
+    pub lock: LockConfig,
+    /// Org or Markdown daily note the day's weight is written into.
+    pub daily_note: Option<DailyNoteConfig>,
+    /// Date ranges whose entries are left out of statistics.
+    pub exclude: Vec<ExcludeRange>,
+    pub challenge: Vec<ChallengeConfig>,
+    /// Copies of the data in other formats, written on every save.
+    pub auto_export: Vec<AutoExportConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StalenessConfig {
+    /// Warn once the newest entry is older than this many days.
+    pub days: i64,
+}
+
+impl Default for StalenessConfig {
+    fn default() -> Self {
+        StalenessConfig { days: 3 }
+    }
+}
+
+/// Startup message when the weekly rate of the 7-day average changes a lot.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TrendAlertConfig {
+    pub enabled: bool,
+    /// How far back the rate is compared with.
+    pub weeks: u32,
+    /// Rates below this, in the display unit per week, count as flat.
+    pub min_rate: f64,
+    /// Factor by which the rate has to grow or shrink to be reported.
+    pub ratio: f64,
+}
+
+impl Default for TrendAlertConfig {
+    fn default() -> Self {
+        TrendAlertConfig {
+            enabled: true,
+            weeks: 2,
+            min_rate: 0.2,
+            ratio: 2.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TrashConfig {
+    /// Deleted entries are purged from the trash after this many days; `0`
+    /// keeps them forever.
+    pub retention_days: i64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig { retention_days: 30 }
+    }
+}
+
+/// Scales that read differently, e.g. a gym scale showing 0.6 kg more than
+/// the one at home.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DevicesConfig {
+    /// Device recorded for entries logged without naming one.
+    pub default: Option<String>,
+    /// How much each device reads above the true weight, in the display unit.
+    pub offsets: BTreeMap<String, f64>,
+    /// Subtract the offsets in statistics, the table and the chart.
+    pub normalize: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DailyNoteConfig {
+    /// Note file of a day, with `strftime` fields, e.g.
+    /// `~/notes/daily/%Y-%m-%d.md`.
+    pub path: String,
+    /// Line written into the note; `{date}`, `{weight}` and `{unit}` are
+    /// filled in.
+    pub template: String,
+    /// Also write the note whenever `add` logs a weight.
+    pub on_add: bool,
+}
+
+impl Default for DailyNoteConfig {
+    fn default() -> Self {
+        DailyNoteConfig {
+            path: String::new(),
+            template: String::from("Weight:: {weight} {unit}"),
+            on_add: false,
+        }
+    }
+}
+
+const PASSPHRASE_PREFIX: &str = "sha256:";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct LockConfig {
+    /// Salted hash of the passphrase asked for on start, as printed by
+    /// `weight-tracker passphrase`. No lock screen if unset.
+    pub passphrase: Option<String>,
+    /// Lock again after this many minutes without a key press; `0` only
+    /// locks on start.
+    pub idle_minutes: u64,
+}
+
+impl LockConfig {
+    pub fn verify(&self, passphrase: &str) -> bool {
+        let Some(stored) = &self.passphrase else {
+            return true;
+        };
+        let Some((salt, _)) = stored.strip_prefix(PASSPHRASE_PREFIX).and_then(|x| x.split_once(':')) else {
+            return false;
+        };
+        hash_passphrase(passphrase, salt) == *stored
+    }
+}
+
+/// `sha256:<salt>:<hex digest of the salt followed by the passphrase>`.
+pub fn hash_passphrase(passphrase: &str, salt: &str) -> String {
+    let digest = Sha256::digest(format!("{}{}", salt, passphrase).as_bytes());
+    let hex = digest.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    format!("{}{}:{}", PASSPHRASE_PREFIX, salt, hex)
+}
+
+/// Fields of the input popup: the date and weight, then the metrics listed
+/// in `display.input_metrics`.
+pub fn parse_input_fields(config: &DisplayConfig) -> Result<Vec<InputField>, WtError> {
+    let mut fields = vec![InputField::Date, InputField::Weight];
+    for name in &config.input_metrics {
+        let field = InputField::parse(name).ok_or_else(|| {
+            WtError::Config(format!("unknown input metric '{}', expected rhr or device", name))
+        })?;
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    Ok(fields)
+}
+
+/// Checks that `lock.passphrase` is a hash written by `hash_passphrase`.
+pub fn validate_lock(config: &LockConfig) -> Result<(), WtError> {
+    let Some(stored) = &config.passphrase else {
+        return Ok(());
+    };
+    let digest = stored
+        .strip_prefix(PASSPHRASE_PREFIX)
+        .and_then(|x| x.split_once(':'))
+        .map(|(_, digest)| digest);
+    if !digest.is_some_and(|x| x.len() == 64 && x.chars().all(|c| c.is_ascii_hexdigit())) {
+        return Err(WtError::Config(String::from(
+            "lock passphrase must be a hash printed by `weight-tracker passphrase`",
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteKind {
+    #[default]
+    Webdav,
+    S3,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    pub kind: RemoteKind,
+    /// WebDAV directory the files are put into, or the S3 endpoint.
+    pub url: String,
+    /// WebDAV credentials (HTTP basic auth).
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// S3 bucket, addressed path-style (`<url>/<bucket>/<file>`).
+    pub bucket: String,
+    pub region: String,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    /// Upload the data file whenever changes are saved.
+    pub on_save: bool,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        RemoteConfig {
+            kind: RemoteKind::Webdav,
+            url: String::new(),
+            username: None,
+            password: None,
+            bucket: String::new(),
+            region: String::from("us-east-1"),
+            access_key: None,
+            secret_key: None,
+            on_save: true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExcludeRange {
+    /// First excluded day, `dd-mm-yyyy`.
+    pub from: String,
+    /// Last excluded day (inclusive), `dd-mm-yyyy`.
+    pub to: String,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Ics,
+    Markdown,
+}
+
+#[derive(Deserialize)]
+pub struct AutoExportConfig {
+    pub format: ExportFormat,
+    /// File rewritten on every save; `~/` is expanded.
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChallengeConfig {
+    pub name: String,
+    /// First day, `dd-mm-yyyy`.
+    pub start: String,
+    pub weeks: u32,
+    /// Target weight change over the whole challenge, e.g. `-3.0`.
+    pub change: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GoalConfig {
+    pub target: Option<f64>,
+    /// Weight the plan started from, for the share of it still to go. The
+    /// first entry if not set.
+    pub start: Option<f64>,
+    /// Distance between intermediate milestones on the way to `target`.
+    pub milestone_step: f64,
+    /// Smoothing factor of the trend whose crossing of `target` counts as
+    /// the trend goal.
+    pub trend_alpha: f64,
+}
+
+impl Default for GoalConfig {
+    fn default() -> Self {
+        GoalConfig {
+            target: None,
+            start: None,
+            milestone_step: 1.0,
+            trend_alpha: 0.1,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Weekdays (e.g. `["mon", "thu"]`) on which a weigh-in is expected.
+    /// Empty means every day.
+    pub days: Vec<String>,
+    /// Remind daily while the trend is short of the goal and weekly once it's
+    /// reached, instead of following `days`.
+    pub adaptive: bool,
+    /// Weekdays on which no reminder is shown.
+    pub quiet_days: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkerKind {
+    Dot,
+    Braille,
+    Block,
+    HalfBlock,
+}
+
+/// Bar charts are left out until the upstream rendering bug in ratatui 0.29 is fixed.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphKind {
+    Scatter,
+    Line,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct ChartStyle {
+    pub marker: MarkerKind,
+    pub graph_type: GraphKind,
+}
+
+impl Default for ChartStyle {
+    fn default() -> Self {
+        ChartStyle {
+            marker: MarkerKind::Dot,
+            graph_type: GraphKind::Scatter,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ChartConfig {
+    pub month: ChartStyle,
+    pub quarter: ChartStyle,
+    pub months: ChartStyle,
+    pub year: ChartStyle,
+    pub window_year: ChartStyle,
+    pub year_over_year: ChartStyle,
+    pub overview: ChartStyle,
+    pub padding: PaddingConfig,
+    pub colors: PointColors,
+    /// Length of the multi-month timeframe.
+    pub window_months: u32,
+    /// Points older than this many days are dimmed in the one year window;
+    /// `0` disables it.
+    pub fade_after_days: i64,
+    /// Labels of the x-axis of the one year window.
+    pub x_labels: AxisLabels,
+    /// Band of one rolling standard deviation around the rolling average.
+    pub volatility: bool,
+    /// Line of the weights with the yearly seasonality removed.
+    pub seasonal: bool,
+    /// Days at the end of the overview drawn at daily resolution; older
+    /// entries are averaged per week.
+    pub overview_days: u64,
+    /// Title of the timeframe charts, with `{label}` (e.g. `May 2024`),
+    /// `{count}`, `{average}`, `{change}` and `{unit}` of the entries shown.
+    pub title: String,
+}
+
+impl Default for ChartConfig {
+    fn default() -> Self {
+        ChartConfig {
+            month: ChartStyle::default(),
+            quarter: ChartStyle::default(),
+            months: ChartStyle::default(),
+            year: ChartStyle::default(),
+            window_year: ChartStyle::default(),
+            year_over_year: ChartStyle::default(),
+            overview: ChartStyle::default(),
+            padding: PaddingConfig::default(),
+            colors: PointColors::default(),
+            window_months: 2,
+            fade_after_days: 90,
+            x_labels: AxisLabels::default(),
+            volatility: false,
+            seasonal: false,
+            overview_days: 90,
+            title: String::from("{label}"),
+        }
+    }
+}
+
+/// How the weight points of the chart are colored.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PointColors {
+    /// All points in one color.
+    #[default]
+    Plain,
+    /// From red for the point farthest from `goal.target` to green at it.
+    Goal,
+    /// Green for a move towards the goal (or down, without a goal) since
+    /// the previous point, red for a move away from it.
+    Change,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AxisLabels {
+    /// Calendar dates, e.g. `05-01-2024`.
+    #[default]
+    Dates,
+    /// Whole weeks before today, e.g. `-8w`, and `now`.
+    Relative,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PaddingMode {
+    /// `value` kg above and below the data.
+    #[default]
+    Absolute,
+    /// `value` percent of the weight above and below the data.
+    Percent,
+    /// `value` times the visible range above and below the data.
+    Snug,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PaddingConfig {
+    pub mode: PaddingMode,
+    /// Defaults to 2.0 for `absolute` and `percent`, 0.1 for `snug`.
+    pub value: Option<f64>,
+}
+
+impl PaddingConfig {
+    /// Y-axis bounds for data spanning `min` to `max`.
+    pub fn pad(&self, min: f64, max: f64) -> (f64, f64) {
+        match self.mode {
+            PaddingMode::Absolute => {
+                let v = self.value.unwrap_or(2.0);
+                (min - v, max + v)
+            }
+            PaddingMode::Percent => {
+                let v = self.value.unwrap_or(2.0) / 100.0;
+                (min - min.abs() * v, max + max.abs() * v)
+            }
+            PaddingMode::Snug => {
+                let pad = (max - min) * self.value.unwrap_or(0.1);
+                (min - pad, max + pad)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// `"auto"` (derived from the locale environment variables), `"."` or `","`.
+    /// Only affects what is shown and typed, the data file always uses `.`.
+    pub decimal_separator: String,
+    pub theme: Theme,
+    /// Show the rolling 7-day average next to the weight in the table.
+    pub average_column: bool,
+    /// Show where each entry came from in the table.
+    pub source_column: bool,
+    /// Apply the table filter to the chart as well.
+    pub linked_views: bool,
+    /// Unit weights are shown and typed in. Rows stored in another unit are
+    /// converted when loaded.
+    pub unit: Unit,
+    /// With `unit = "lb"`, show weights as stones and pounds (`12 st 6.4 lb`)
+    /// and accept them typed as `12st6`.
+    pub stones: bool,
+    /// Optional metrics (`"rhr"` or `"device"`) with a field in the input
+    /// popup, after the date and weight.
+    pub input_metrics: Vec<String>,
+    /// Removed: it stopped the hint line from scrolling, and hints are paged
+    /// now. Only read to warn configs that still set it.
+    pub reduced_motion: Option<bool>,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            decimal_separator: String::from("auto"),
+            theme: Theme::Default,
+            average_column: false,
+            source_column: false,
+            linked_views: false,
+            unit: Unit::Kg,
+            stones: false,
+            input_metrics: vec![String::from("rhr")],
+            reduced_motion: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Default,
+    /// No colors, ASCII-only borders and chart markers.
+    Monochrome,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// Append every change to a journal file immediately; the journal is
+    /// compacted into the data file on save.
+    pub journal: bool,
+    /// The data file is gzip-compressed once its CSV content exceeds this many
+    /// bytes. `0` disables compression.
+    pub compress_threshold: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            backend: StorageBackend::Csv,
+            journal: false,
+            compress_threshold: 4 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Csv,
+    Sqlite,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct OutlierConfig {
+    /// Smoothing factor of the EWMA the entries are compared against.
+    pub alpha: f64,
+    /// Entries further than `k` standard deviations from the EWMA are flagged.
+    pub k: f64,
+}
+
+impl Default for OutlierConfig {
+    fn default() -> Self {
+        OutlierConfig { alpha: 0.1, k: 3.0 }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DerivedConfig {
+    pub name: String,
+    pub expr: String,
+    #[serde(default = "default_true")]
+    pub table: bool,
+    #[serde(default)]
+    pub chart: bool,
+    #[serde(default)]
+    pub axis: AxisSide,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AxisSide {
+    #[default]
+    Left,
+    Right,
+}
+
+pub struct DerivedColumn {
+    pub name: String,
+    pub expr: Expr,
+    pub table: bool,
+    pub chart: bool,
+    pub axis: AxisSide,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Num(f64),
+    Var(String),
+    Neg(Box<Expr>),
+    Bin(char, Box<Expr>, Box<Expr>),
+    Call(String, Box<Expr>),
+}
+
+/// Decimal separator of the user's locale, read from `LC_ALL`, `LC_NUMERIC` or `LANG`.
+fn locale_decimal_separator() -> char {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|x| !x.is_empty())
+        .unwrap_or_default();
+    let lang = locale.split(['_', '.', '-']).next().unwrap_or_default();
+    if COMMA_LOCALES.contains(&lang) { ',' } else { '.' }
+}
+
+pub fn resolve_decimal_separator(config: &DisplayConfig) -> Result<char, WtError> {
+    match config.decimal_separator.as_str() {
+        "auto" => Ok(locale_decimal_separator()),
+        "." => Ok('.'),
+        "," => Ok(','),
+        x => Err(WtError::Config(format!("unknown decimal_separator '{}'", x))),
+    }
+}
+
+impl MarkerKind {
+    pub fn marker(self) -> Marker {
+        match self {
+            MarkerKind::Dot => Marker::Dot,
+            MarkerKind::Braille => Marker::Braille,
+            MarkerKind::Block => Marker::Block,
+            MarkerKind::HalfBlock => Marker::HalfBlock,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            MarkerKind::Dot => MarkerKind::Braille,
+            MarkerKind::Braille => MarkerKind::Block,
+            MarkerKind::Block => MarkerKind::HalfBlock,
+            MarkerKind::HalfBlock => MarkerKind::Dot,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MarkerKind::Dot => "dot",
+            MarkerKind::Braille => "braille",
+            MarkerKind::Block => "block",
+            MarkerKind::HalfBlock => "halfblock",
+        }
+    }
+}
+
+impl GraphKind {
+    pub fn graph_type(self) -> GraphType {
+        match self {
+            GraphKind::Scatter => GraphType::Scatter,
+            GraphKind::Line => GraphType::Line,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            GraphKind::Scatter => GraphKind::Line,
+            GraphKind::Line => GraphKind::Scatter,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GraphKind::Scatter => "scatter",
+            GraphKind::Line => "line",
+        }
+    }
+}
+
+impl ChartConfig {
+    pub fn style(&self, tf: &ChartTimeFrame) -> ChartStyle {
+        match tf {
+            ChartTimeFrame::Month => self.month,
+            ChartTimeFrame::Quarter => self.quarter,
+            ChartTimeFrame::Months => self.months,
+            ChartTimeFrame::Year => self.year,
+            ChartTimeFrame::WindowYear => self.window_year,
+            ChartTimeFrame::YearOverYear => self.year_over_year,
+            ChartTimeFrame::Overview => self.overview,
+        }
+    }
+
+    pub fn style_mut(&mut self, tf: &ChartTimeFrame) -> &mut ChartStyle {
+        match tf {
+            ChartTimeFrame::Month => &mut self.month,
+            ChartTimeFrame::Quarter => &mut self.quarter,
+            ChartTimeFrame::Months => &mut self.months,
+            ChartTimeFrame::Year => &mut self.year,
+            ChartTimeFrame::WindowYear => &mut self.window_year,
+            ChartTimeFrame::YearOverYear => &mut self.year_over_year,
+            ChartTimeFrame::Overview => &mut self.overview,
+        }
+    }
+}
+
+pub fn parse_schedule(config: &ScheduleConfig) -> Result<Vec<Weekday>, WtError> {
+    parse_weekdays(&config.days, "schedule day")
+}
+
+pub fn parse_quiet_days(config: &ScheduleConfig) -> Result<Vec<Weekday>, WtError> {
+    parse_weekdays(&config.quiet_days, "quiet day")
+}
+
+fn parse_weekdays(days: &[String], what: &str) -> Result<Vec<Weekday>, WtError> {
+    days.iter()
+        .map(|x| {
+            x.parse::<Weekday>()
+                .map_err(|_| WtError::Config(format!("unknown {} '{}'", what, x)))
+        })
+        .collect()
+}
+
+pub fn parse_exclusions(ranges: &[ExcludeRange]) -> Result<Vec<(NaiveDate, NaiveDate)>, WtError> {
+    let parse = |x: &str| {
+        NaiveDate::parse_from_str(x, "%d-%m-%Y")
+            .map_err(|_| WtError::Config(format!("invalid exclude date '{}'", x)))
+    };
+    ranges
+        .iter()
+        .map(|r| {
+            let (from, to) = (parse(&r.from)?, parse(&r.to)?);
+            if from > to {
+                return Err(WtError::Config(format!(
+                    "exclude range {} to {} ends before it starts",
+                    r.from, r.to
+                )));
+            }
+            Ok((from, to))
+        })
+        .collect()
+}
+
+pub fn parse_challenges(configs: &[ChallengeConfig]) -> Result<Vec<Challenge>, WtError> {
+    configs
+        .iter()
+        .map(|c| {
+            let start = NaiveDate::parse_from_str(&c.start, "%d-%m-%Y")
+                .map_err(|_| WtError::Config(format!("invalid challenge start '{}'", c.start)))?;
+            if c.weeks == 0 {
+                return Err(WtError::Config(format!("challenge '{}' must last at least a week", c.name)));
+            }
+            if !c.change.is_finite() || c.change == 0.0 {
+                return Err(WtError::Config(format!("challenge '{}' needs a non-zero change", c.name)));
+            }
+            Ok(Challenge {
+                name: c.name.clone(),
+                start,
+                end: start + Days::new(u64::from(c.weeks) * 7 - 1),
+                change: c.change,
+            })
+        })
+        .collect()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+pub fn get_config_file() -> Result<PathBuf, WtError> {
+    let base_dirs = BaseDirs::new()
+        .ok_or_else(|| WtError::Config(String::from("cannot find the home directory")))?;
+    let mut config_path = base_dirs.config_dir().to_path_buf();
+    config_path.push("weight-tracker");
+    config_path.push("config.toml");
+    Ok(config_path)
+}
+
+pub fn load_config() -> Result<Config, WtError> {
+    let path = get_config_file()?;
+    if !path.exists() {
+        debug!("no config file at {}, using defaults", path.display());
+        return Ok(Config::default());
+    }
+    info!("loading config from {}", path.display());
+    let text = fs::read_to_string(&path).map_err(|e| WtError::Config(e.to_string()))?;
+    toml::from_str(&text).map_err(|e| WtError::Config(e.message().to_string()))
+}
+
+/// Sets `key`, a dotted path like `display.unit`, in the TOML `text`, or
+/// removes it if `value` is `None`. Missing tables are created. Comments and
+/// layout of the text are not kept.
+pub fn set_config_value(text: &str, key: &str, value: Option<toml::Value>) -> Result<String, WtError> {
+    let mut root = text.parse::<toml::Table>().map_err(|e| WtError::Config(e.message().to_string()))?;
+    let (tables, name) = key.rsplit_once('.').map_or((None, key), |(t, n)| (Some(t), n));
+    let mut table = &mut root;
+    for part in tables.into_iter().flat_map(|x| x.split('.')) {
+        table = table
+            .entry(part)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| WtError::Config(format!("'{}' is not a table", part)))?;
+    }
+    match value {
+        Some(value) => table.insert(name.to_string(), value),
+        None => table.remove(name),
+    };
+    toml::to_string(&root).map_err(|e| WtError::Config(e.to_string()))
+}
+
+/// Small recursive-descent parser for derived column expressions.
+/// Supports `+ - * / ^`, unary minus, parentheses and one-argument functions.
+pub struct ExprParser {
+    pub chars: Vec<char>,
+    pub pos: usize,
+}
+
+const EXPR_FUNCTIONS: [&str; 6] = ["sqrt", "abs", "ln", "exp", "round", "floor"];
+
+impl ExprParser {
+    fn peek(&mut self) -> Option<char> {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), String> {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at position {}", ch, self.pos))
+        }
+    }
+
+    pub fn sum(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.product()?));
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some('-') {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.atom()?;
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            return Ok(Expr::Bin('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.sum()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(ch) if ch.is_ascii_digit() || ch == '.' => {
+                let start = self.pos;
+                while self.pos < self.chars.len()
+                    && (self.chars[self.pos].is_ascii_digit() || self.chars[self.pos] == '.')
+                {
+                    self.pos += 1;
+                }
+                let text = self.chars[start..self.pos].iter().collect::<String>();
+                text.parse::<f64>()
+                    .map(Expr::Num)
+                    .map_err(|_| format!("invalid number '{}'", text))
+            }
+            Some(ch) if ch.is_alphabetic() || ch == '_' => {
+                let start = self.pos;
+                while self.pos < self.chars.len()
+                    && (self.chars[self.pos].is_alphanumeric() || self.chars[self.pos] == '_')
+                {
+                    self.pos += 1;
+                }
+                let name = self.chars[start..self.pos].iter().collect::<String>();
+                if self.peek() == Some('(') {
+                    if !EXPR_FUNCTIONS.contains(&name.as_str()) {
+                        return Err(format!("unknown function '{}'", name));
+                    }
+                    self.pos += 1;
+                    let arg = self.sum()?;
+                    self.expect(')')?;
+                    Ok(Expr::Call(name, Box::new(arg)))
+                } else {
+                    Ok(Expr::Var(name))
+                }
+            }
+            Some(ch) => Err(format!("unexpected '{}' at position {}", ch, self.pos)),
+            None => Err(String::from("unexpected end of expression")),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Expr, String> {
+        let mut parser = ExprParser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.sum()?;
+        if let Some(ch) = parser.peek() {
+            return Err(format!("unexpected '{}' at position {}", ch, parser.pos));
+        }
+        Ok(expr)
+    }
+
+    fn variables<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::Num(_) => {}
+            Expr::Var(name) => out.push(name),
+            Expr::Neg(inner) | Expr::Call(_, inner) => inner.variables(out),
+            Expr::Bin(_, lhs, rhs) => {
+                lhs.variables(out);
+                rhs.variables(out);
+            }
+        }
+    }
+
+    pub fn eval(&self, vars: &dyn Fn(&str) -> Option<f64>) -> Option<f64> {
+        let ret = match self {
+            Expr::Num(n) => *n,
+            Expr::Var(name) => vars(name)?,
+            Expr::Neg(inner) => -inner.eval(vars)?,
+            Expr::Bin(op, lhs, rhs) => {
+                let (l, r) = (lhs.eval(vars)?, rhs.eval(vars)?);
+                match op {
+                    '+' => l + r,
+                    '-' => l - r,
+                    '*' => l * r,
+                    '/' => l / r,
+                    '^' => l.powf(r),
+                    _ => unreachable!("Invalid operator"),
+                }
+            }
+            Expr::Call(name, arg) => {
+                let x = arg.eval(vars)?;
+                match name.as_str() {
+                    "sqrt" => x.sqrt(),
+                    "abs" => x.abs(),
+                    "ln" => x.ln(),
+                    "exp" => x.exp(),
+                    "round" => x.round(),
+                    "floor" => x.floor(),
+                    _ => unreachable!("Invalid function"),
+                }
+            }
+        };
+        ret.is_finite().then_some(ret)
+    }
+}
+
+/// Compiles the `[[derived]]` config entries. Only `weight` and the names in
+/// `[constants]` may be referenced.
+pub fn compile_derived(config: &Config) -> Result<Vec<DerivedColumn>, WtError> {
+    config
+        .derived
+        .iter()
+        .map(|d| {
+            let expr = Expr::parse(&d.expr)
+                .map_err(|e| WtError::Config(format!("derived '{}': {}", d.name, e)))?;
+            let mut vars = Vec::new();
+            expr.variables(&mut vars);
+            if let Some(v) = vars
+                .iter()
+                .find(|v| **v != "weight" && !config.constants.contains_key(**v))
+            {
+                return Err(WtError::Config(format!(
+                    "derived '{}': unknown variable '{}'",
+                    d.name, v
+                )));
+            }
+            Ok(DerivedColumn {
+                name: d.name.clone(),
+                expr,
+                table: d.table,
+                chart: d.chart,
+                axis: d.axis,
+            })
+        })
+        .collect()
+}
+
+const COMMA_LOCALES: [&str; 16] = [
+    "de", "fr", "es", "it", "nl", "pt", "ru", "pl", "sv", "da", "nb", "fi", "cs", "tr", "uk", "id",
+];
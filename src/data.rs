@@ -0,0 +1,2242 @@
+use chrono::{DateTime, Datelike, Days, IsoWeek, Local, NaiveDate, SecondsFormat, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{collections::{BTreeMap, BTreeSet, HashSet}, fs, io::Read};
+use tracing::{debug, info};
+
+use crate::WtError;
+use crate::config::OutlierConfig;
+use crate::storage::decompress;
+
+#[derive(Clone)]
+pub struct Entry {
+    pub date: String,
+    pub weight: f64,
+    /// Excluded from trend computations (e.g. a scale glitch).
+    pub excluded: bool,
+    /// Resting heart rate in beats per minute, if logged.
+    pub rhr: Option<f64>,
+    /// Interpolated by `backfill` rather than measured.
+    pub estimated: bool,
+    /// Where the entry came from, e.g. `manual`, `import:apple-health` or
+    /// `ble-scale`. `None` for entries logged before sources were recorded.
+    pub source: Option<String>,
+    /// Scale the weight was taken on, e.g. `home` or `gym`, whose offset in
+    /// `[devices]` can be taken out of the statistics.
+    pub device: Option<String>,
+    /// Unit and weight as stored, for rows with a unit column. `weight` is
+    /// then converted to the display unit by `normalize`; rows without a unit
+    /// are in the display unit already.
+    pub original: Option<(Unit, f64)>,
+    /// Stable identifier, assigned on the first save.
+    pub id: Option<String>,
+    /// Last change, used to pick the newer side when merging.
+    pub modified: Option<DateTime<Utc>>,
+    /// Tombstone of a deleted entry, kept in the data file so that a merge
+    /// knows when it was deleted. Never part of the data shown or analyzed.
+    pub deleted: bool,
+}
+
+/// Rows with a unit column compare by their stored weight, so that rows read
+/// from disk equal their normalized copies. The id and modification time are
+/// bookkeeping and not compared.
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        let stored = |e: &Entry| e.original.map_or((None, e.weight), |(unit, w)| (Some(unit), w));
+        self.date == other.date
+            && stored(self) == stored(other)
+            && self.excluded == other.excluded
+            && self.rhr == other.rhr
+            && self.estimated == other.estimated
+            && self.source == other.source
+            && self.device == other.device
+            && self.deleted == other.deleted
+    }
+}
+
+/// Unit of a weight.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Kg,
+    Lb,
+}
+
+impl Unit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unit::Kg => "kg",
+            Unit::Lb => "lb",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Unit> {
+        match text.to_lowercase().as_str() {
+            "kg" => Some(Unit::Kg),
+            "lb" | "lbs" => Some(Unit::Lb),
+            _ => None,
+        }
+    }
+
+    /// `value` in this unit converted to `to`.
+    pub fn convert(&self, value: f64, to: Unit) -> f64 {
+        match (self, to) {
+            (Unit::Kg, Unit::Lb) => value * LB_PER_KG,
+            (Unit::Lb, Unit::Kg) => value / LB_PER_KG,
+            _ => value,
+        }
+    }
+}
+
+/// `lb` as stones and pounds to one decimal, e.g. `12 st 6.4 lb`.
+pub fn format_stones(lb: f64) -> String {
+    // Rounded first, so that 13.96 lb shows as `1 st 0.0 lb` and not `0 st 14.0 lb`.
+    let tenths = (lb * 10.0).round() as i64;
+    let per_stone = (LB_PER_ST * 10.0) as i64;
+    let (st, rest) = (tenths / per_stone, tenths % per_stone);
+    format!("{} st {}.{} lb", st, rest / 10, rest % 10)
+}
+
+/// Pounds of `12st6`, `12 st 6.4 lb` or `12st`; `None` for anything else,
+/// plain numbers included.
+pub fn parse_stones(text: &str) -> Option<f64> {
+    let text = text.trim().to_lowercase();
+    let (st, rest) = text.split_once("st")?;
+    let st = st.trim().parse::<u32>().ok()?;
+    let rest = rest.trim();
+    let rest = rest.strip_suffix("lbs").or_else(|| rest.strip_suffix("lb")).unwrap_or(rest).trim();
+    let lb = if rest.is_empty() { 0.0 } else { rest.parse::<f64>().ok()? };
+    (0.0..LB_PER_ST).contains(&lb).then_some(st as f64 * LB_PER_ST + lb)
+}
+
+/// Date column format of an imported file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DateFormat {
+    /// `%d-%m-%Y`, as written by `export`.
+    #[default]
+    Dmy,
+    /// ISO 8601 `%Y-%m-%d`, optionally followed by a time.
+    Iso,
+    DmySlash,
+    MdySlash,
+    /// Seconds since the Unix epoch, read in local time.
+    Epoch,
+}
+
+impl DateFormat {
+    /// Every format, in the order ties are broken when detecting.
+    pub const ALL: [DateFormat; 5] = [
+        DateFormat::Dmy,
+        DateFormat::Iso,
+        DateFormat::DmySlash,
+        DateFormat::MdySlash,
+        DateFormat::Epoch,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DateFormat::Dmy => "%d-%m-%Y",
+            DateFormat::Iso => "%Y-%m-%d",
+            DateFormat::DmySlash => "%d/%m/%Y",
+            DateFormat::MdySlash => "%m/%d/%Y",
+            DateFormat::Epoch => "epoch",
+        }
+    }
+
+    pub fn from_name(text: &str) -> Option<DateFormat> {
+        DateFormat::ALL.into_iter().find(|x| x.name() == text)
+    }
+
+    pub fn parse(&self, text: &str) -> Option<NaiveDate> {
+        match self {
+            DateFormat::Iso => {
+                // Drop the time of `2024-05-01T07:30:00Z` or `2024-05-01 07:30`.
+                let date = match text.char_indices().nth(10) {
+                    Some((i, 'T' | ' ')) => &text[..i],
+                    _ => text,
+                };
+                NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+            }
+            DateFormat::Epoch => {
+                let secs = text.parse::<i64>().ok()?;
+                Some(Local.timestamp_opt(secs, 0).single()?.date_naive())
+            }
+            _ => NaiveDate::parse_from_str(text, self.name()).ok(),
+        }
+    }
+}
+
+/// Validation and display rules of a logged metric.
+pub struct Metric {
+    pub name: &'static str,
+    pub min: f64,
+    pub max: f64,
+    /// Maximum number of decimals accepted on input and shown on output.
+    pub decimals: usize,
+}
+
+pub const WEIGHT: Metric = Metric {
+    name: "weight",
+    min: 0.1,
+    max: 1000.0,
+    decimals: 1,
+};
+
+pub const RHR: Metric = Metric {
+    name: "resting heart rate",
+    min: 20.0,
+    max: 250.0,
+    decimals: 0,
+};
+
+/// A target weight change within a fixed period, configured in `[[challenge]]`.
+pub struct Challenge {
+    pub name: String,
+    pub start: NaiveDate,
+    /// Last day of the challenge (inclusive).
+    pub end: NaiveDate,
+    pub change: f64,
+}
+
+pub struct ChallengeProgress {
+    pub baseline: Option<f64>,
+    /// Newest weight within the challenge period.
+    pub current: Option<f64>,
+    /// Days since the start including today, at most `total`.
+    pub elapsed: i64,
+    pub total: i64,
+}
+
+impl ChallengeProgress {
+    pub fn change(&self) -> Option<f64> {
+        Some(self.current? - self.baseline?)
+    }
+
+    pub fn remaining(&self) -> i64 {
+        self.total - self.elapsed
+    }
+}
+
+impl Challenge {
+    /// Weekly change needed over the whole period.
+    pub fn required_pace(&self, progress: &ChallengeProgress) -> f64 {
+        self.change / (progress.total as f64 / 7.0)
+    }
+
+    pub fn reached(&self, change: f64) -> bool {
+        if self.change < 0.0 {
+            change <= self.change
+        } else {
+            change >= self.change
+        }
+    }
+}
+
+/// Final result of a challenge, archived in `state.toml` once it has ended.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChallengeResult {
+    pub name: String,
+    pub start: String,
+    pub end: String,
+    pub target: f64,
+    pub change: Option<f64>,
+    pub success: bool,
+}
+
+/// Check-in summary of a completed ISO week.
+pub struct WeekSummary {
+    pub week: IsoWeek,
+    pub average: Option<f64>,
+    /// Difference to the average of the week before.
+    pub change: Option<f64>,
+    pub logged: usize,
+    pub scheduled: usize,
+}
+
+/// Outcome of merging changes made to the data file by another device.
+#[derive(Default, Debug, PartialEq)]
+pub struct MergeReport {
+    /// Dates taken from the file on disk.
+    pub merged: usize,
+    /// Dates changed or deleted both here and on disk; the more recent change
+    /// was kept.
+    pub conflicts: usize,
+}
+
+#[derive(Default)]
+pub struct ImportReport {
+    pub added: usize,
+    pub duplicates: usize,
+    /// Line number (1-based) and reason of every rejected line.
+    pub failed: Vec<(usize, String)>,
+    /// Line number and text of rows without any number, such as the table
+    /// titles and repeated headers of spreadsheet exports.
+    pub skipped: Vec<(usize, String)>,
+    /// Lines whose empty trailing columns were dropped.
+    pub stripped: usize,
+}
+
+impl Entry {
+    pub fn new(date: String, weight: f64) -> Self {
+        Entry {
+            date,
+            weight,
+            excluded: false,
+            rhr: None,
+            estimated: false,
+            source: None,
+            device: None,
+            original: None,
+            id: None,
+            modified: None,
+            deleted: false,
+        }
+    }
+
+    /// Tombstone recording that the entry dated `date` was deleted at `now`.
+    pub fn tombstone(date: String, now: DateTime<Utc>) -> Self {
+        let mut entry = Entry::new(date, 0.0);
+        entry.deleted = true;
+        entry.touch(now);
+        entry
+    }
+
+    /// Marks the entry as changed at `now`, giving it an id if it has none.
+    pub fn touch(&mut self, now: DateTime<Utc>) {
+        self.modified = Some(now);
+        self.assign_id();
+    }
+
+    pub fn assign_id(&mut self) {
+        self.id.get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+    }
+
+    /// `date, weight` followed by the flags, as stored in the data file.
+    /// Tombstones are `date, deleted` followed by the id and modification time.
+    pub fn to_fields(&self) -> String {
+        let weight = self.original.map_or(self.weight, |(_, w)| w);
+        let mut text = match self.deleted {
+            true => format!("{}, {}", self.date, FLAG_DELETED),
+            false => format!("{}, {}", self.date, WEIGHT.format(weight)),
+        };
+        if self.excluded {
+            text.push_str(&format!(", {}", FLAG_EXCLUDED));
+        }
+        if self.estimated {
+            text.push_str(&format!(", {}", FLAG_ESTIMATED));
+        }
+        if let Some(rhr) = self.rhr {
+            text.push_str(&format!(", {}{}", FIELD_RHR, RHR.format(rhr)));
+        }
+        if let Some(source) = &self.source {
+            text.push_str(&format!(", {}{}", FIELD_SOURCE, source));
+        }
+        if let Some(device) = &self.device {
+            text.push_str(&format!(", {}{}", FIELD_DEVICE, device));
+        }
+        if let Some((unit, _)) = self.original {
+            text.push_str(&format!(", {}{}", FIELD_UNIT, unit.name()));
+        }
+        if let Some(id) = &self.id {
+            text.push_str(&format!(", {}{}", FIELD_ID, id));
+        }
+        if let Some(modified) = self.modified {
+            text.push_str(&format!(", {}{}", FIELD_MODIFIED, format_modified(modified)));
+        }
+        text
+    }
+
+    /// Converts the weight of a row with a unit column to `unit`. Can be
+    /// called again, e.g. after merging rows read from disk.
+    pub fn normalize(&mut self, unit: Unit) {
+        if let Some((original, weight)) = self.original {
+            self.weight = original.convert(weight, unit);
+        }
+    }
+
+    /// Parses the output of `to_fields`.
+    pub fn from_fields(text: &str) -> Option<Entry> {
+        parse_import_line(text, DateFormat::Dmy).ok()
+    }
+
+    /// Reads the flags following `date, weight`. Unknown flags are ignored.
+    pub fn set_flags(&mut self, flags: &[&str]) {
+        for flag in flags {
+            if *flag == FLAG_EXCLUDED {
+                self.excluded = true;
+            } else if *flag == FLAG_ESTIMATED {
+                self.estimated = true;
+            } else if let Some(rhr) = flag.strip_prefix(FIELD_RHR) {
+                self.rhr = RHR.validate(rhr).ok();
+            } else if let Some(source) = flag.strip_prefix(FIELD_SOURCE) {
+                self.source = Some(source.to_string()).filter(|x| !x.is_empty());
+            } else if let Some(device) = flag.strip_prefix(FIELD_DEVICE) {
+                self.device = Some(device.to_string()).filter(|x| !x.is_empty());
+            } else if let Some(unit) = flag.strip_prefix(FIELD_UNIT).and_then(Unit::parse) {
+                self.original = Some((unit, self.weight));
+            } else if let Some(id) = flag.strip_prefix(FIELD_ID) {
+                self.id = Some(id.to_string()).filter(|x| !x.is_empty());
+            } else if let Some(modified) = flag.strip_prefix(FIELD_MODIFIED) {
+                self.modified = DateTime::parse_from_rfc3339(modified).ok().map(|x| x.to_utc());
+            }
+        }
+    }
+}
+
+/// Modification time as stored by every backend: RFC 3339 in UTC with
+/// microseconds, so that two edits within a second still order.
+pub fn format_modified(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Updates the tombstones `deleted` after the entries dated `dates` changed:
+/// dates missing from `data` were deleted at `now`, the others have none.
+pub fn record_deletions(deleted: &mut Vec<Entry>, data: &[Entry], dates: &[String], now: DateTime<Utc>) {
+    deleted.retain(|x| !dates.contains(&x.date));
+    for date in dates.iter().filter(|d| !data.iter().any(|x| &x.date == *d)) {
+        deleted.push(Entry::tombstone(date.clone(), now));
+    }
+}
+
+/// The entries and the tombstones in one list sorted by date, as stored. A
+/// date with an entry has no tombstone.
+pub fn with_tombstones(data: &[Entry], deleted: &[Entry]) -> Vec<Entry> {
+    let dates = data.iter().map(|x| x.date.as_str()).collect::<HashSet<_>>();
+    let deleted = deleted.iter().filter(|x| !dates.contains(x.date.as_str()));
+    let mut rows = data.iter().chain(deleted).cloned().collect::<Vec<_>>();
+    rows.sort_by_key(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok());
+    rows
+}
+
+/// Splits stored rows into the entries and the tombstones.
+pub fn split_tombstones(rows: Vec<Entry>) -> (Vec<Entry>, Vec<Entry>) {
+    let (deleted, data) = rows.into_iter().partition(|x| x.deleted);
+    (data, deleted)
+}
+
+/// Flags entries deviating from the EWMA of the preceding entries by more than
+/// `k` robust standard deviations (scaled MAD of the residuals). Excluded
+/// entries are never flagged and do not feed the EWMA; neither do outliers.
+pub fn detect_outliers(data: &[Entry], config: &OutlierConfig) -> Vec<bool> {
+    let mut flags = vec![false; data.len()];
+    let included = data.iter().filter(|e| !e.excluded).map(|e| e.weight).collect::<Vec<_>>();
+    if included.len() < OUTLIER_MIN_ENTRIES {
+        return flags;
+    }
+    let mut ewma = included[0];
+    let mut residuals = Vec::with_capacity(included.len() - 1);
+    for w in &included[1..] {
+        residuals.push((w - ewma).abs());
+        ewma += config.alpha * (w - ewma);
+    }
+    residuals.sort_by(f64::total_cmp);
+    let sigma = 1.4826 * residuals[residuals.len() / 2];
+    if sigma <= f64::EPSILON {
+        return flags;
+    }
+    let mut ewma = None;
+    for (i, entry) in data.iter().enumerate() {
+        if entry.excluded {
+            continue;
+        }
+        match ewma {
+            None => ewma = Some(entry.weight),
+            Some(avg) if (entry.weight - avg).abs() > config.k * sigma => flags[i] = true,
+            Some(avg) => ewma = Some(avg + config.alpha * (entry.weight - avg)),
+        }
+    }
+    flags
+}
+
+/// Parses one `date, weight[, resting heart rate][, unit]` line of an
+/// imported CSV file. The flags written by `export` are accepted as well, so
+/// an exported file imports back unchanged.
+fn parse_import_line(line: &str, format: DateFormat) -> Result<Entry, String> {
+    let fields = line.split(',').map(|x| x.trim()).collect::<Vec<_>>();
+    if fields.len() < 2 {
+        return Err(format!("expected 2 columns, found {}", fields.len()));
+    }
+    let date = format
+        .parse(fields[0])
+        .ok_or_else(|| format!("invalid date '{}', expected {}", fields[0], format.name()))?;
+    let weight = WEIGHT
+        .validate(fields[1])
+        .map_err(|e| format!("invalid weight '{}': {}", fields[1], e))?;
+    let mut entry = Entry::new(date.format("%d-%m-%Y").to_string(), weight);
+    for field in &fields[2..] {
+        if field.is_empty()
+            || *field == FLAG_EXCLUDED
+            || *field == FLAG_ESTIMATED
+            || field.starts_with(FIELD_SOURCE)
+            || field.starts_with(FIELD_DEVICE)
+            || field.starts_with(FIELD_ID)
+            || field.starts_with(FIELD_MODIFIED)
+        {
+            entry.set_flags(&[field]);
+            continue;
+        }
+        if let Some(text) = field.strip_prefix(FIELD_UNIT).or(Unit::parse(field).map(|_| *field)) {
+            let unit = Unit::parse(text).ok_or_else(|| format!("invalid unit '{}'", text))?;
+            entry.original = Some((unit, weight));
+            continue;
+        }
+        let text = field.strip_prefix(FIELD_RHR).unwrap_or(field);
+        entry.rhr = Some(
+            RHR.validate(text)
+                .map_err(|e| format!("invalid resting heart rate '{}': {}", text, e))?,
+        );
+    }
+    Ok(entry)
+}
+
+impl Metric {
+    pub fn validate(&self, text: &str) -> Result<f64, String> {
+        let text = text.trim();
+        let value = text
+            .parse::<f64>()
+            .map_err(|_| format!("{} is not a number", self.name))?;
+        if !value.is_finite() || value < self.min || value > self.max {
+            return Err(format!("{} must be between {} and {}", self.name, self.min, self.max));
+        }
+        let decimals = text.split_once('.').map_or(0, |(_, frac)| frac.len());
+        if decimals > self.decimals {
+            return Err(format!("{} allows at most {} decimals", self.name, self.decimals));
+        }
+        Ok(value)
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        format!("{:.*}", self.decimals, value)
+    }
+}
+
+/// Change between the newest entry and the newest entry at least a week older.
+pub fn weekly_change(data: &[Entry]) -> Option<f64> {
+    let parse = |x: &Entry| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok();
+    let mut included = data.iter().filter(|x| !x.excluded);
+    let last = included.next_back()?;
+    let week_ago = parse(last)? - Days::new(7);
+    let before = included.rfind(|x| parse(x).is_some_and(|d| d <= week_ago))?;
+    Some(last.weight - before.weight)
+}
+
+/// Average of the non-excluded entries dated `from` to `to`, both included.
+fn window_average(data: &[Entry], from: NaiveDate, to: NaiveDate) -> Option<f64> {
+    let weights = data
+        .iter()
+        .filter(|x| !x.excluded)
+        .filter(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").is_ok_and(|d| from <= d && d <= to))
+        .map(|x| x.weight)
+        .collect::<Vec<_>>();
+    (!weights.is_empty()).then(|| weights.iter().sum::<f64>() / weights.len() as f64)
+}
+
+/// Change of the 7-day average over the week up to `date`.
+pub fn weekly_rate(data: &[Entry], date: NaiveDate) -> Option<f64> {
+    rate_between(date, |from, to| window_average(data, from, to))
+}
+
+/// Change of the 7-day average over the week up to `date`, with `average`
+/// giving the average of the entries dated `from` to `to`.
+fn rate_between(date: NaiveDate, average: impl Fn(NaiveDate, NaiveDate) -> Option<f64>) -> Option<f64> {
+    let week_ago = date - Days::new(7);
+    let now = average(date - Days::new(6), date)?;
+    let before = average(week_ago - Days::new(6), week_ago)?;
+    Some(now - before)
+}
+
+/// `weekly_rate` at every date with a counted entry. Dates are parsed once
+/// and the windows averaged from prefix sums, so that the whole history
+/// takes O(n log n).
+pub fn weekly_rates(data: &[Entry]) -> Vec<(NaiveDate, f64)> {
+    let counted = data
+        .iter()
+        .filter(|x| !x.excluded)
+        .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.weight)))
+        .collect::<Vec<_>>();
+    let sums = counted.iter().scan(0.0, |sum, x| {
+        *sum += x.1;
+        Some(*sum)
+    });
+    let prefix = std::iter::once(0.0).chain(sums).collect::<Vec<_>>();
+    let average = |from: NaiveDate, to: NaiveDate| {
+        let lo = counted.partition_point(|x| x.0 < from);
+        let hi = counted.partition_point(|x| x.0 <= to);
+        (hi > lo).then(|| (prefix[hi] - prefix[lo]) / (hi - lo) as f64)
+    };
+    counted
+        .iter()
+        .filter_map(|&(date, _)| Some((date, rate_between(date, average)?)))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrendShiftKind {
+    /// Losing turned into gaining or the other way round.
+    Reversed,
+    Faster,
+    Slower,
+}
+
+/// A significant change of the weekly rate between `weeks` weeks ago and now.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrendShift {
+    pub kind: TrendShiftKind,
+    pub before: f64,
+    pub now: f64,
+}
+
+/// Compares the weekly rate up to `today` with the one `weeks` weeks
+/// earlier. Rates below `min_rate` count as flat; a rate has to grow or
+/// shrink by `ratio` to count as faster or slower.
+pub fn trend_shift(
+    data: &[Entry],
+    today: NaiveDate,
+    weeks: u32,
+    min_rate: f64,
+    ratio: f64,
+) -> Option<TrendShift> {
+    let now = weekly_rate(data, today)?;
+    let before = weekly_rate(data, today - Days::new(u64::from(weeks) * 7))?;
+    let (moving_before, moving_now) = (before.abs() >= min_rate, now.abs() >= min_rate);
+    let kind = if moving_before && moving_now && before.signum() != now.signum() {
+        TrendShiftKind::Reversed
+    } else if moving_before && (now * before.signum()).max(0.0) * ratio <= before.abs() {
+        TrendShiftKind::Slower
+    } else if moving_now && now.abs() >= (before * now.signum()).max(0.0) * ratio {
+        TrendShiftKind::Faster
+    } else {
+        return None;
+    };
+    Some(TrendShift { kind, before, now })
+}
+
+/// Position of the entry dated `date` in the sorted data, or where it would be inserted.
+pub fn find_entry(data: &[Entry], date: NaiveDate) -> Result<usize, usize> {
+    data.binary_search_by(|x| {
+        NaiveDate::parse_from_str(x.date.as_str(), "%d-%m-%Y")
+            .unwrap()
+            .cmp(&date)
+    })
+}
+
+/// Inserts `entry` keeping the data sorted by date. Returns `false` and leaves
+/// the data unchanged if the date is invalid or already has an entry.
+pub fn insert_entry(data: &mut Vec<Entry>, entry: Entry) -> bool {
+    let Ok(date) = NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y") else {
+        return false;
+    };
+    match find_entry(data, date) {
+        Ok(_) => false,
+        Err(idx) => {
+            data.insert(idx, entry);
+            true
+        }
+    }
+}
+
+/// Replaces the measured values of an entry, which is then no longer estimated.
+pub fn edit_entry(entry: &mut Entry, weight: f64, rhr: Option<f64>) {
+    entry.weight = weight;
+    entry.rhr = rhr;
+    entry.estimated = false;
+    entry.source = Some(String::from(SOURCE_MANUAL));
+    entry.original = None;
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Reads an import source, fetching it first if it is an HTTP(S) URL.
+pub fn read_import_source(source: &str) -> Result<String, WtError> {
+    if is_url(source) {
+        info!("fetching {}", source);
+        let mut response = ureq::get(source)
+            .call()
+            .map_err(|e| WtError::Storage(format!("cannot fetch {}: {}", source, e)))?;
+        let mut bytes = Vec::new();
+        response
+            .body_mut()
+            .as_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|e| WtError::Storage(format!("cannot fetch {}: {}", source, e)))?;
+        decode_import(decompress(bytes)?)
+    } else {
+        fs::read(source)
+            .map_err(|e| WtError::Storage(format!("cannot read {}: {}", source, e)))
+            .and_then(decompress)
+            .and_then(decode_import)
+    }
+}
+
+/// Text encoding of an imported file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// The "ANSI" code page spreadsheet apps on Western Windows save in.
+    Windows1252,
+}
+
+impl TextEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+            TextEncoding::Windows1252 => "Windows-1252",
+        }
+    }
+
+    /// Encoding of `bytes` and the length of its byte order mark. Without a
+    /// mark, text with a zero byte in most character pairs is UTF-16, and
+    /// anything that isn't valid UTF-8 is taken for Windows-1252.
+    pub fn detect(bytes: &[u8]) -> (TextEncoding, usize) {
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return (TextEncoding::Utf8, 3);
+        }
+        if bytes.starts_with(&[0xff, 0xfe]) {
+            return (TextEncoding::Utf16Le, 2);
+        }
+        if bytes.starts_with(&[0xfe, 0xff]) {
+            return (TextEncoding::Utf16Be, 2);
+        }
+        let pairs = bytes.len() / 2;
+        let zeros = |offset: usize| bytes.chunks_exact(2).filter(|x| x[offset] == 0).count();
+        if pairs > 0 && zeros(1) * 2 > pairs {
+            (TextEncoding::Utf16Le, 0)
+        } else if pairs > 0 && zeros(0) * 2 > pairs {
+            (TextEncoding::Utf16Be, 0)
+        } else if std::str::from_utf8(bytes).is_ok() {
+            (TextEncoding::Utf8, 0)
+        } else {
+            (TextEncoding::Windows1252, 0)
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<String, String> {
+        match self {
+            TextEncoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string()),
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(String::from("odd number of bytes"));
+                }
+                let units = bytes.chunks_exact(2).map(|x| match self {
+                    TextEncoding::Utf16Le => u16::from_le_bytes([x[0], x[1]]),
+                    _ => u16::from_be_bytes([x[0], x[1]]),
+                });
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_err(|e| format!("unpaired surrogate {:#06x}", e.unpaired_surrogate()))
+            }
+            TextEncoding::Windows1252 => bytes
+                .iter()
+                .enumerate()
+                .map(|(i, x)| match x {
+                    0x80..=0x9f => WINDOWS_1252_HIGH[(x - 0x80) as usize]
+                        .ok_or_else(|| format!("undefined byte {:#04x} at offset {}", x, i)),
+                    // The rest matches Latin-1, which maps straight to Unicode.
+                    _ => Ok(*x as char),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Text of an imported file in any of the `TextEncoding`s, without a byte
+/// order mark.
+pub fn decode_import(bytes: Vec<u8>) -> Result<String, WtError> {
+    let (encoding, bom) = TextEncoding::detect(&bytes);
+    if encoding != TextEncoding::Utf8 || bom > 0 {
+        info!("decoding import as {} (byte order mark: {})", encoding.name(), bom > 0);
+    }
+    encoding
+        .decode(&bytes[bom..])
+        .map_err(|e| WtError::Storage(format!("cannot decode the file as {}: {}", encoding.name(), e)))
+}
+
+/// Source recorded for entries imported from `path` without a `label`:
+/// `import:<file name without extension>`.
+pub fn import_source(path: &str, label: Option<&str>) -> String {
+    let label = match label {
+        Some(label) => label.to_string(),
+        None => {
+            let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or(path);
+            let name = name.split(['?', '#']).next().unwrap_or(name);
+            name.split('.').next().unwrap_or(name).to_string()
+        }
+    };
+    // Commas separate the fields of the data file.
+    let label = label.replace([',', ' '], "-");
+    format!("{}{}", SOURCE_IMPORT_PREFIX, label)
+}
+
+/// Splits imported text into lines, blanking a leading header so line numbers
+/// in the report still match the file.
+pub fn split_import_lines(text: &str) -> Vec<String> {
+    let mut lines = text.lines().map(String::from).collect::<Vec<_>>();
+    let is_header = |x: &String| {
+        x.trim_start_matches(['\u{feff}', ' ', '"'])
+            .to_lowercase()
+            .starts_with("date")
+    };
+    if lines.first().is_some_and(is_header) {
+        lines[0].clear();
+    }
+    lines
+}
+
+/// Date formats parsing the most rows of `lines`, in the order of
+/// `DateFormat::ALL`. More than one means the file is ambiguous, e.g. slashed
+/// dates whose days are all at most 12; none means no row has a known date.
+pub fn detect_date_formats(lines: &[String]) -> Vec<DateFormat> {
+    let dates = lines
+        .iter()
+        .filter_map(|x| tidy_import_line(x))
+        .filter_map(|(x, _)| x.split(',').next().map(|x| x.trim().to_string()))
+        .collect::<Vec<_>>();
+    let counts = DateFormat::ALL.map(|f| (f, dates.iter().filter(|x| f.parse(x).is_some()).count()));
+    let best = counts.iter().map(|x| x.1).max().unwrap_or_default();
+    counts.into_iter().filter(|x| best > 0 && x.1 == best).map(|x| x.0).collect()
+}
+
+/// Cleans up a row of a spreadsheet export (Numbers, Sheets): splits it on
+/// the commas outside quotes, unquotes the fields, reading a quoted comma as
+/// the decimal separator, and drops empty trailing columns. Returns the row
+/// and the number of dropped columns, or `None` for a row without any number.
+fn tidy_import_line(line: &str) -> Option<(String, usize)> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    for c in line.trim_start_matches('\u{feff}').chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if quoted => fields.last_mut().unwrap().push('.'),
+            ',' => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    let columns = fields.len();
+    let mut fields = fields.iter().map(|x| x.trim()).collect::<Vec<_>>();
+    if !fields.iter().any(|x| x.starts_with(|c: char| c.is_ascii_digit())) {
+        return None;
+    }
+    while fields.len() > 2 && fields.last().is_some_and(|x| x.is_empty()) {
+        fields.pop();
+    }
+    Some((fields.join(", "), columns - fields.len()))
+}
+
+/// Three-way merge of the rows by date, tombstones included: changes made on
+/// only one side since `base` are kept. When both sides changed or deleted
+/// an entry the later `modified` wins, ties going to the greater id so that
+/// every machine picks the same one. A side with neither an entry nor a
+/// tombstone for the date loses.
+pub fn merge_entries(base: &[Entry], ours: &[Entry], theirs: &[Entry]) -> (Vec<Entry>, MergeReport) {
+    let by_date = |data: &[Entry]| {
+        data.iter()
+            .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.clone())))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let (base, ours, theirs) = (by_date(base), by_date(ours), by_date(theirs));
+    let dates = ours.keys().chain(theirs.keys()).chain(base.keys()).collect::<BTreeSet<_>>();
+    let mut report = MergeReport::default();
+    let mut merged = Vec::new();
+    for date in dates {
+        let (b, o, t) = (base.get(date), ours.get(date), theirs.get(date));
+        let entry = if o == b {
+            if t != b {
+                report.merged += 1;
+            }
+            t
+        } else if t == b || t == o {
+            o
+        } else {
+            report.conflicts += 1;
+            let stamp = |x: Option<&Entry>| x.map(|x| (x.modified, x.id.clone()));
+            if stamp(t) > stamp(o) { t } else { o }
+        };
+        merged.extend(entry.cloned());
+    }
+    (merged, report)
+}
+
+/// Merges `lines` into the sorted data, skipping dates that already exist.
+/// `offset` is the index of `lines[0]` in the whole file. Returns the dates
+/// of the added entries.
+/// Entries without a source of their own get `source`. Rows are tidied
+/// first; rows without any number are skipped and listed in the report.
+pub fn import_lines(
+    data: &mut Vec<Entry>,
+    lines: &[String],
+    offset: usize,
+    source: &str,
+    format: DateFormat,
+    report: &mut ImportReport,
+) -> Vec<String> {
+    let mut added = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.split(',').all(|x| x.trim().is_empty()) {
+            continue;
+        }
+        let Some((line, stripped)) = tidy_import_line(line) else {
+            debug!("import line {} skipped: {}", offset + i + 1, line);
+            report.skipped.push((offset + i + 1, line.trim().to_string()));
+            continue;
+        };
+        if stripped > 0 {
+            report.stripped += 1;
+        }
+        let mut entry = match parse_import_line(&line, format) {
+            Ok(x) => x,
+            Err(e) => {
+                debug!("import line {} rejected: {}", offset + i + 1, e);
+                report.failed.push((offset + i + 1, e));
+                continue;
+            }
+        };
+        entry.source.get_or_insert_with(|| source.to_string());
+        let date = entry.date.clone();
+        if insert_entry(data, entry) {
+            added.push(date);
+            report.added += 1;
+        } else {
+            report.duplicates += 1;
+        }
+    }
+    added
+}
+
+/// A day of the year heat map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeatDay {
+    /// Before the first of January, after the end of the year or in the future.
+    Outside,
+    Missing,
+    /// A weigh-in, with the change to the previous one unless either is
+    /// excluded from the trend.
+    Logged(Option<f64>),
+}
+
+/// The days of `year` up to `today`, one column per week starting on Monday
+/// like a contribution graph; 53 or 54 columns of seven days.
+pub fn year_heatmap(data: &[Entry], year: i32, today: NaiveDate) -> Vec<[HeatDay; 7]> {
+    let bounds = (NaiveDate::from_ymd_opt(year, 1, 1), NaiveDate::from_ymd_opt(year, 12, 31));
+    let (Some(first), Some(last)) = bounds else {
+        return Vec::new();
+    };
+    let mut previous = None;
+    let mut logged = BTreeMap::new();
+    for entry in data {
+        let Ok(date) = NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y") else {
+            continue;
+        };
+        let delta = previous.filter(|_| !entry.excluded).map(|x: f64| entry.weight - x);
+        if !entry.excluded {
+            previous = Some(entry.weight);
+        }
+        logged.insert(date, delta);
+    }
+    let start = first - Days::new(u64::from(first.weekday().num_days_from_monday()));
+    let mut columns = Vec::new();
+    let mut day = start;
+    while day <= last {
+        let mut column = [HeatDay::Outside; 7];
+        for cell in column.iter_mut() {
+            if first <= day && day <= last && day <= today {
+                *cell = logged.get(&day).map_or(HeatDay::Missing, |x| HeatDay::Logged(*x));
+            }
+            day = day + Days::new(1);
+        }
+        columns.push(column);
+    }
+    columns
+}
+
+pub fn week_label(week: IsoWeek) -> String {
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+/// Inserts linearly interpolated entries, marked as estimated, for the days
+/// without an entry between two measured ones. Excluded entries are not used
+/// as anchors. Returns the dates of the added entries.
+pub fn backfill(data: &mut Vec<Entry>) -> Vec<String> {
+    let anchors = data
+        .iter()
+        .filter(|x| !x.excluded && !x.estimated)
+        .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.weight)))
+        .collect::<Vec<_>>();
+    let mut added = Vec::new();
+    for pair in anchors.windows(2) {
+        let ((from, w_from), (to, w_to)) = (pair[0], pair[1]);
+        let span = (to - from).num_days() as f64;
+        for day in from.iter_days().skip(1).take_while(|d| *d < to) {
+            let Err(idx) = find_entry(data, day) else {
+                continue;
+            };
+            let t = (day - from).num_days() as f64 / span;
+            let weight = ((w_from + (w_to - w_from) * t) * 10.0).round() / 10.0;
+            let mut entry = Entry::new(day.format("%d-%m-%Y").to_string(), weight);
+            entry.estimated = true;
+            entry.source = Some(String::from(SOURCE_BACKFILL));
+            added.push(entry.date.clone());
+            data.insert(idx, entry);
+        }
+    }
+    added
+}
+
+/// Average of the non-excluded entries in the `days` days up to each entry,
+/// or `None` where that window has no such entries. `data` must be sorted.
+pub fn rolling_averages(data: &[Entry], days: u64) -> Vec<Option<f64>> {
+    let dates = data
+        .iter()
+        .map(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok())
+        .collect::<Vec<_>>();
+    let (mut start, mut sum, mut count) = (0, 0.0, 0);
+    let mut ret = Vec::with_capacity(data.len());
+    for (i, entry) in data.iter().enumerate() {
+        if !entry.excluded {
+            sum += entry.weight;
+            count += 1;
+        }
+        if let Some(date) = dates[i] {
+            let first = date - Days::new(days - 1);
+            while start < i && dates[start].is_none_or(|d| d < first) {
+                if !data[start].excluded {
+                    sum -= data[start].weight;
+                    count -= 1;
+                }
+                start += 1;
+            }
+        }
+        ret.push((count > 0).then(|| sum / count as f64));
+    }
+    ret
+}
+
+/// Sample standard deviation of the non-excluded entries in the `days` days
+/// up to each entry, or `None` where that window has fewer than two of them.
+/// `data` must be sorted.
+pub fn rolling_std_devs(data: &[Entry], days: u64) -> Vec<Option<f64>> {
+    let dates = data
+        .iter()
+        .map(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok())
+        .collect::<Vec<_>>();
+    let mut start = 0;
+    let mut ret = Vec::with_capacity(data.len());
+    for (i, date) in dates.iter().enumerate() {
+        if let Some(date) = date {
+            let first = *date - Days::new(days - 1);
+            while start < i && dates[start].is_none_or(|d| d < first) {
+                start += 1;
+            }
+        }
+        let weights = data[start..=i].iter().filter(|x| !x.excluded).map(|x| x.weight).collect::<Vec<_>>();
+        if weights.len() < 2 {
+            ret.push(None);
+            continue;
+        }
+        let mean = weights.iter().sum::<f64>() / weights.len() as f64;
+        let variance = weights.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / (weights.len() - 1) as f64;
+        ret.push(Some(variance.sqrt()));
+    }
+    ret
+}
+
+/// Volatility as of the last entry: the standard deviation over the
+/// `VOLATILITY_DAYS` days up to it.
+pub fn volatility(data: &[Entry]) -> Option<f64> {
+    rolling_std_devs(data, VOLATILITY_DAYS).last().copied().flatten()
+}
+
+/// Weight of each entry with the yearly seasonality removed, by a simple
+/// STL-like decomposition of the daily series, linearly interpolated between
+/// the non-excluded entries. The trend is a centered moving average over
+/// `SEASONAL_TREND_DAYS`; the seasonality is the average distance from it per
+/// week of the year across all years, smoothed and centered on zero. `None`
+/// for excluded entries, and for every entry with less than
+/// `SEASONAL_MIN_DAYS` of data. `data` must be sorted.
+pub fn deseasonalize(data: &[Entry]) -> Vec<Option<f64>> {
+    let parse = |x: &Entry| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok().filter(|_| !x.excluded);
+    let points = data.iter().filter_map(|x| Some((parse(x)?, x.weight))).collect::<Vec<_>>();
+    let (Some((first, _)), Some((last, _))) = (points.first(), points.last()) else {
+        return vec![None; data.len()];
+    };
+    let (first, span) = (*first, (*last - *first).num_days());
+    if span < SEASONAL_MIN_DAYS {
+        return vec![None; data.len()];
+    }
+    let mut daily = Vec::with_capacity(span as usize + 1);
+    for pair in points.windows(2) {
+        let ((from, a), (to, b)) = (pair[0], pair[1]);
+        let days = (to - from).num_days();
+        daily.extend((0..days).map(|i| a + (b - a) * i as f64 / days as f64));
+    }
+    daily.push(points[points.len() - 1].1);
+    let mut sums = vec![0.0];
+    for weight in &daily {
+        sums.push(sums[sums.len() - 1] + weight);
+    }
+    let half = SEASONAL_TREND_DAYS as usize / 2;
+    let week = |date: NaiveDate| (date.ordinal0() as usize / 7).min(SEASONAL_WEEKS - 1);
+    let (mut totals, mut counts) = ([0.0; SEASONAL_WEEKS], [0usize; SEASONAL_WEEKS]);
+    for (i, weight) in daily.iter().enumerate() {
+        let (lo, hi) = (i.saturating_sub(half), (i + half).min(daily.len() - 1));
+        let trend = (sums[hi + 1] - sums[lo]) / (hi - lo + 1) as f64;
+        let bin = week(first + Days::new(i as u64));
+        totals[bin] += weight - trend;
+        counts[bin] += 1;
+    }
+    let means = std::array::from_fn::<f64, SEASONAL_WEEKS, _>(|i| totals[i] / counts[i].max(1) as f64);
+    let smoothed = std::array::from_fn::<f64, SEASONAL_WEEKS, _>(|i| {
+        let n = SEASONAL_WEEKS;
+        (means[(i + n - 1) % n] + means[i] + means[(i + 1) % n]) / 3.0
+    });
+    let center = smoothed.iter().sum::<f64>() / SEASONAL_WEEKS as f64;
+    data.iter()
+        .map(|x| Some(x.weight - (smoothed[week(parse(x)?)] - center)))
+        .collect()
+}
+
+/// Average weight of the non-excluded entries dated `from` through `from + 6 days`.
+fn week_average(data: &[Entry], from: NaiveDate) -> Option<f64> {
+    window_average(data, from, from + Days::new(6))
+}
+
+/// Summary of the ISO week before the one containing `today`.
+pub fn weekly_summary(data: &[Entry], schedule: &[Weekday], today: NaiveDate) -> WeekSummary {
+    let monday = today.week(Weekday::Mon).first_day() - Days::new(7);
+    let average = week_average(data, monday);
+    let previous = week_average(data, monday - Days::new(7));
+    let days = (0..7).map(|i| monday + Days::new(i));
+    let scheduled = days
+        .filter(|d| schedule.is_empty() || schedule.contains(&d.weekday()))
+        .collect::<Vec<_>>();
+    let logged = scheduled
+        .iter()
+        .filter(|d| {
+            let date = d.format("%d-%m-%Y").to_string();
+            data.iter().any(|x| x.date == date)
+        })
+        .count();
+    WeekSummary {
+        week: monday.iso_week(),
+        average,
+        change: average.zip(previous).map(|(a, p)| a - p),
+        logged,
+        scheduled: scheduled.len(),
+    }
+}
+
+/// How an entry differs between the saved data and the data being edited.
+#[derive(Clone, PartialEq)]
+pub enum PendingChange {
+    Added(Entry),
+    /// The entry as saved, then as edited.
+    Edited(Entry, Entry),
+    Deleted(Entry),
+}
+
+impl PendingChange {
+    pub fn date(&self) -> &str {
+        match self {
+            PendingChange::Added(x) | PendingChange::Edited(_, x) | PendingChange::Deleted(x) => &x.date,
+        }
+    }
+}
+
+/// Names of what differs between two versions of an entry, e.g. `["weight", "rhr"]`.
+pub fn changed_fields(before: &Entry, after: &Entry) -> Vec<&'static str> {
+    let stored = |e: &Entry| e.original.map_or((None, e.weight), |(unit, w)| (Some(unit), w));
+    [
+        ("weight", stored(before) != stored(after)),
+        ("rhr", before.rhr != after.rhr),
+        (FLAG_EXCLUDED, before.excluded != after.excluded),
+        (FLAG_ESTIMATED, before.estimated != after.estimated),
+        ("source", before.source != after.source),
+        ("device", before.device != after.device),
+    ]
+    .into_iter()
+    .filter(|x| x.1)
+    .map(|x| x.0)
+    .collect()
+}
+
+/// Every entry added, edited or deleted in `current` compared to `saved`, by date.
+pub fn pending_changes(saved: &[Entry], current: &[Entry]) -> Vec<PendingChange> {
+    let mut dates = BTreeMap::<NaiveDate, (Option<&Entry>, Option<&Entry>)>::new();
+    let date = |x: &Entry| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok();
+    for x in saved {
+        if let Some(d) = date(x) {
+            dates.entry(d).or_default().0 = Some(x);
+        }
+    }
+    for x in current {
+        if let Some(d) = date(x) {
+            dates.entry(d).or_default().1 = Some(x);
+        }
+    }
+    dates
+        .into_values()
+        .filter_map(|pair| match pair {
+            (None, Some(x)) => Some(PendingChange::Added(x.clone())),
+            (Some(x), None) => Some(PendingChange::Deleted(x.clone())),
+            (Some(a), Some(b)) if a != b => Some(PendingChange::Edited(a.clone(), b.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rows shown in the table, e.g. `>=90 <=01-06-2024`: every term is a lower
+/// or upper bound, on the date if it contains a `-` and on the weight
+/// otherwise.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableFilter {
+    pub min_weight: Option<f64>,
+    pub max_weight: Option<f64>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl TableFilter {
+    pub fn parse(text: &str, today: NaiveDate) -> Result<TableFilter, String> {
+        let mut filter = TableFilter::default();
+        for term in text.split_whitespace() {
+            let (lower, value) = match (term.strip_prefix(">="), term.strip_prefix("<=")) {
+                (Some(value), _) => (true, value),
+                (_, Some(value)) => (false, value),
+                _ => return Err(format!("'{}' should start with >= or <=", term)),
+            };
+            if value.contains('-') {
+                let date = resolve_date(value, today).ok_or_else(|| format!("invalid date '{}'", value))?;
+                *(if lower { &mut filter.from } else { &mut filter.to }) = Some(date);
+            } else {
+                let weight = WEIGHT.validate(value)?;
+                *(if lower { &mut filter.min_weight } else { &mut filter.max_weight }) = Some(weight);
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let date = NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y").ok();
+        self.min_weight.is_none_or(|x| entry.weight >= x)
+            && self.max_weight.is_none_or(|x| entry.weight <= x)
+            && self.from.is_none_or(|x| date.is_some_and(|d| d >= x))
+            && self.to.is_none_or(|x| date.is_some_and(|d| d <= x))
+    }
+}
+
+/// Completes a partially typed date: `5` is the 5th of the current month,
+/// `5-3` the 5th of March of the current year, `5-3-2024` or `2024-03-05` a
+/// full date.
+pub fn resolve_date(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let parts = text.trim().split('-').collect::<Vec<_>>();
+    let num = |x: &str| x.parse::<u32>().ok();
+    match parts[..] {
+        [d] => NaiveDate::from_ymd_opt(today.year(), today.month(), num(d)?),
+        [d, m] => NaiveDate::from_ymd_opt(today.year(), num(m)?, num(d)?),
+        [y, _, _] if y.len() == 4 => NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok(),
+        _ => NaiveDate::parse_from_str(text.trim(), "%d-%m-%Y").ok(),
+    }
+}
+
+/// Pearson correlation coefficient of `pairs`, or `None` for fewer than two
+/// pairs or when either side is constant.
+pub fn pearson(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x <= f64::EPSILON || var_y <= f64::EPSILON {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+/// Date, weight and resting heart rate of the non-excluded entries that have both.
+pub fn rhr_pairs(data: &[Entry]) -> Vec<(NaiveDate, f64, f64)> {
+    data.iter()
+        .filter(|x| !x.excluded)
+        .filter_map(|x| {
+            let date = NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?;
+            Some((date, x.weight, x.rhr?))
+        })
+        .collect()
+}
+
+/// Correlation of weight and resting heart rate over the `days` days up to
+/// each entry that has both, wherever at least `min_points` entries fall
+/// into that window.
+pub fn rolling_correlation(data: &[Entry], days: u64, min_points: usize) -> Vec<(NaiveDate, f64)> {
+    let pairs = rhr_pairs(data);
+    pairs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (date, _, _))| {
+            let first = *date - Days::new(days - 1);
+            let window = pairs[..=i]
+                .iter()
+                .filter(|x| x.0 >= first)
+                .map(|x| (x.1, x.2))
+                .collect::<Vec<_>>();
+            if window.len() < min_points {
+                return None;
+            }
+            Some((*date, pearson(&window)?))
+        })
+        .collect()
+}
+
+/// Date on which `target` is reached when changing by `rate` kg per week from
+/// `weight` on `from`, or `None` if it is never reached at that rate.
+pub fn project_date(from: NaiveDate, weight: f64, rate: f64, target: f64) -> Option<NaiveDate> {
+    let days = (target - weight) / (rate / 7.0);
+    if !days.is_finite() || days < 0.0 {
+        return None;
+    }
+    // Tolerate rounding noise so an exact number of days isn't rounded up.
+    from.checked_add_days(Days::new((days - 1e-9).ceil().max(0.0) as u64))
+}
+
+/// Days over which the trend's rate of change is measured for goal projections.
+pub const TREND_RATE_DAYS: i64 = 28;
+
+/// When a goal weight was or will be reached.
+#[derive(Debug, PartialEq)]
+pub enum GoalEta {
+    Reached(NaiveDate),
+    Projected(NaiveDate),
+    /// The trend is flat or heading away from the goal.
+    Never,
+}
+
+impl GoalEta {
+    pub fn describe(&self) -> String {
+        match self {
+            GoalEta::Reached(date) => format!("reached {}", date.format("%d-%m-%Y")),
+            GoalEta::Projected(date) => format!("~{}", date.format("%d-%m-%Y")),
+            GoalEta::Never => String::from("not at current trend"),
+        }
+    }
+}
+
+/// When the raw scale reading (first) and the trend, an EWMA with smoothing
+/// factor `alpha` of the non-excluded entries (second), reach `target`. A
+/// single low reading meets the scale goal, while the trend crossing shows
+/// the weight has really settled there. Both are projected at the trend's
+/// rate over the last `TREND_RATE_DAYS` days until reached.
+pub fn goal_etas(data: &[Entry], target: f64, alpha: f64) -> Option<(GoalEta, GoalEta)> {
+    let points = data
+        .iter()
+        .filter(|x| !x.excluded)
+        .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.weight)))
+        .collect::<Vec<_>>();
+    let losing = points.first()?.1 > target;
+    let crossed = |w: f64| if losing { w <= target } else { w >= target };
+    let mut trend = Vec::with_capacity(points.len());
+    for (date, weight) in &points {
+        let value = trend.last().map_or(*weight, |(_, t): &(NaiveDate, f64)| t + alpha * (weight - t));
+        trend.push((*date, value));
+    }
+    let (last_date, last_trend) = *trend.last()?;
+    let since = last_date - Days::new(TREND_RATE_DAYS as u64);
+    let (from_date, from_trend) = trend.iter().rev().find(|x| x.0 <= since).unwrap_or(&trend[0]);
+    let days = (last_date - *from_date).num_days();
+    let rate = (days > 0).then(|| (last_trend - from_trend) / days as f64 * 7.0);
+    let eta = |series: &[(NaiveDate, f64)]| match series.iter().find(|x| crossed(x.1)) {
+        Some((date, _)) => GoalEta::Reached(*date),
+        None => rate
+            .and_then(|rate| project_date(last_date, series.last()?.1, rate, target))
+            .map_or(GoalEta::Never, GoalEta::Projected),
+    };
+    Some((eta(&points), eta(&trend)))
+}
+
+/// What is left of the planned change from the start weight to the target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoalCountdown {
+    /// Distance of the trend to the target, `0` once it got there.
+    pub remaining: f64,
+    /// `remaining` as a share of the planned change, in percent. Above 100 if
+    /// the trend moved away from the start.
+    pub percent: f64,
+}
+
+/// Countdown to `target` from the trend, an EWMA with smoothing factor
+/// `alpha` of the non-excluded entries, so that a single reading doesn't
+/// make it jump. `start` defaults to the first entry.
+pub fn goal_countdown(data: &[Entry], start: Option<f64>, target: f64, alpha: f64) -> Option<GoalCountdown> {
+    let mut weights = data.iter().filter(|x| !x.excluded).map(|x| x.weight);
+    let first = weights.next()?;
+    let trend = weights.fold(first, |t, w| t + alpha * (w - t));
+    let planned = start.unwrap_or(first) - target;
+    if planned.abs() < f64::EPSILON {
+        return None;
+    }
+    let remaining = ((trend - target) * planned.signum()).max(0.0);
+    Some(GoalCountdown { remaining, percent: remaining / planned.abs() * 100.0 })
+}
+
+/// Fills the `{date}`, `{weight}` and `{unit}` fields of a daily note line.
+pub fn note_line(template: &str, date: NaiveDate, weight: &str, unit: Unit) -> String {
+    template
+        .replace("{date}", &date.format("%d-%m-%Y").to_string())
+        .replace("{weight}", weight)
+        .replace("{unit}", unit.name())
+}
+
+/// `note` with `line` in place of the line written by an earlier run, found
+/// by the part of the template before `{weight}`, or else appended.
+pub fn update_note(note: &str, template: &str, line: &str) -> String {
+    let prefix = template.split("{weight}").next().unwrap_or_default().trim();
+    let mut lines = note.lines().map(String::from).collect::<Vec<_>>();
+    let existing = lines.iter_mut().find(|x| {
+        let x = x.trim_start();
+        if prefix.is_empty() { x == line.trim_start() } else { x.starts_with(prefix) }
+    });
+    match existing {
+        Some(x) => {
+            let indent = x.len() - x.trim_start().len();
+            *x = format!("{}{}", &x[..indent], line.trim_start());
+        }
+        None => lines.push(line.to_string()),
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Days between the newest entry and `today`.
+pub fn last_entry_age(data: &[Entry], today: NaiveDate) -> Option<i64> {
+    let last = NaiveDate::parse_from_str(&data.last()?.date, "%d-%m-%Y").ok()?;
+    Some((today - last).num_days())
+}
+
+/// Number of counted (non-excluded) entries, the share of days from the
+/// first of them up to `today` that have one, and that first date.
+pub fn coverage(data: &[Entry], today: NaiveDate) -> Option<(usize, f64, NaiveDate)> {
+    let dates = data
+        .iter()
+        .filter(|x| !x.excluded)
+        .filter_map(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok())
+        .collect::<BTreeSet<_>>();
+    let first = *dates.first()?;
+    let last = today.max(*dates.last()?);
+    let days = (last - first).num_days() + 1;
+    Some((dates.len(), dates.len() as f64 / days as f64, first))
+}
+
+pub fn format_age(days: i64) -> String {
+    match days {
+        ..=0 => String::from("today"),
+        1 => String::from("yesterday"),
+        _ => format!("{} days ago", days),
+    }
+}
+
+/// Progress of `challenge` as of `today`. The starting weight is the newest
+/// entry on or before the first day, or the first entry of the period.
+pub fn challenge_progress(challenge: &Challenge, data: &[Entry], today: NaiveDate) -> ChallengeProgress {
+    let dated = data
+        .iter()
+        .filter(|x| !x.excluded)
+        .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.weight)))
+        .collect::<Vec<_>>();
+    let last_day = challenge.end.min(today);
+    let in_period = dated
+        .iter()
+        .filter(|(d, _)| challenge.start <= *d && *d <= last_day)
+        .collect::<Vec<_>>();
+    let baseline = dated
+        .iter()
+        .rev()
+        .find(|(d, _)| *d <= challenge.start)
+        .or(in_period.first().copied())
+        .map(|x| x.1);
+    let total = (challenge.end - challenge.start).num_days() + 1;
+    let elapsed = ((today - challenge.start).num_days() + 1).clamp(0, total);
+    ChallengeProgress {
+        baseline,
+        current: in_period.last().map(|x| x.1),
+        elapsed,
+        total,
+    }
+}
+
+/// Number of consecutive scheduled days with an entry, counting back from
+/// today. Today only breaks the streak once it is over, not while unlogged.
+/// Estimated entries are not weigh-ins and do not count.
+pub fn schedule_streak(data: &[Entry], schedule: &[Weekday], today: NaiveDate) -> usize {
+    let logged = data
+        .iter()
+        .filter(|x| !x.estimated)
+        .filter_map(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok())
+        .collect::<HashSet<_>>();
+    let Some(first) = logged.iter().min().copied() else {
+        return 0;
+    };
+    let mut day = today;
+    if !logged.contains(&today) {
+        day = today - Days::new(1);
+    }
+    let mut streak = 0;
+    while day >= first {
+        if schedule.is_empty() || schedule.contains(&day.weekday()) {
+            if !logged.contains(&day) {
+                break;
+            }
+            streak += 1;
+        }
+        day = day - Days::new(1);
+    }
+    streak
+}
+
+/// Next multiple of `step` between `current` and `target`, or `target` itself
+/// when it is closer than one step.
+pub fn next_milestone(current: f64, target: f64, step: f64) -> f64 {
+    if step <= 0f64 {
+        return target;
+    }
+    let milestone = if target < current {
+        ((current / step).ceil() - 1f64) * step
+    } else {
+        ((current / step).floor() + 1f64) * step
+    };
+    if (target < current && milestone <= target) || (target >= current && milestone >= target) {
+        target
+    } else {
+        milestone
+    }
+}
+
+const OUTLIER_MIN_ENTRIES: usize = 5;
+
+/// Days averaged around each day for the trend of `deseasonalize`.
+const SEASONAL_TREND_DAYS: i64 = 365;
+
+/// Span of data needed before a yearly seasonality is estimated.
+pub const SEASONAL_MIN_DAYS: i64 = 730;
+
+/// Bins of the seasonality; the last one also takes days 365 and 366.
+const SEASONAL_WEEKS: usize = 52;
+
+pub const FLAG_EXCLUDED: &str = "excluded";
+
+pub const FLAG_ESTIMATED: &str = "estimated";
+
+/// Window of the volatility stat and chart band.
+pub const VOLATILITY_DAYS: u64 = 14;
+
+const FIELD_RHR: &str = "rhr=";
+
+const FIELD_SOURCE: &str = "source=";
+
+const FIELD_DEVICE: &str = "device=";
+
+const FIELD_UNIT: &str = "unit=";
+
+pub const FIELD_ID: &str = "id=";
+
+const FIELD_MODIFIED: &str = "modified=";
+
+/// Second column of a tombstone row in place of the weight.
+pub const FLAG_DELETED: &str = "deleted";
+
+const LB_PER_KG: f64 = 2.204_622_621_8;
+
+const LB_PER_ST: f64 = 14.0;
+
+/// Characters of the bytes 0x80 to 0x9f in Windows-1252; five are undefined.
+const WINDOWS_1252_HIGH: [Option<char>; 32] = [
+    Some('€'), None, Some('‚'), Some('ƒ'), Some('„'), Some('…'), Some('†'), Some('‡'),
+    Some('ˆ'), Some('‰'), Some('Š'), Some('‹'), Some('Œ'), None, Some('Ž'), None,
+    None, Some('‘'), Some('’'), Some('“'), Some('”'), Some('•'), Some('–'), Some('—'),
+    Some('˜'), Some('™'), Some('š'), Some('›'), Some('œ'), None, Some('ž'), Some('Ÿ'),
+];
+
+pub const SOURCE_MANUAL: &str = "manual";
+
+pub const SOURCE_BACKFILL: &str = "backfill";
+
+const SOURCE_IMPORT_PREFIX: &str = "import:";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChallengeConfig, parse_challenges};
+    use crate::storage::write_csv;
+    use proptest::prelude::*;
+
+    fn entries(items: &[(&str, f64)]) -> Vec<Entry> {
+        items.iter().map(|(d, w)| Entry::new(d.to_string(), *w)).collect()
+    }
+
+    #[test]
+    fn merge_entries_combines_both_sides() {
+        let base = entries(&[("01-01-2024", 80.0), ("02-01-2024", 79.8)]);
+        let ours = entries(&[("01-01-2024", 80.0), ("02-01-2024", 79.8), ("04-01-2024", 79.5)]);
+        let theirs = entries(&[("01-01-2024", 80.0), ("02-01-2024", 79.8), ("03-01-2024", 79.6)]);
+        let (merged, report) = merge_entries(&base, &ours, &theirs);
+        let expected = entries(&[
+            ("01-01-2024", 80.0),
+            ("02-01-2024", 79.8),
+            ("03-01-2024", 79.6),
+            ("04-01-2024", 79.5),
+        ]);
+        assert!(merged == expected);
+        assert_eq!(report, MergeReport { merged: 1, conflicts: 0 });
+    }
+
+    #[test]
+    fn merge_entries_applies_remote_edits_and_deletions() {
+        let base = entries(&[("01-01-2024", 80.0), ("02-01-2024", 79.8)]);
+        let ours = base.clone();
+        let theirs = entries(&[("01-01-2024", 80.2)]);
+        let (merged, report) = merge_entries(&base, &ours, &theirs);
+        assert!(merged == entries(&[("01-01-2024", 80.2)]));
+        assert_eq!(report, MergeReport { merged: 2, conflicts: 0 });
+    }
+
+    #[test]
+    fn merge_entries_keeps_local_version_on_conflict() {
+        let base = entries(&[("01-01-2024", 80.0)]);
+        let ours = entries(&[("01-01-2024", 80.1)]);
+        let theirs = entries(&[("01-01-2024", 80.3)]);
+        let (merged, report) = merge_entries(&base, &ours, &theirs);
+        assert!(merged == ours);
+        assert_eq!(report, MergeReport { merged: 0, conflicts: 1 });
+    }
+
+    #[test]
+    fn merge_entries_conflict_keeps_the_latest_write() {
+        let lines = [
+            "01-01-2024, 80.1, id=a, modified=2024-01-02T09:30:00.250000Z",
+            "01-01-2024, 80.3, id=a, modified=2024-01-02T09:30:00.750000Z",
+            "02-01-2024, 79.9, id=b, modified=2024-01-02T09:00:00.000000Z",
+            "02-01-2024, 79.7, id=c, modified=2024-01-02T09:00:00.000000Z",
+        ];
+        let parsed = lines.iter().map(|x| Entry::from_fields(x).unwrap()).collect::<Vec<_>>();
+        assert_eq!(parsed.iter().map(|x| x.to_fields()).collect::<Vec<_>>(), lines);
+        let base = entries(&[("01-01-2024", 80.0)]);
+        let ours = vec![parsed[0].clone(), parsed[3].clone()];
+        let theirs = vec![parsed[1].clone(), parsed[2].clone()];
+        let (merged, report) = merge_entries(&base, &ours, &theirs);
+        let (swapped, _) = merge_entries(&base, &theirs, &ours);
+        assert_eq!(merged.iter().map(|x| x.to_fields()).collect::<Vec<_>>(), [lines[1], lines[3]]);
+        assert_eq!(swapped.iter().map(|x| x.to_fields()).collect::<Vec<_>>(), [lines[1], lines[3]]);
+        assert_eq!(report, MergeReport { merged: 0, conflicts: 2 });
+    }
+
+    #[test]
+    fn merge_entries_compares_deletions_by_time() {
+        let at = |hour| Utc.with_ymd_and_hms(2024, 1, 2, hour, 0, 0).unwrap();
+        let edited = |weight, hour| {
+            let mut entry = Entry::new(String::from("01-01-2024"), weight);
+            entry.touch(at(hour));
+            entry
+        };
+        let base = vec![edited(80.0, 7)];
+        let deleted = |hour| vec![Entry::tombstone(String::from("01-01-2024"), at(hour))];
+        // A stale deletion loses to a newer edit on the other side.
+        let (merged, report) = merge_entries(&base, &deleted(8), &[edited(80.3, 9)]);
+        assert!(merged == [edited(80.3, 9)]);
+        assert_eq!(report, MergeReport { merged: 0, conflicts: 1 });
+        // A local edit doesn't bring back an entry deleted later on disk.
+        let (merged, _) = merge_entries(&base, &[edited(80.3, 8)], &deleted(9));
+        assert!(merged == deleted(9));
+        // Tombstones are merged like any other change.
+        let (merged, report) = merge_entries(&[], &[], &deleted(9));
+        assert!(merged == deleted(9));
+        assert_eq!(report, MergeReport { merged: 1, conflicts: 0 });
+    }
+
+    #[test]
+    fn project_date_reaches_target() {
+        let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(project_date(from, 80.0, -0.5, 79.0), NaiveDate::from_ymd_opt(2024, 1, 15));
+        assert_eq!(project_date(from, 80.0, 0.7, 80.2), NaiveDate::from_ymd_opt(2024, 1, 3));
+    }
+
+    #[test]
+    fn goal_etas_separate_scale_and_trend() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + Days::new(d);
+        let mut data = (0..29)
+            .map(|d| Entry::new(day(d).format("%d-%m-%Y").to_string(), 90.0 - d as f64 / 7.0))
+            .collect::<Vec<_>>();
+        assert_eq!(goal_etas(&[], 80.0, 0.1), None);
+        // A low reading reaches the scale goal while the trend lags behind.
+        data[28].weight = 85.5;
+        let (scale, trend) = goal_etas(&data, 86.0, 0.1).unwrap();
+        assert_eq!(scale, GoalEta::Reached(day(28)));
+        let GoalEta::Projected(date) = trend else { panic!("{:?}", trend) };
+        assert!(date > day(28));
+        let (scale, trend) = goal_etas(&data, 80.0, 0.1).unwrap();
+        assert!(matches!(scale, GoalEta::Projected(_)) && matches!(trend, GoalEta::Projected(_)));
+        assert_eq!(goal_etas(&data, 95.0, 0.1).unwrap().1, GoalEta::Never);
+        assert_eq!(GoalEta::Reached(day(28)).describe(), "reached 29-01-2024");
+    }
+
+    #[test]
+    fn daily_note_line_is_replaced_or_appended() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 3).unwrap();
+        let template = "- Weight:: {weight} {unit}";
+        let line = note_line(template, date, "80.4", Unit::Kg);
+        assert_eq!(line, "- Weight:: 80.4 kg");
+        assert_eq!(update_note("", template, &line), "- Weight:: 80.4 kg\n");
+        let note = "* Journal\n  - Weight:: 81.0 kg\n  - Mood:: good\n";
+        assert_eq!(update_note(note, template, &line), "* Journal\n  - Weight:: 80.4 kg\n  - Mood:: good\n");
+        assert_eq!(update_note("# 03-05\nslept well", template, &line), "# 03-05\nslept well\n- Weight:: 80.4 kg\n");
+        // Without a prefix only an identical line counts as written.
+        assert_eq!(update_note("80.4 kg\n", "{weight} {unit}", "80.4 kg"), "80.4 kg\n");
+    }
+
+    #[test]
+    fn date_formats_are_detected_across_rows() {
+        let lines = |text: &str| split_import_lines(text);
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d);
+        assert_eq!(detect_date_formats(&lines("01-05-2024, 80\n02-05-2024, 79")), [DateFormat::Dmy]);
+        let iso = lines("Date,Weight\n2024-05-01T07:30:00Z, 80\n2024-05-02, 79");
+        assert_eq!(detect_date_formats(&iso), [DateFormat::Iso]);
+        // One row with a day above 12 settles the order of the whole file.
+        let us = lines("05/01/2024, 80\n05/13/2024, 79\n05/14/2024, 79");
+        assert_eq!(detect_date_formats(&us), [DateFormat::MdySlash]);
+        let ambiguous = lines("05/01/2024, 80\n05/02/2024, 79");
+        assert_eq!(detect_date_formats(&ambiguous), [DateFormat::DmySlash, DateFormat::MdySlash]);
+        assert_eq!(DateFormat::MdySlash.parse("05/01/2024"), day(5, 1));
+        assert_eq!(DateFormat::DmySlash.parse("05/01/2024"), day(1, 5));
+        // Noon UTC is the same day in every time zone up to ±11 hours.
+        assert_eq!(detect_date_formats(&lines("1714564800, 80")), [DateFormat::Epoch]);
+        assert_eq!(DateFormat::Epoch.parse("1714564800"), day(5, 1));
+        assert!(detect_date_formats(&lines("Table 1,,")).is_empty());
+
+        let mut data = Vec::new();
+        let mut report = ImportReport::default();
+        import_lines(&mut data, &us, 0, "import:us", DateFormat::MdySlash, &mut report);
+        assert_eq!(report.added, 3);
+        assert_eq!(data[1].date, "13-05-2024");
+        import_lines(&mut data, &lines("13/05/2024, 79"), 0, "import:us", DateFormat::MdySlash, &mut report);
+        assert_eq!(report.failed[0].1, "invalid date '13/05/2024', expected %m/%d/%Y");
+    }
+
+    #[test]
+    fn deseasonalize_removes_a_yearly_bump() {
+        let start = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+        // A slow loss with two kilos gained every December.
+        let data = (0..1095u64)
+            .step_by(3)
+            .map(|i| {
+                let date = start + Days::new(i);
+                let bump = if date.month() == 12 { 2.0 } else { 0.0 };
+                Entry::new(date.format("%d-%m-%Y").to_string(), 90.0 - i as f64 / 365.0 + bump)
+            })
+            .collect::<Vec<_>>();
+        let adjusted = deseasonalize(&data);
+        let at = |m, d| {
+            let i = (NaiveDate::from_ymd_opt(2022, m, d).unwrap() - start).num_days() as usize / 3;
+            (data[i].weight, adjusted[i].unwrap())
+        };
+        let (raw_dec, adj_dec) = at(12, 15);
+        let (raw_oct, adj_oct) = at(10, 16);
+        assert!(raw_dec - raw_oct > 1.5);
+        assert!((adj_dec - adj_oct).abs() < 0.6);
+        assert!(deseasonalize(&data[..200]).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn pending_changes_compare_by_date() {
+        let entry = |date: &str, weight| Entry::new(String::from(date), weight);
+        let saved = [entry("30-04-2024", 80.0), entry("01-05-2024", 81.0), entry("02-05-2024", 82.0)];
+        let mut current = vec![entry("01-05-2024", 81.5), entry("02-05-2024", 82.0)];
+        current.push(entry("03-05-2024", 83.0));
+        current[1].touch(Utc::now());
+        let changes = pending_changes(&saved, &current);
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(&changes[0], PendingChange::Deleted(x) if x.date == "30-04-2024"));
+        assert!(matches!(&changes[1], PendingChange::Edited(a, b) if a.weight == 81.0 && b.weight == 81.5));
+        assert!(matches!(&changes[2], PendingChange::Added(x) if x.date == "03-05-2024"));
+        assert!(pending_changes(&saved, &saved).is_empty());
+        let mut after = saved[0].clone();
+        after.weight = 80.5;
+        after.excluded = true;
+        assert_eq!(changed_fields(&saved[0], &after), ["weight", "excluded"]);
+    }
+
+    #[test]
+    fn table_filter_bounds_weight_and_date() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 20).unwrap();
+        let filter = TableFilter::parse(">=90 <=10-05", today).unwrap();
+        assert_eq!(filter.min_weight, Some(90.0));
+        assert_eq!(filter.to, NaiveDate::from_ymd_opt(2024, 5, 10));
+        assert!(filter.matches(&Entry::new(String::from("01-05-2024"), 90.0)));
+        assert!(!filter.matches(&Entry::new(String::from("01-05-2024"), 89.9)));
+        assert!(!filter.matches(&Entry::new(String::from("11-05-2024"), 95.0)));
+        assert_eq!(TableFilter::parse("", today), Ok(TableFilter::default()));
+        assert!(TableFilter::parse("90", today).is_err());
+        assert!(TableFilter::parse(">=31-02", today).is_err());
+    }
+
+    #[test]
+    fn resolve_date_completes_partial_and_iso_dates() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 20).unwrap();
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d);
+        assert_eq!(resolve_date("5", today), day(5, 5));
+        assert_eq!(resolve_date("5-3", today), day(3, 5));
+        assert_eq!(resolve_date("05-03-2024", today), day(3, 5));
+        assert_eq!(resolve_date("2024-03-05", today), day(3, 5));
+        assert_eq!(resolve_date("2024-13-05", today), None);
+    }
+
+    #[test]
+    fn coverage_counts_logged_days_since_the_first() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let mut data = vec![
+            Entry::new(String::from("01-05-2024"), 80.0),
+            Entry::new(String::from("02-05-2024"), 79.8),
+            Entry::new(String::from("04-05-2024"), 79.9),
+        ];
+        assert_eq!(coverage(&data, day(4)), Some((3, 0.75, day(1))));
+        assert_eq!(coverage(&data, day(10)), Some((3, 0.3, day(1))));
+        data[0].excluded = true;
+        assert_eq!(coverage(&data, day(4)), Some((2, 2.0 / 3.0, day(2))));
+        assert_eq!(coverage(&[], day(4)), None);
+    }
+
+    #[test]
+    fn project_date_wrong_direction_or_flat() {
+        let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(project_date(from, 80.0, 0.5, 79.0), None);
+        assert_eq!(project_date(from, 80.0, 0.0, 79.0), None);
+    }
+
+    #[test]
+    fn stones_are_formatted_and_parsed() {
+        assert_eq!(format_stones(174.4), "12 st 6.4 lb");
+        assert_eq!(format_stones(181.96), "13 st 0.0 lb");
+        assert_eq!(format_stones(5.0), "0 st 5.0 lb");
+        assert_eq!(parse_stones("12st6"), Some(174.0));
+        assert_eq!(parse_stones(" 12 St 6.4 lbs"), Some(174.4));
+        assert_eq!(parse_stones("12st"), Some(168.0));
+        assert_eq!(parse_stones(&format_stones(150.3)), Some(150.3));
+        for text in ["174.4", "12st14", "st6", "12.5st", "12st-1"] {
+            assert_eq!(parse_stones(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn year_heatmap_has_a_column_per_week() {
+        let mut data = entries(&[
+            ("31-12-2023", 80.0),
+            ("01-01-2024", 80.4),
+            ("03-01-2024", 90.0),
+            ("04-01-2024", 80.1),
+        ]);
+        data[2].excluded = true;
+        let today = NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
+        let columns = year_heatmap(&data, 2024, today);
+        // 2024 starts on a Monday and is a leap year.
+        assert_eq!(columns.len(), 53);
+        let days = columns[0];
+        assert!(matches!(days[0], HeatDay::Logged(Some(x)) if (x - 0.4).abs() < 1e-9));
+        assert_eq!(days[1], HeatDay::Missing);
+        assert_eq!(days[2], HeatDay::Logged(None));
+        assert!(matches!(days[3], HeatDay::Logged(Some(x)) if (x + 0.3).abs() < 1e-9));
+        assert_eq!(days[5], HeatDay::Outside);
+        // 2023 started on a Sunday, alone in its first column.
+        let columns = year_heatmap(&data, 2023, today);
+        assert_eq!(columns[0][..6], [HeatDay::Outside; 6]);
+        assert_eq!(columns[0][6], HeatDay::Missing);
+        assert_eq!(columns.last().unwrap()[6], HeatDay::Logged(None));
+    }
+
+    #[test]
+    fn rolling_averages_window_and_exclusions() {
+        let mut data = entries(&[
+            ("01-01-2024", 80.0),
+            ("03-01-2024", 79.0),
+            ("07-01-2024", 78.0),
+            ("08-01-2024", 90.0),
+            ("20-01-2024", 77.0),
+        ]);
+        data[3].excluded = true;
+        let averages = rolling_averages(&data, 7);
+        assert_eq!(averages, vec![Some(80.0), Some(79.5), Some(79.0), Some(78.5), Some(77.0)]);
+        data[4].excluded = true;
+        assert_eq!(rolling_averages(&data, 7)[4], None);
+    }
+
+    /// Six weeks of daily entries from 01-01-2024, losing 0.1 a day for the
+    /// first four and changing by `late` a day after that.
+    fn two_phase_data(late: f64) -> Vec<Entry> {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut weight = 90.0;
+        (0..42u64)
+            .map(|day| {
+                weight += if day < 28 { -0.1 } else { late };
+                Entry::new((start + Days::new(day)).format("%d-%m-%Y").to_string(), weight)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn goal_countdown_follows_the_trend() {
+        let mut data = [("01-05-2024", 100.0), ("02-05-2024", 96.0), ("03-05-2024", 94.0)]
+            .map(|(d, w)| Entry::new(d.to_string(), w))
+            .to_vec();
+        // The trend is 96.0, not the last reading of 94.0.
+        let countdown = goal_countdown(&data, None, 90.0, 0.5).unwrap();
+        assert!((countdown.remaining - 6.0).abs() < 1e-9);
+        assert!((countdown.percent - 60.0).abs() < 1e-9);
+        let countdown = goal_countdown(&data, Some(110.0), 90.0, 0.5).unwrap();
+        assert!((countdown.percent - 30.0).abs() < 1e-9);
+        assert_eq!(goal_countdown(&data, None, 100.0, 0.5), None);
+        data[2].weight = 70.0;
+        assert_eq!(goal_countdown(&data, None, 90.0, 0.5).map(|x| x.remaining), Some(0.0));
+        // Moving away from a target above the start leaves more than planned.
+        let countdown = goal_countdown(&data, None, 110.0, 0.5).unwrap();
+        assert!((countdown.remaining - 26.0).abs() < 1e-9);
+        assert!((countdown.percent - 260.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weekly_rates_start_after_two_weeks() {
+        let data = two_phase_data(0.0);
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let rates = weekly_rates(&data).into_iter().filter(|x| x.0 <= day(31)).collect::<Vec<_>>();
+        // The first week before has an entry from the 8th on.
+        assert_eq!(rates.first().map(|x| x.0), Some(day(8)));
+        assert_eq!(rates.last().map(|x| x.0), Some(day(31)));
+        assert!(rates.iter().filter(|x| x.0 >= day(14) && x.0 <= day(28)).all(|x| (x.1 + 0.7).abs() < 1e-9));
+        // The plateau from the 29th pulls the rate towards zero.
+        assert!(rates.last().unwrap().1 > -0.7);
+        for (date, rate) in &rates {
+            assert!(weekly_rate(&data, *date).is_some_and(|x| (x - rate).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn trend_shifts_are_detected() {
+        let today = NaiveDate::from_ymd_opt(2024, 2, 11).unwrap();
+        let kind = |late: f64| trend_shift(&two_phase_data(late), today, 2, 0.2, 2.0).map(|x| x.kind);
+        assert_eq!(kind(-0.03), Some(TrendShiftKind::Slower));
+        assert_eq!(kind(0.05), Some(TrendShiftKind::Reversed));
+        assert_eq!(kind(-0.25), Some(TrendShiftKind::Faster));
+        assert_eq!(kind(-0.08), None);
+        let shift = trend_shift(&two_phase_data(-0.03), today, 2, 0.2, 2.0).unwrap();
+        assert!((shift.before + 0.7).abs() < 1e-9 && (shift.now + 0.21).abs() < 1e-9);
+        // Not enough history for the earlier week.
+        assert_eq!(trend_shift(&two_phase_data(-0.03), today, 5, 0.2, 2.0), None);
+    }
+
+    #[test]
+    fn rolling_std_devs_window_and_exclusions() {
+        let mut data = entries(&[
+            ("01-01-2024", 80.0),
+            ("02-01-2024", 82.0),
+            ("03-01-2024", 81.0),
+            ("20-01-2024", 79.0),
+            ("21-01-2024", 85.0),
+        ]);
+        data[4].excluded = true;
+        let devs = rolling_std_devs(&data, 14)
+            .iter()
+            .map(|x| x.map(|x| format!("{:.3}", x)))
+            .collect::<Vec<_>>();
+        let expected = [None, Some("1.414"), Some("1.000"), None, None];
+        assert_eq!(devs, expected.map(|x| x.map(String::from)));
+        data[4].excluded = false;
+        assert_eq!(volatility(&data).map(|x| format!("{:.3}", x)).as_deref(), Some("4.243"));
+    }
+
+    #[test]
+    fn pearson_perfect_and_degenerate() {
+        let r = pearson(&[(80.0, 60.0), (81.0, 62.0), (82.0, 64.0)]).unwrap();
+        assert!((r - 1.0).abs() < 1e-9);
+        let r = pearson(&[(80.0, 64.0), (81.0, 62.0), (82.0, 60.0)]).unwrap();
+        assert!((r + 1.0).abs() < 1e-9);
+        assert_eq!(pearson(&[(80.0, 60.0)]), None);
+        assert_eq!(pearson(&[(80.0, 60.0), (80.0, 62.0)]), None);
+    }
+
+    #[test]
+    fn rolling_correlation_needs_enough_points_in_window() {
+        let mut data = entries(&[
+            ("01-01-2024", 80.0),
+            ("02-01-2024", 81.0),
+            ("03-01-2024", 82.0),
+            ("20-01-2024", 83.0),
+            ("21-01-2024", 82.0),
+        ]);
+        for (entry, rhr) in data.iter_mut().zip([60.0, 61.0, 62.0, 63.0, 64.0]) {
+            entry.rhr = Some(rhr);
+        }
+        let rolling = rolling_correlation(&data, 7, 2);
+        let dates = rolling.iter().map(|x| x.0.format("%d-%m-%Y").to_string()).collect::<Vec<_>>();
+        assert_eq!(dates, ["02-01-2024", "03-01-2024", "21-01-2024"]);
+        assert!((rolling[2].1 + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn import_line_with_heart_rate() {
+        let entry = parse_import_line("01-01-2024, 80.5, 58", DateFormat::Dmy).unwrap();
+        assert_eq!((entry.weight, entry.rhr), (80.5, Some(58.0)));
+        let entry = parse_import_line("01-01-2024, 80.5, rhr=61", DateFormat::Dmy).unwrap();
+        assert_eq!(entry.rhr, Some(61.0));
+        let entry = parse_import_line("01-01-2024, 80.5, excluded", DateFormat::Dmy).unwrap();
+        assert!(entry.excluded && entry.rhr.is_none());
+        assert!(parse_import_line("01-01-2024, 80.5, 400", DateFormat::Dmy).is_err());
+    }
+
+    #[test]
+    fn spreadsheet_exports_are_tidied_with_a_report() {
+        let text = "Table 1,,,\n\"Date\",\"Weight\",,\n01-01-2024,\"80,5\",,\n02-01-2024,80.1,58,\n,,,\nExported by Numbers,,,";
+        let lines = split_import_lines(text);
+        let mut data = Vec::new();
+        let mut report = ImportReport::default();
+        import_lines(&mut data, &lines, 0, "import:sheet", DateFormat::Dmy, &mut report);
+        assert_eq!(data.iter().map(|x| x.to_fields()).collect::<Vec<_>>(), [
+            "01-01-2024, 80.5, source=import:sheet",
+            "02-01-2024, 80.1, rhr=58, source=import:sheet",
+        ]);
+        assert_eq!(report.stripped, 2);
+        assert!(report.failed.is_empty());
+        let skipped = report.skipped.iter().map(|x| x.0).collect::<Vec<_>>();
+        assert_eq!(skipped, [1, 2, 6]);
+        assert!(split_import_lines("\u{feff}date,weight\n")[0].is_empty());
+    }
+
+    #[test]
+    fn imports_are_decoded_from_common_encodings() {
+        let text = "Date,Weight\n01-01-2024,80.5\n";
+        let utf16 = |bom: &[u8], le: bool| {
+            let units = text.encode_utf16().flat_map(|x| if le { x.to_le_bytes() } else { x.to_be_bytes() });
+            bom.iter().copied().chain(units).collect::<Vec<_>>()
+        };
+        let utf8_bom = [&[0xef, 0xbb, 0xbf], text.as_bytes()].concat();
+        for bytes in [utf8_bom, utf16(&[0xff, 0xfe], true), utf16(&[0xfe, 0xff], false), utf16(&[], true)] {
+            assert_eq!(decode_import(bytes).unwrap(), text);
+        }
+        assert_eq!(TextEncoding::detect(&utf16(&[], false)), (TextEncoding::Utf16Be, 0));
+
+        let ansi = b"Date,Weight,Note\n01-01-2024,80.5,caf\xe9 \x80\n".to_vec();
+        assert_eq!(TextEncoding::detect(&ansi).0, TextEncoding::Windows1252);
+        assert!(decode_import(ansi).unwrap().ends_with("café €\n"));
+        let error = decode_import(b"80,5 \x81".to_vec()).unwrap_err().to_string();
+        assert!(error.contains("Windows-1252: undefined byte 0x81 at offset 5"), "{}", error);
+        let error = decode_import(vec![0xff, 0xfe, 0x00, 0xd8, 0x41, 0x00]).unwrap_err().to_string();
+        assert!(error.contains("UTF-16LE: unpaired surrogate 0xd800"), "{}", error);
+    }
+
+    #[test]
+    fn rows_with_a_unit_are_normalized_and_stored_unchanged() {
+        let lines = ["01-01-2024, 80.0, unit=kg", "02-01-2024, 176.4, unit=lb", "03-01-2024, 175.9"];
+        let mut data = lines.iter().map(|x| Entry::from_fields(x).unwrap()).collect::<Vec<_>>();
+        let normalized = data.clone();
+        data.iter_mut().for_each(|x| x.normalize(Unit::Lb));
+        let weights = data.iter().map(|x| WEIGHT.format(x.weight)).collect::<Vec<_>>();
+        assert_eq!(weights, ["176.4", "176.4", "175.9"]);
+        assert!(data == normalized);
+        assert_eq!(data.iter().map(|x| x.to_fields()).collect::<Vec<_>>(), lines);
+        data.iter_mut().for_each(|x| x.normalize(Unit::Kg));
+        assert_eq!(WEIGHT.format(data[1].weight), "80.0");
+
+        edit_entry(&mut data[0], 177.0, None);
+        assert!(data[0].to_fields().starts_with("01-01-2024, 177.0, source=manual"));
+        let entry = parse_import_line("01-01-2024, 80.0, lbs", DateFormat::Dmy).unwrap();
+        assert_eq!(entry.original, Some((Unit::Lb, 80.0)));
+        assert!(parse_import_line("01-01-2024, 80.0, unit=stone", DateFormat::Dmy).is_err());
+    }
+
+    #[test]
+    fn imported_entries_keep_or_get_a_source() {
+        let lines = ["01-01-2024, 80.5", "02-01-2024, 80.1, source=ble-scale"].map(String::from);
+        let mut data = Vec::new();
+        let source = import_source("/tmp/apple health.csv", None);
+        assert_eq!(source, "import:apple-health");
+        import_lines(&mut data, &lines, 0, &source, DateFormat::Dmy, &mut ImportReport::default());
+        let sources = data.iter().map(|x| x.source.as_deref()).collect::<Vec<_>>();
+        assert_eq!(sources, [Some("import:apple-health"), Some("ble-scale")]);
+        assert_eq!(import_source("https://example.com/export.csv?key=1", None), "import:export");
+        assert_eq!(import_source("x.csv", Some("api")), "import:api");
+    }
+
+    fn challenge(start: &str, weeks: u32, change: f64) -> Challenge {
+        let config = ChallengeConfig {
+            name: String::from("cut"),
+            start: start.to_string(),
+            weeks,
+            change,
+        };
+        parse_challenges(&[config]).unwrap().remove(0)
+    }
+
+    #[test]
+    fn parse_challenges_computes_end_and_rejects_invalid() {
+        let c = challenge("01-03-2024", 8, -3.0);
+        assert_eq!(c.end, NaiveDate::from_ymd_opt(2024, 4, 25).unwrap());
+        let invalid = ChallengeConfig {
+            name: String::from("none"),
+            start: String::from("01-03-2024"),
+            weeks: 0,
+            change: -1.0,
+        };
+        assert!(parse_challenges(&[invalid]).is_err());
+    }
+
+    #[test]
+    fn challenge_progress_uses_weight_before_start() {
+        let c = challenge("03-01-2024", 2, -2.0);
+        let data = entries(&[
+            ("01-01-2024", 82.0),
+            ("05-01-2024", 81.0),
+            ("10-01-2024", 79.8),
+            ("20-01-2024", 75.0),
+        ]);
+        let today = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+        let progress = challenge_progress(&c, &data, today);
+        assert_eq!((progress.baseline, progress.current), (Some(82.0), Some(79.8)));
+        assert_eq!((progress.elapsed, progress.remaining()), (8, 6));
+        assert!(c.reached(progress.change().unwrap()));
+        assert_eq!(c.required_pace(&progress), -1.0);
+
+        // Entries after the end do not count towards the result.
+        let later = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let progress = challenge_progress(&c, &data, later);
+        assert_eq!((progress.current, progress.remaining()), (Some(79.8), 0));
+    }
+
+    #[test]
+    fn backfill_interpolates_gaps_between_measured_entries() {
+        let mut data = entries(&[
+            ("01-01-2024", 80.0),
+            ("02-01-2024", 95.0),
+            ("05-01-2024", 79.0),
+            ("06-01-2024", 78.9),
+        ]);
+        data[1].excluded = true;
+        let added = backfill(&mut data);
+        assert_eq!(added, ["03-01-2024", "04-01-2024"]);
+        let weights = data.iter().map(|x| (x.weight, x.estimated)).collect::<Vec<_>>();
+        assert_eq!(
+            weights,
+            [(80.0, false), (95.0, false), (79.5, true), (79.3, true), (79.0, false), (78.9, false)]
+        );
+        assert!(backfill(&mut data).is_empty());
+    }
+
+    /// A mutation as made from the table, the input popup or `import`.
+    #[derive(Clone, Debug)]
+    enum Op {
+        Append { day: u64, weight: f64, rhr: Option<f64>, excluded: bool },
+        Edit { index: usize, weight: f64, rhr: Option<f64> },
+        Delete { index: usize },
+        Import(Vec<String>),
+    }
+
+    fn day(offset: u64) -> String {
+        let first = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        (first + Days::new(offset)).format("%d-%m-%Y").to_string()
+    }
+
+    fn weight() -> impl Strategy<Value = f64> {
+        (1..10000u32).prop_map(|x| x as f64 / 10.0)
+    }
+
+    fn rhr() -> impl Strategy<Value = Option<f64>> {
+        proptest::option::of((20..=250u32).prop_map(f64::from))
+    }
+
+    fn import_line() -> impl Strategy<Value = String> {
+        let unit = proptest::option::of(prop_oneof![Just(Unit::Kg), Just(Unit::Lb)]);
+        let valid = (0..60u64, weight(), rhr(), any::<bool>(), unit).prop_map(|(d, w, r, excluded, unit)| {
+            let mut entry = Entry::new(day(d), w);
+            entry.rhr = r;
+            entry.excluded = excluded;
+            entry.original = unit.map(|u| (u, w));
+            entry.to_fields()
+        });
+        prop_oneof![4 => valid, 1 => "[ -~]{0,24}"]
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0..60u64, weight(), rhr(), any::<bool>())
+                .prop_map(|(day, weight, rhr, excluded)| Op::Append { day, weight, rhr, excluded }),
+            (any::<usize>(), weight(), rhr()).prop_map(|(index, weight, rhr)| Op::Edit { index, weight, rhr }),
+            any::<usize>().prop_map(|index| Op::Delete { index }),
+            proptest::collection::vec(import_line(), 0..8).prop_map(Op::Import),
+        ]
+    }
+
+    fn apply(data: &mut Vec<Entry>, op: Op) -> Result<(), TestCaseError> {
+        match op {
+            Op::Append { day: d, weight, rhr, excluded } => {
+                let exists = data.iter().any(|x| x.date == day(d));
+                let mut entry = Entry::new(day(d), weight);
+                entry.rhr = rhr;
+                entry.excluded = excluded;
+                entry.source = Some(String::from(SOURCE_MANUAL));
+                prop_assert_eq!(insert_entry(data, entry), !exists);
+            }
+            Op::Edit { index, weight, rhr } if !data.is_empty() => {
+                let len = data.len();
+                edit_entry(&mut data[index % len], weight, rhr);
+            }
+            Op::Delete { index } if !data.is_empty() => {
+                let len = data.len();
+                data.remove(index % len);
+            }
+            Op::Import(lines) => {
+                let before = data.len();
+                let mut report = ImportReport::default();
+                let added = import_lines(data, &lines, 0, "import:test", DateFormat::Dmy, &mut report);
+                prop_assert_eq!(added.len(), data.len() - before);
+                let non_empty = lines.iter().filter(|x| !x.split(',').all(|x| x.trim().is_empty())).count();
+                let handled = report.added + report.duplicates + report.failed.len() + report.skipped.len();
+                prop_assert_eq!(handled, non_empty);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn mutations_keep_dates_sorted_and_unique(ops in proptest::collection::vec(op(), 0..40)) {
+            let mut data = Vec::new();
+            for op in ops {
+                apply(&mut data, op)?;
+                let dates = data
+                    .iter()
+                    .map(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").unwrap())
+                    .collect::<Vec<_>>();
+                prop_assert!(dates.windows(2).all(|x| x[0] < x[1]));
+            }
+        }
+
+        #[test]
+        fn export_import_round_trip(ops in proptest::collection::vec(op(), 0..40)) {
+            let mut data = Vec::new();
+            for op in ops {
+                apply(&mut data, op)?;
+            }
+            let mut buffer = Vec::new();
+            write_csv(&data, &mut buffer).unwrap();
+            let lines = split_import_lines(&String::from_utf8(buffer).unwrap());
+            let mut imported = Vec::new();
+            let mut report = ImportReport::default();
+            import_lines(&mut imported, &lines, 0, "import:round-trip", DateFormat::Dmy, &mut report);
+            prop_assert!(report.failed.is_empty());
+            prop_assert!(imported == data);
+        }
+    }
+}
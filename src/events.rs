@@ -0,0 +1,1223 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    style::{Style, Stylize},
+};
+use std::time::{Duration, Instant};
+use tracing::warn;
+use tui_textarea::{CursorMove, Input, TextArea};
+
+use crate::WtError;
+use crate::app::{
+    App, ChartTimeFrame, CompareMode, FrameType, InputField, MessageType, SMOOTHING_WINDOWS, Setting,
+    TextMode, WindowType, merge_message,
+};
+use crate::data::{RHR, TableFilter, WEIGHT, resolve_date, weekly_change};
+use crate::keymap::{self, Action, Mode};
+
+/// Single-line editor sized for a table cell, with the cursor after `text`.
+fn cell_editor(text: String) -> TextArea<'static> {
+    let mut area = TextArea::new(vec![text]);
+    area.move_cursor(CursorMove::End);
+    area.set_cursor_line_style(Style::default());
+    area.set_style(Style::default().black().on_white());
+    area
+}
+
+const SIMULATOR_DEFAULT_RATE: f64 = -0.5;
+
+const MAX_COUNT: usize = 9999;
+
+impl App<'_> {
+    pub fn init_text_area(&mut self) {
+        let edited = match self.text_mode {
+            Some(TextMode::Edit) => self.table_state.selected().and_then(|idx| self.data.get_mut().get(idx).cloned()),
+            _ => None,
+        };
+        let texts = self
+            .input_fields
+            .iter()
+            .map(|field| match (field, &edited) {
+                (InputField::Date, Some(entry)) => entry.date.clone(),
+                (InputField::Date, None) if self.text_mode == Some(TextMode::Append) => {
+                    self.today().format("%d-%m-%Y").to_string()
+                }
+                (InputField::Weight, Some(entry)) => self.format_weight(entry.weight),
+                (InputField::Rhr, Some(entry)) => entry.rhr.map(|x| RHR.format(x)).unwrap_or_default(),
+                (InputField::Device, Some(entry)) => entry.device.clone().unwrap_or_default(),
+                (InputField::Device, None) => self.config.devices.default.clone().unwrap_or_default(),
+                _ => String::new(),
+            })
+            .collect::<Vec<_>>();
+        self.text_area = texts
+            .iter()
+            .map(|text| {
+                let mut area = TextArea::new(vec![text.clone()]);
+                area.move_cursor(CursorMove::End);
+                area
+            })
+            .collect();
+        self.text_is_valid = (0..texts.len()).map(|i| self.field_is_valid(i)).collect();
+        self.selected_area = 1;
+        self.input_original = (self.text_mode == Some(TextMode::Edit)).then_some(texts);
+        self.discard_confirm = false;
+    }
+
+    /// Text typed into the box of `field`, empty if the popup has none.
+    fn field_text(&self, field: InputField) -> &str {
+        self.input_fields
+            .iter()
+            .position(|x| *x == field)
+            .and_then(|i| self.text_area.get(i))
+            .map_or("", |x| x.lines()[0].as_str())
+    }
+
+    /// Whether the box at `idx` holds something submit would accept.
+    pub fn field_is_valid(&self, idx: usize) -> bool {
+        match self.input_fields[idx] {
+            InputField::Date => self.input_date().is_some(),
+            InputField::Weight => self.parse_weight(&self.text_area[idx].lines()[0]).is_ok(),
+            InputField::Rhr => self.input_rhr().is_ok(),
+            InputField::Device => self.input_device().is_ok(),
+        }
+    }
+
+    /// Moves to the next box of the input popup; the date of an edited entry
+    /// can't be changed, so its box is skipped.
+    fn next_input_field(&mut self) {
+        let count = self.input_fields.len();
+        for step in 1..=count {
+            let idx = (self.selected_area + step) % count;
+            if self.text_mode == Some(TextMode::Append) || self.input_fields[idx] != InputField::Date {
+                self.selected_area = idx;
+                return;
+            }
+        }
+    }
+
+    /// Edited fields of the input popup that differ from the entry and would
+    /// be accepted on submit.
+    fn has_unsaved_edit(&self) -> bool {
+        let Some(original) = &self.input_original else {
+            return false;
+        };
+        let changed = original.iter().zip(&self.text_area).any(|(o, t)| *o != t.lines()[0]);
+        changed
+            && self.input_date().is_some()
+            && self.parse_weight(&self.text_area[1].lines()[0]).is_ok()
+            && self.input_rhr().is_ok()
+    }
+
+    /// Closes the input popup, asking first if that would throw away a valid
+    /// correction.
+    fn cancel_input(&mut self) {
+        if self.has_unsaved_edit() && !self.discard_confirm {
+            self.discard_confirm = true;
+            self.messages.push("Discard changes? Esc to discard, Enter to save", MessageType::Warning);
+            return;
+        }
+        self.discard_confirm = false;
+        self.current_window = WindowType::MainWindow;
+        self.hint_page = 0;
+    }
+
+    /// Shows the lock screen if a passphrase is configured.
+    pub fn lock(&mut self) {
+        if self.config.lock.passphrase.is_none() {
+            return;
+        }
+        self.locked = true;
+        self.lock_failed = false;
+        self.lock_area = TextArea::default();
+        self.lock_area.set_mask_char('•');
+        self.inline_edit = None;
+    }
+
+    pub fn lock_if_idle(&mut self) {
+        let idle = self.config.lock.idle_minutes;
+        if !self.locked && idle > 0 && self.last_input.elapsed() >= Duration::from_secs(idle * 60) {
+            self.lock();
+        }
+    }
+
+    fn unlock(&mut self) {
+        let passphrase = self.lock_area.lines().join("\n");
+        self.lock_area = TextArea::default();
+        self.lock_area.set_mask_char('•');
+        if self.config.lock.verify(&passphrase) {
+            self.locked = false;
+            self.lock_failed = false;
+        } else {
+            warn!("wrong passphrase on the lock screen");
+            self.lock_failed = true;
+        }
+    }
+
+    /// Resting heart rate typed in the input popup; the field is optional.
+    pub fn input_rhr(&self) -> Result<Option<f64>, String> {
+        let text = self.field_text(InputField::Rhr).trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        RHR.validate(text).map(Some)
+    }
+
+    /// Device typed in the input popup; blank for none.
+    pub fn input_device(&self) -> Result<Option<String>, String> {
+        let text = self.field_text(InputField::Device).trim();
+        if text.contains(',') {
+            return Err(String::from("must not contain a comma"));
+        }
+        Ok(Some(text.to_string()).filter(|x| !x.is_empty()))
+    }
+
+    /// Adjusts the weight input by `delta`, starting from the last logged
+    /// weight if the field doesn't hold a valid weight yet.
+    fn spin_weight(&mut self, delta: f64) {
+        let current = self.parse_weight(&self.text_area[1].lines()[0]).ok();
+        let Some(base) = current.or_else(|| self.data.borrow().last().map(|x| x.weight)) else {
+            return;
+        };
+        let weight = ((base + delta) * 10.0).round() / 10.0;
+        let text = self.format_weight(weight.clamp(WEIGHT.min, WEIGHT.max));
+        self.text_area[1] = TextArea::new(vec![text]);
+        self.text_area[1].move_cursor(CursorMove::End);
+    }
+
+    /// Date typed in the input popup, with missing month and year filled in.
+    pub fn input_date(&self) -> Option<NaiveDate> {
+        resolve_date(&self.text_area[0].lines()[0], self.today())
+    }
+
+    fn toggle_simulator(&mut self) {
+        self.simulated_rate = match self.simulated_rate {
+            Some(_) => None,
+            None => {
+                let rate = weekly_change(&self.stats_data()).unwrap_or(SIMULATOR_DEFAULT_RATE);
+                Some((rate * 10.0).round() / 10.0)
+            }
+        };
+    }
+
+    /// Widens or narrows the chart's moving average to the next window in
+    /// `SMOOTHING_WINDOWS`; narrowing the smallest one hides it.
+    fn step_smoothing(&mut self, wider: bool) {
+        let idx = self.smoothing_days.and_then(|days| SMOOTHING_WINDOWS.iter().position(|x| *x == days));
+        self.smoothing_days = match (idx, wider) {
+            (None, true) => Some(SMOOTHING_WINDOWS[0]),
+            (None, false) | (Some(0), false) => None,
+            (Some(i), true) => Some(SMOOTHING_WINDOWS[(i + 1).min(SMOOTHING_WINDOWS.len() - 1)]),
+            (Some(i), false) => Some(SMOOTHING_WINDOWS[i - 1]),
+        };
+    }
+
+    fn select_date(&mut self, date: &str) {
+        let idx = self.data.borrow().iter().position(|x| x.date == date);
+        self.table_state.select(idx);
+    }
+
+    /// Moves the table selection to the next or previous row the filter shows.
+    fn select_row(&mut self, forward: bool) {
+        if self.table_filter.is_none() {
+            if forward {
+                self.table_state.select_next();
+            } else {
+                self.table_state.select_previous();
+            }
+            return;
+        }
+        let data_ref = self.data.borrow();
+        let visible = (0..data_ref.len()).filter(|i| self.shows_row(&data_ref[*i])).collect::<Vec<_>>();
+        drop(data_ref);
+        let current = self.table_state.selected();
+        let next = if forward {
+            visible.iter().find(|i| current.is_none_or(|c| **i > c)).or(visible.last())
+        } else {
+            visible.iter().rev().find(|i| current.is_some_and(|c| **i < c)).or(visible.first())
+        };
+        self.table_state.select(next.copied());
+    }
+
+    /// Sets the table filter typed into the filter popup; an empty one
+    /// removes it. A selected row the filter hides gives way to the last
+    /// shown row.
+    fn apply_filter(&mut self) -> Result<(), WtError> {
+        let text = self.filter_area.lines()[0].trim().to_string();
+        let filter = TableFilter::parse(&self.delocalize(&text), self.today()).map_err(WtError::Validation)?;
+        self.table_filter = (filter != TableFilter::default()).then_some(filter);
+        self.filter_offset = 0;
+        let data_ref = self.data.borrow();
+        let selected = self.table_state.selected().and_then(|i| data_ref.get(i));
+        if !selected.is_some_and(|x| self.shows_row(x)) {
+            let last = (0..data_ref.len()).rev().find(|i| self.shows_row(&data_ref[*i]));
+            drop(data_ref);
+            self.table_state.select(last);
+        }
+        Ok(())
+    }
+
+    /// Places an editor for the selected row's weight directly into its table cell.
+    fn start_inline_edit(&mut self) {
+        let Some(weight) = self
+            .table_state
+            .selected()
+            .and_then(|idx| self.data.get_mut().get(idx))
+            .map(|x| x.weight)
+        else {
+            return;
+        };
+        self.rm_confirm = false;
+        self.inline_edit = Some(cell_editor(self.format_weight(weight)));
+    }
+
+    fn commit_inline_edit(&mut self) {
+        let Some(area) = &self.inline_edit else {
+            return;
+        };
+        match self.parse_weight(&area.lines()[0]) {
+            Ok(weight) => {
+                let rhr = self.table_state.selected().and_then(|idx| self.data.get_mut().get(idx)?.rhr);
+                self.text_mode = Some(TextMode::Edit);
+                self.modify_data((String::new(), Some((weight, rhr))));
+                self.text_mode = None;
+                self.inline_edit = None;
+            }
+            Err(reason) => {
+                self.messages.push(format!("Invalid weight: {}!", reason), MessageType::Error);
+            }
+        }
+    }
+
+    /// Steps the inline edited weight like `spin_weight` does in the popup.
+    fn spin_inline_weight(&mut self, step: f64) {
+        let Some(area) = &self.inline_edit else {
+            return;
+        };
+        let Ok(weight) = self.parse_weight(&area.lines()[0]) else {
+            return;
+        };
+        let weight = ((weight + step) * 10.0).round() / 10.0;
+        let text = self.format_weight(weight.clamp(WEIGHT.min, WEIGHT.max));
+        self.inline_edit = Some(cell_editor(text));
+    }
+
+    fn open_edit_popup(&mut self) {
+        self.current_window = WindowType::InputPopup;
+        self.hint_page = 0;
+        self.text_mode = Some(TextMode::Edit);
+        self.init_text_area();
+    }
+
+    /// First press asks for confirmation, the second deletes `rm_count` rows
+    /// starting at the selected one.
+    fn delete_selected(&mut self, count: usize) -> Result<(), WtError> {
+        if self.rm_confirm {
+            let idx = self.table_state.selected_mut();
+            if idx.is_none() {
+                return Err(WtError::Validation(String::from("No row is selected.")));
+            }
+            let idx = idx.unwrap();
+            let trashed = self.trash.len();
+            for _ in 0..self.rm_count {
+                // Rows hidden by the filter in between are skipped.
+                let data_ref = self.data.borrow();
+                let Some(selected) = data_ref.iter().skip(idx).find(|x| self.shows_row(x)).cloned() else {
+                    break;
+                };
+                drop(data_ref);
+                self.select_date(&selected.date);
+                self.modify_data((selected.date, None));
+            }
+            if self.table_filter.is_some() {
+                let data_ref = self.data.borrow();
+                let next = (idx..data_ref.len()).chain((0..idx).rev()).find(|i| self.shows_row(&data_ref[*i]));
+                drop(data_ref);
+                self.table_state.select(next);
+            }
+            let msg = match self.trash.len() - trashed {
+                1 => String::from("Moved to trash, press 'T' to restore"),
+                n => format!("Moved {} rows to trash, press 'T' to restore", n),
+            };
+            self.messages.push(msg, MessageType::Info);
+            self.rm_count = 1;
+        } else {
+            self.rm_confirm = true;
+            self.rm_count = count;
+            let msg = if count > 1 {
+                format!("Press 'd' again to delete {} rows", count)
+            } else {
+                String::from("Press 'd' again to confirm deletion")
+            };
+            self.messages.push(msg, MessageType::Warning);
+        }
+        Ok(())
+    }
+
+    /// Moves the chart window one step back or forward in the current timeframe.
+    fn pan_chart(&mut self, forward: bool) {
+        if forward {
+            match self.current_tf {
+                ChartTimeFrame::Month | ChartTimeFrame::Months => {
+                    self.selected_date_m = self
+                        .selected_date_m
+                        .checked_add_months(Months::new(1))
+                        .unwrap()
+                }
+                ChartTimeFrame::Quarter => {
+                    self.selected_date_m = self
+                        .selected_date_m
+                        .checked_add_months(Months::new(3))
+                        .unwrap()
+                }
+                ChartTimeFrame::Year | ChartTimeFrame::YearOverYear => {
+                    self.selected_date_y = self
+                        .selected_date_y
+                        .checked_add_months(Months::new(12))
+                        .unwrap()
+                }
+                ChartTimeFrame::WindowYear => {
+                    self.selected_date_wy = self
+                        .selected_date_wy
+                        .checked_add_days(Days::new(1))
+                        .unwrap()
+                }
+                ChartTimeFrame::Overview => {}
+            }
+        } else {
+            match self.current_tf {
+                ChartTimeFrame::Month | ChartTimeFrame::Months => {
+                    self.selected_date_m = self
+                        .selected_date_m
+                        .checked_sub_months(Months::new(1))
+                        .unwrap()
+                }
+                ChartTimeFrame::Quarter => {
+                    self.selected_date_m = self
+                        .selected_date_m
+                        .checked_sub_months(Months::new(3))
+                        .unwrap()
+                }
+                ChartTimeFrame::Year | ChartTimeFrame::YearOverYear => {
+                    self.selected_date_y = self
+                        .selected_date_y
+                        .checked_sub_months(Months::new(12))
+                        .unwrap()
+                }
+                ChartTimeFrame::WindowYear => {
+                    self.selected_date_wy = self
+                        .selected_date_wy
+                        .checked_sub_days(Days::new(1))
+                        .unwrap()
+                }
+                ChartTimeFrame::Overview => {}
+            }
+        }
+    }
+
+    /// Date the window of the current timeframe is anchored at.
+    fn selected_chart_date(&mut self) -> &mut NaiveDate {
+        match self.current_tf {
+            ChartTimeFrame::Month | ChartTimeFrame::Quarter | ChartTimeFrame::Months => {
+                &mut self.selected_date_m
+            }
+            ChartTimeFrame::Year | ChartTimeFrame::YearOverYear => &mut self.selected_date_y,
+            // The overview always ends today; paging moves a date it never reads.
+            ChartTimeFrame::WindowYear | ChartTimeFrame::Overview => &mut self.selected_date_wy,
+        }
+    }
+
+    /// Moves the chart window by its own width, so that a year in the one
+    /// year window is a single key press instead of 365.
+    fn page_chart(&mut self, forward: bool) {
+        let months = Months::new(match self.current_tf {
+            ChartTimeFrame::Month => 1,
+            ChartTimeFrame::Quarter => 3,
+            ChartTimeFrame::Months => self.config.chart.window_months,
+            ChartTimeFrame::Year | ChartTimeFrame::YearOverYear | ChartTimeFrame::WindowYear => 12,
+            ChartTimeFrame::Overview => return,
+        });
+        let date = self.selected_chart_date();
+        let moved = if forward {
+            date.checked_add_months(months)
+        } else {
+            date.checked_sub_months(months)
+        };
+        if let Some(moved) = moved {
+            *date = moved;
+        }
+    }
+
+    fn chart_to_today(&mut self) {
+        let today = self.today();
+        *self.selected_chart_date() = today;
+    }
+
+    /// Cycles the month comparison view: off, previous month, previous year.
+    fn cycle_compare(&mut self) {
+        self.compare = match self.compare {
+            None => Some(CompareMode::PrevMonth),
+            Some(CompareMode::PrevMonth) => Some(CompareMode::PrevYear),
+            Some(CompareMode::PrevYear) => None,
+        };
+    }
+
+    fn pan_compare(&mut self, months: i32) {
+        let step = Months::new(months.unsigned_abs());
+        let date = if months < 0 {
+            self.selected_date_m.checked_sub_months(step)
+        } else {
+            self.selected_date_m.checked_add_months(step)
+        };
+        if let Some(date) = date {
+            self.selected_date_m = date;
+        }
+    }
+
+    /// Chart inspection highlights the selected table row on the chart and
+    /// lets h/l walk through the entries instead of panning.
+    fn toggle_inspect(&mut self) {
+        self.chart_inspect = !self.chart_inspect;
+        if self.chart_inspect && self.table_state.selected().is_none() {
+            self.table_state.select_last();
+        }
+        if self.chart_inspect {
+            self.move_inspect_cursor(0);
+        }
+    }
+
+    /// Moves the inspected entry by `step` and pans the chart so it stays visible.
+    fn move_inspect_cursor(&mut self, step: isize) {
+        let len = self.data.get_mut().len();
+        let Some(idx) = self.table_state.selected() else {
+            return;
+        };
+        if len == 0 {
+            return;
+        }
+        let idx = idx.saturating_add_signed(step).min(len - 1);
+        self.table_state.select(Some(idx));
+        self.rm_confirm = false;
+        let date = NaiveDate::parse_from_str(&self.data.get_mut()[idx].date, "%d-%m-%Y").unwrap();
+        let (date_left, date_right) = self.chart_range();
+        if date >= date_left && date <= date_right {
+            return;
+        }
+        match self.current_tf {
+            ChartTimeFrame::Month | ChartTimeFrame::Quarter | ChartTimeFrame::Months => {
+                self.selected_date_m = date
+            }
+            ChartTimeFrame::Year | ChartTimeFrame::YearOverYear => self.selected_date_y = date,
+            ChartTimeFrame::WindowYear if date > date_right => self.selected_date_wy = date,
+            ChartTimeFrame::WindowYear => {
+                self.selected_date_wy = date.checked_add_months(Months::new(12)).unwrap()
+            }
+            ChartTimeFrame::Overview => {}
+        }
+    }
+
+    fn toggle_excluded(&mut self) {
+        let Some(idx) = self.table_state.selected() else {
+            return;
+        };
+        self.averages = None;
+        self.rates = None;
+        let data_ref = self.data.get_mut();
+        let Some(entry) = data_ref.get_mut(idx) else {
+            return;
+        };
+        entry.excluded = !entry.excluded;
+        let msg = if entry.excluded {
+            format!("{} marked as estimated and excluded from trend", entry.date)
+        } else {
+            format!("{} included in trend again", entry.date)
+        };
+        let date = entry.date.clone();
+        self.messages.push(msg, MessageType::Info);
+        self.journal(&[date]);
+    }
+
+    fn toggle_frame(&mut self) {
+        self.chart_inspect = false;
+        self.hint_page = 0;
+        self.current_frame = match self.current_frame {
+            FrameType::Chart => FrameType::Table,
+            FrameType::Table => FrameType::Chart,
+        }
+    }
+
+    fn cycle_next_tf(&mut self) {
+        self.current_tf = match self.current_tf {
+            ChartTimeFrame::Overview => ChartTimeFrame::Month,
+            ChartTimeFrame::YearOverYear => ChartTimeFrame::Overview,
+            ChartTimeFrame::WindowYear => ChartTimeFrame::YearOverYear,
+            ChartTimeFrame::Year => ChartTimeFrame::WindowYear,
+            ChartTimeFrame::Months => ChartTimeFrame::Year,
+            ChartTimeFrame::Quarter => ChartTimeFrame::Months,
+            ChartTimeFrame::Month => ChartTimeFrame::Quarter,
+        };
+    }
+
+    fn cycle_prev_tf(&mut self) {
+        self.current_tf = match self.current_tf {
+            ChartTimeFrame::Month => ChartTimeFrame::Overview,
+            ChartTimeFrame::Overview => ChartTimeFrame::YearOverYear,
+            ChartTimeFrame::YearOverYear => ChartTimeFrame::WindowYear,
+            ChartTimeFrame::WindowYear => ChartTimeFrame::Year,
+            ChartTimeFrame::Year => ChartTimeFrame::Months,
+            ChartTimeFrame::Months => ChartTimeFrame::Quarter,
+            ChartTimeFrame::Quarter => ChartTimeFrame::Month,
+        };
+    }
+
+    fn open_settings(&mut self) {
+        self.current_window = WindowType::Settings;
+        self.hint_page = 0;
+        self.settings_area = None;
+        if self.settings_state.selected().is_none() {
+            self.settings_state.select(Some(0));
+        }
+    }
+
+    /// Enter in the settings popup: a setting with fixed values moves on to
+    /// the next one, the others are typed into an editor first.
+    fn submit_setting(&mut self) {
+        let Some(&setting) = self.settings_state.selected().and_then(|idx| Setting::ALL.get(idx)) else {
+            return;
+        };
+        let value = match &self.settings_area {
+            Some(editor) => editor.lines()[0].clone(),
+            None if setting.choices().is_empty() => {
+                self.settings_area = Some(cell_editor(self.setting_value(setting)));
+                return;
+            }
+            None => {
+                let choices = setting.choices();
+                let current = self.setting_value(setting);
+                let idx = choices.iter().position(|x| *x == current).map_or(0, |idx| idx + 1);
+                choices[idx % choices.len()].to_string()
+            }
+        };
+        match self.change_setting(setting, &value) {
+            Ok(()) => {
+                self.settings_area = None;
+                let shown = self.setting_value(setting);
+                let shown = if shown.is_empty() { String::from("not set") } else { shown };
+                self.messages.push(format!("{}: {}", setting.name(), shown), MessageType::Info);
+            }
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    /// Inserts the first line of pasted text into the focused input field.
+    pub fn paste(&mut self, text: &str) {
+        if !self.accepts_paste() {
+            return;
+        }
+        let text = text.lines().next().unwrap_or_default().trim();
+        if let Some(editor) = self.inline_edit.as_mut() {
+            editor.insert_str(text);
+            return;
+        }
+        match self.current_window {
+            WindowType::Settings => {
+                if let Some(editor) = self.settings_area.as_mut() {
+                    editor.insert_str(text);
+                }
+            }
+            WindowType::InputPopup => {
+                self.text_area[self.selected_area].insert_str(text);
+                self.activate_text();
+            }
+            WindowType::ImportPathPopup => {
+                self.import_path_area.insert_str(text);
+            }
+            WindowType::FilterPopup => {
+                self.filter_area.insert_str(text);
+            }
+            WindowType::DiffPathPopup => {
+                self.diff_path_area.insert_str(text);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether keys are typed into a text box, which also takes pastes.
+    pub fn accepts_paste(&self) -> bool {
+        self.inline_edit.is_some()
+            || (self.current_window == WindowType::Settings && self.settings_area.is_some())
+            || matches!(
+                self.current_window,
+                WindowType::InputPopup
+                    | WindowType::ImportPathPopup
+                    | WindowType::FilterPopup
+                    | WindowType::DiffPathPopup
+            )
+    }
+
+    fn paste_clipboard(&mut self) {
+        match arboard::Clipboard::new().and_then(|mut x| x.get_text()) {
+            Ok(text) => self.paste(&text),
+            Err(e) => {
+                self.messages.push(format!("Cannot read clipboard: {}", e), MessageType::Error);
+            }
+        }
+    }
+
+    pub fn handle_events(&mut self) -> Result<(), WtError> {
+        let event = event::read()?;
+        if let Event::Paste(text) = &event {
+            if self.locked {
+                self.lock_area.insert_str(text);
+            } else {
+                self.paste(text);
+            }
+        }
+        if let Event::Key(key) = event {
+            if key.kind == event::KeyEventKind::Release {
+                return Ok(());
+            }
+            self.last_input = Instant::now();
+            if self.locked {
+                match (key.modifiers, key.code) {
+                    (KeyModifiers::CONTROL, KeyCode::Char('c')) => self.close = true,
+                    (_, KeyCode::Enter) => self.unlock(),
+                    _ => {
+                        self.lock_area.input(Event::Key(key));
+                    }
+                }
+                return Ok(());
+            }
+            if self.load_error.is_some() {
+                let recovered = match (key.modifiers, key.code) {
+                    (KeyModifiers::CONTROL, KeyCode::Char('c')) | (_, KeyCode::Esc | KeyCode::Char('q')) => {
+                        self.close = true;
+                        Ok(())
+                    }
+                    (_, KeyCode::Char('b')) => match self.newest_backup() {
+                        Some(backup) => self.recover(Some(&backup)),
+                        None => Ok(()),
+                    },
+                    (_, KeyCode::Char('n')) => self.recover(None),
+                    _ => Ok(()),
+                };
+                // The screen shows the latest problem, e.g. a broken backup.
+                if let Err(e) = recovered {
+                    warn!("recovery failed: {}", e);
+                    self.load_error = Some(e);
+                }
+                return Ok(());
+            }
+            self.handle_key(key)?;
+        }
+        Ok(())
+    }
+
+    fn show(&mut self, window: WindowType) {
+        self.current_window = window;
+        self.hint_page = 0;
+    }
+
+    /// Runs the action `key` is bound to in the current mode; keys a text box
+    /// doesn't bind are typed into it.
+    fn handle_key(&mut self, key: KeyEvent) -> Result<(), WtError> {
+        if key.code != KeyCode::Esc {
+            self.discard_confirm = false;
+        }
+        let mode = self.key_mode();
+        let typed = matches!(key.code, KeyCode::Char(_)) && !key.modifiers.contains(KeyModifiers::CONTROL);
+        let mut count = 1;
+        if matches!(mode, Mode::Chart | Mode::ChartCompare | Mode::ChartSimulator | Mode::ChartInspect | Mode::Table) {
+            if key.code == KeyCode::Esc && self.pending_count.is_some() {
+                self.pending_count = None;
+                return Ok(());
+            }
+            if let (true, KeyCode::Char(ch)) = (typed, key.code) {
+                // Vim-style count prefix, e.g. `5j` or `3d`.
+                if let Some(digit) = ch.to_digit(10).filter(|d| *d > 0 || self.pending_count.is_some()) {
+                    let count = self.pending_count.unwrap_or(0);
+                    let count = count.saturating_mul(10).saturating_add(digit as usize);
+                    self.pending_count = Some(count.min(MAX_COUNT));
+                    return Ok(());
+                }
+                count = self.pending_count.take().unwrap_or(1);
+            }
+        }
+        // Text boxes take every letter, including ones bound globally like '?'.
+        if mode.takes_text() && typed {
+            self.type_key(mode, key);
+            return Ok(());
+        }
+        match keymap::lookup(mode, &key) {
+            Some(action) => self.perform(action, key, count)?,
+            None if mode.takes_text() => self.type_key(mode, key),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn type_key(&mut self, mode: Mode, key: KeyEvent) {
+        let input: Input = Event::Key(key).into();
+        if mode == Mode::InlineEdit {
+            if let Some(editor) = self.inline_edit.as_mut() {
+                editor.input(input);
+            }
+            return;
+        }
+        if !matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace) {
+            return;
+        }
+        match mode {
+            Mode::InputPopup => {
+                let changed = self.text_area[self.selected_area].input(input);
+                if changed {
+                    self.activate_text();
+                }
+            }
+            Mode::FilterPopup => {
+                self.filter_area.input(input);
+            }
+            Mode::ImportPathPopup => {
+                self.import_path_area.input(input);
+            }
+            Mode::DiffPathPopup => {
+                self.diff_path_area.input(input);
+            }
+            Mode::SettingEditor => {
+                if let Some(editor) = self.settings_area.as_mut() {
+                    editor.input(input);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs `action`, repeated `count` times where a count makes sense.
+    fn perform(&mut self, action: Action, key: KeyEvent, count: usize) -> Result<(), WtError> {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match action {
+            Action::Quit => self.close = true,
+            Action::Save if self.staging.is_some() => {
+                self.show(WindowType::StagedChanges);
+                self.staged_state.select(Some(0));
+            }
+            Action::Save => match self.save() {
+                Ok(None) => {
+                    let msg = format!("Saved to {}", self.storage.location());
+                    self.messages.push(msg, MessageType::Info);
+                }
+                Ok(Some(merge)) => {
+                    let msg_type = if merge.conflicts > 0 {
+                        MessageType::Warning
+                    } else {
+                        MessageType::Info
+                    };
+                    self.messages.push(merge_message(&merge), msg_type);
+                }
+                Err(e) => self.show_error(e),
+            },
+            Action::Paste if self.accepts_paste() => self.paste_clipboard(),
+            Action::Paste => {}
+            Action::MoreHints => self.hint_page += 1,
+            Action::Close => self.show(WindowType::MainWindow),
+            Action::AskQuit => self.show(WindowType::ClosePopup),
+            Action::Add => {
+                self.show(WindowType::InputPopup);
+                self.text_mode = Some(TextMode::Append);
+                self.init_text_area();
+            }
+            Action::SubmitInput => self.submit_input(),
+            Action::CancelInput => self.cancel_input(),
+            Action::NextField if self.text_mode.is_some() => self.next_input_field(),
+            Action::NextField => {}
+            Action::SpinWeight(up) if self.selected_area == 1 => {
+                let step = if shift { 1.0 } else { 0.1 };
+                self.spin_weight(if up { step } else { -step });
+            }
+            Action::SpinWeight(_) => {}
+            Action::ApplyFilter => match self.apply_filter() {
+                Ok(()) => self.current_window = WindowType::MainWindow,
+                Err(e) => self.show_error(e),
+            },
+            Action::OpenImport => {
+                self.show(WindowType::ImportPathPopup);
+                self.import_path_area = TextArea::default();
+            }
+            Action::StartImport => self.start_import(),
+            Action::OpenDiff => {
+                self.show(WindowType::DiffPathPopup);
+                self.diff_path_area = TextArea::default();
+            }
+            Action::Compare => {
+                let path = self.diff_path_area.lines()[0].trim().to_string();
+                match self.open_diff(&path) {
+                    Ok(()) => self.current_window = WindowType::DiffView,
+                    Err(e) => self.show_error(e),
+                }
+            }
+            Action::CancelImport => {
+                self.import_job = None;
+                self.show(WindowType::MainWindow);
+            }
+            Action::ConfirmImport => self.show(WindowType::ImportProgress),
+            Action::SwitchFormat => self.cycle_import_format(),
+            Action::StopImport => {
+                self.messages.set_progress(None);
+                self.show(WindowType::ImportSummary);
+            }
+            Action::DismissWeek => self.dismiss_weekly_summary(),
+            Action::DismissAchievement => self.dismiss_achievement(),
+            Action::Select(down) => match self.current_window {
+                WindowType::MainWindow => (0..count).for_each(|_| self.select_row(down)),
+                WindowType::Settings if down => {
+                    let idx = self.settings_state.selected().map_or(0, |x| x + 1);
+                    self.settings_state.select(Some(idx.min(Setting::ALL.len() - 1)));
+                }
+                WindowType::Settings => self.settings_state.select_previous(),
+                WindowType::DiffView if down => self.diff_state.select_next(),
+                WindowType::DiffView => self.diff_state.select_previous(),
+                WindowType::Trash if down => self.trash_state.select_next(),
+                WindowType::Trash => self.trash_state.select_previous(),
+                WindowType::StagedChanges if down => self.staged_state.select_next(),
+                WindowType::StagedChanges => self.staged_state.select_previous(),
+                _ => {}
+            },
+            Action::Restore => {
+                if let Some(idx) = self.trash_state.selected() {
+                    if let Err(e) = self.restore_trashed(idx) {
+                        self.show_error(e);
+                    }
+                    let last = self.trash.len().checked_sub(1);
+                    self.trash_state.select(last.map(|x| x.min(idx)));
+                }
+            }
+            Action::EmptyTrash if self.rm_confirm => {
+                self.empty_trash();
+                self.rm_confirm = false;
+                self.messages.dismiss();
+            }
+            Action::EmptyTrash if !self.trash.is_empty() => {
+                self.rm_confirm = true;
+                let msg = format!("Press 'E' again to delete {} entries for good", self.trash.len());
+                self.messages.push(msg, MessageType::Warning);
+            }
+            Action::EmptyTrash => {}
+            Action::SubmitSetting => self.submit_setting(),
+            Action::CancelSetting => self.settings_area = None,
+            Action::HeatmapYear(false) => self.heatmap_year -= 1,
+            Action::HeatmapYear(true) => self.heatmap_year = (self.heatmap_year + 1).min(self.today().year()),
+            Action::HeatmapColors => self.heatmap_deltas = !self.heatmap_deltas,
+            Action::Commit => {
+                match self.commit_staged() {
+                    Ok(Some(merge)) => self.messages.push(merge_message(&merge), MessageType::Info),
+                    Ok(None) => {}
+                    Err(e) => self.show_error(e),
+                }
+                self.show(WindowType::MainWindow);
+            }
+            Action::Abort if self.rm_confirm => {
+                self.rm_confirm = false;
+                self.abort_staged();
+                self.show(WindowType::MainWindow);
+            }
+            Action::Abort if self.is_modified() => {
+                self.rm_confirm = true;
+                let msg = format!("Press 'a' again to discard {} staged changes", self.pending_changes().len());
+                self.messages.push(msg, MessageType::Warning);
+            }
+            Action::Abort => {}
+            Action::CloseCompare => self.compare = None,
+            Action::PanCompare(forward) => {
+                let months = count as i32;
+                self.pan_compare(if forward { months } else { -months });
+            }
+            Action::CycleCompare if !self.chart_inspect => self.cycle_compare(),
+            Action::ToggleSimulator if self.compare.is_none() => self.toggle_simulator(),
+            Action::SimulatedRate(up) => {
+                let step = if up { 0.1 } else { -0.1 };
+                self.simulated_rate =
+                    self.simulated_rate.map(|x| ((x + step * count as f64) * 10.0).round() / 10.0);
+            }
+            Action::CycleChart(true) => self.cycle_next_tf(),
+            Action::CycleChart(false) => self.cycle_prev_tf(),
+            Action::Pan(forward) => (0..count).for_each(|_| self.pan_chart(forward)),
+            Action::Page(forward) => (0..count).for_each(|_| self.page_chart(forward)),
+            Action::Today => self.chart_to_today(),
+            Action::Smoothing(wider) => self.step_smoothing(wider),
+            Action::ToggleInspect if self.compare.is_none() => self.toggle_inspect(),
+            Action::InspectStep(forward) => {
+                let step = count as isize;
+                self.move_inspect_cursor(if forward { step } else { -step });
+            }
+            Action::CycleCompare | Action::ToggleSimulator | Action::ToggleInspect => {}
+            Action::Edit => self.open_edit_popup(),
+            Action::Delete => self.delete_selected(count)?,
+            Action::Seasonal => {
+                self.config.chart.seasonal = !self.config.chart.seasonal;
+                let state = if self.config.chart.seasonal { "on" } else { "off" };
+                let msg = format!("Seasonal adjustment: {}", state);
+                self.messages.push(msg, MessageType::Info);
+            }
+            Action::RateView => {
+                self.rate_view = !self.rate_view;
+                let state = if self.rate_view { "on" } else { "off" };
+                let msg = format!("Rate of change: {}", state);
+                self.messages.push(msg, MessageType::Info);
+            }
+            Action::Marker => {
+                let style = self.config.chart.style_mut(&self.current_tf);
+                style.marker = style.marker.next();
+                let msg = format!("Marker: {}", style.marker.name());
+                self.messages.push(msg, MessageType::Info);
+            }
+            Action::GraphType => {
+                let style = self.config.chart.style_mut(&self.current_tf);
+                style.graph_type = style.graph_type.next();
+                let msg = format!("Graph type: {}", style.graph_type.name());
+                self.messages.push(msg, MessageType::Info);
+            }
+            Action::ToggleFrame => self.toggle_frame(),
+            Action::InlineEdit => self.start_inline_edit(),
+            Action::CommitInline => self.commit_inline_edit(),
+            Action::CancelInline => self.inline_edit = None,
+            Action::SpinInline(up) => {
+                let step = if shift { 1.0 } else { 0.1 };
+                self.spin_inline_weight(if up { step } else { -step });
+            }
+            Action::Details if self.table_state.selected().is_some() => self.show(WindowType::EntryDetails),
+            Action::Details => {}
+            Action::Exclude => self.toggle_excluded(),
+            Action::Filter => self.show(WindowType::FilterPopup),
+            Action::Trash => {
+                self.show(WindowType::Trash);
+                self.trash_state.select((!self.trash.is_empty()).then_some(self.trash.len() - 1));
+            }
+            Action::Heatmap => {
+                self.show(WindowType::Heatmap);
+                self.heatmap_year = self.today().year();
+            }
+            Action::Settings => self.open_settings(),
+            Action::Achievements => self.show(WindowType::Achievements),
+            Action::Correlation => self.show(WindowType::Correlation),
+            Action::Challenges => self.show(WindowType::Challenges),
+        }
+        Ok(())
+    }
+
+    /// Enter in the input popup: logs or edits the entry once every box is valid.
+    fn submit_input(&mut self) {
+        let (date, weight) = (
+            self.input_date().map(|d| d.format("%d-%m-%Y").to_string()),
+            self.parse_weight(&self.text_area[1].lines()[0]),
+        );
+        let date_is_valid = date.is_some();
+        let weight_is_valid = weight.is_ok();
+        if let Err(reason) = self.input_rhr() {
+            let msg = format!("Invalid resting heart rate: {}!", reason);
+            self.messages.push(msg, MessageType::Error);
+        } else if let Err(reason) = self.input_device() {
+            self.messages.push(format!("Invalid device: {}!", reason), MessageType::Error);
+        } else if date_is_valid && weight_is_valid {
+            let mut rhr = self.input_rhr().unwrap_or_default();
+            if !self.input_fields.contains(&InputField::Rhr) {
+                // Without a box for it, an edit keeps the logged value.
+                rhr = (self.text_mode == Some(TextMode::Edit))
+                    .then(|| self.table_state.selected().and_then(|i| self.data.borrow().get(i)?.rhr))
+                    .flatten();
+            }
+            let date = date.unwrap();
+            if self.modify_data((date.clone(), Some((weight.unwrap(), rhr)))) {
+                if self.input_fields.contains(&InputField::Device) {
+                    let device = self.input_device().unwrap_or_default();
+                    let data_ref = self.data.get_mut();
+                    if let Some(entry) = data_ref.iter_mut().find(|x| x.date == date) {
+                        entry.device = device;
+                    }
+                    self.journal(&[date]);
+                }
+                self.show(WindowType::MainWindow);
+                self.table_state.select_last();
+                self.text_mode = None;
+            }
+        } else if date_is_valid {
+            let reason = weight.err().unwrap_or_default();
+            self.messages.push(format!("Invalid weight: {}!", reason), MessageType::Error);
+        } else if weight_is_valid {
+            self.messages.push("Invalid date format!", MessageType::Error);
+        } else {
+            self.messages.push("Invalid weight & date format!", MessageType::Error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{App, TextMode, WindowType};
+
+    #[test]
+    fn inline_edit_commits_valid_weight_only() {
+        let mut app = App::default_test();
+        app.table_state.select(Some(0));
+        app.start_inline_edit();
+        app.inline_edit = Some(cell_editor(String::from("abc")));
+        app.commit_inline_edit();
+        assert!(app.inline_edit.is_some());
+
+        app.inline_edit = Some(cell_editor(String::from("81.2")));
+        app.commit_inline_edit();
+        assert!(app.inline_edit.is_none());
+        assert_eq!(app.data.borrow()[0].weight, 81.2);
+        assert!(app.text_mode.is_none());
+    }
+
+    #[test]
+    fn paste_validates_first_line() {
+        let mut app = App::default_test();
+        app.current_window = WindowType::InputPopup;
+        app.text_mode = Some(TextMode::Append);
+        app.init_text_area();
+        app.selected_area = 1;
+        app.paste(" 80.4\nignored");
+        assert_eq!(app.text_area[1].lines()[0], "80.4");
+        assert!(app.text_is_valid[1]);
+
+        app.current_window = WindowType::MainWindow;
+        app.paste("81");
+        assert_eq!(app.text_area[1].lines()[0], "80.4");
+    }
+
+    #[test]
+    fn cancelling_a_changed_edit_asks_first() {
+        let mut app = App::default_test();
+        app.table_state.select(Some(0));
+        app.open_edit_popup();
+        app.cancel_input();
+        assert!(app.current_window == WindowType::MainWindow);
+
+        app.open_edit_popup();
+        app.text_area[1] = TextArea::new(vec![String::from("abc")]);
+        app.cancel_input();
+        assert!(app.current_window == WindowType::MainWindow);
+
+        app.open_edit_popup();
+        app.text_area[1] = TextArea::new(vec![String::from("81.7")]);
+        app.cancel_input();
+        assert!(app.current_window == WindowType::InputPopup);
+        assert!(app.discard_confirm);
+        app.cancel_input();
+        assert!(app.current_window == WindowType::MainWindow);
+    }
+
+    #[test]
+    fn table_filter_hides_rows_from_navigation_and_deletion() {
+        let mut app = App::default_test();
+        app.filter_area = TextArea::new(vec![String::from(">=90")]);
+        app.apply_filter().unwrap();
+        // 90.1, 91.5 and 94.1 pass; the last of them is selected.
+        assert_eq!(app.table_state.selected(), Some(3));
+        app.select_row(false);
+        assert_eq!(app.table_state.selected(), Some(2));
+        app.select_row(false);
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.select_row(true);
+        assert_eq!(app.table_state.selected(), Some(2));
+        app.rm_confirm = true;
+        app.rm_count = 2;
+        app.delete_selected(1).unwrap();
+        let dates = app.data.borrow().iter().map(|x| x.date.clone()).collect::<Vec<_>>();
+        assert_eq!(dates, ["26-04-2024", "27-04-2024", "03-05-2024"]);
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.filter_area = TextArea::new(vec![String::from("90")]);
+        assert!(app.apply_filter().is_err());
+        app.filter_area = TextArea::default();
+        app.apply_filter().unwrap();
+        assert!(app.table_filter.is_none());
+    }
+
+    #[test]
+    fn smoothing_window_steps_through_the_windows() {
+        let mut app = App::default_test();
+        app.step_smoothing(true);
+        assert_eq!(app.smoothing_days, Some(3));
+        (0..4).for_each(|_| app.step_smoothing(true));
+        assert_eq!(app.smoothing_days, Some(30));
+        app.step_smoothing(false);
+        assert_eq!(app.smoothing_days, Some(14));
+        (0..3).for_each(|_| app.step_smoothing(false));
+        assert_eq!(app.smoothing_days, None);
+    }
+
+    #[test]
+    fn input_form_follows_the_enabled_metrics() {
+        let mut app = App::default_test();
+        app.current_window = WindowType::InputPopup;
+        app.text_mode = Some(TextMode::Append);
+        app.init_text_area();
+        assert_eq!(app.text_area.len(), 3);
+        app.next_input_field();
+        assert_eq!(app.input_fields[app.selected_area], InputField::Rhr);
+        app.next_input_field();
+        assert_eq!(app.input_fields[app.selected_area], InputField::Date);
+        // The optional field may stay blank, but not hold garbage.
+        assert!(app.field_is_valid(2));
+        app.text_area[2] = TextArea::new(vec![String::from("fast")]);
+        assert!(!app.field_is_valid(2));
+
+        app.config.display.input_metrics.clear();
+        app.input_fields = crate::config::parse_input_fields(&app.config.display).unwrap();
+        app.data.get_mut()[0].rhr = Some(55.0);
+        app.table_state.select(Some(0));
+        app.open_edit_popup();
+        assert_eq!(app.text_area.len(), 2);
+        app.next_input_field();
+        assert_eq!(app.selected_area, 1);
+        assert_eq!(app.input_rhr(), Ok(None));
+        app.config.display.input_metrics = vec![String::from("spo2")];
+        assert!(crate::config::parse_input_fields(&app.config.display).is_err());
+    }
+
+    #[test]
+    fn lock_screen_needs_the_passphrase() {
+        let mut app = App::default_test();
+        app.lock();
+        assert!(!app.locked);
+        app.config.lock.passphrase = Some(crate::config::hash_passphrase("open sesame", "f00d"));
+        assert!(crate::config::validate_lock(&app.config.lock).is_ok());
+        app.config.lock.idle_minutes = 5;
+        app.lock_if_idle();
+        assert!(!app.locked);
+        app.last_input -= Duration::from_secs(5 * 60);
+        app.lock_if_idle();
+        assert!(app.locked);
+
+        app.lock_area.insert_str("open sesame ");
+        app.unlock();
+        assert!(app.locked && app.lock_failed);
+        assert!(app.lock_area.is_empty());
+        app.lock_area.insert_str("open sesame");
+        app.unlock();
+        assert!(!app.locked && !app.lock_failed);
+
+        app.config.lock.passphrase = Some(String::from("open sesame"));
+        assert!(crate::config::validate_lock(&app.config.lock).is_err());
+    }
+
+    #[test]
+    fn chart_pages_by_window_width_and_returns_to_today() {
+        let mut app = App::default_test();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        app.current_tf = ChartTimeFrame::WindowYear;
+        app.selected_date_wy = date(2024, 5, 5);
+        app.page_chart(false);
+        app.page_chart(false);
+        assert_eq!(app.selected_date_wy, date(2022, 5, 5));
+        app.current_tf = ChartTimeFrame::Months;
+        app.selected_date_m = date(2024, 5, 5);
+        app.page_chart(true);
+        assert_eq!(app.selected_date_m, date(2024, 7, 5));
+        app.current_tf = ChartTimeFrame::WindowYear;
+        app.chart_to_today();
+        assert_eq!(app.selected_date_wy, app.today());
+        assert_eq!(app.selected_date_m, date(2024, 7, 5));
+    }
+}
@@ -0,0 +1,146 @@
+//! Secondary copies of the data written on every save, for anything that
+//! watches a folder: JSON, an iCalendar feed and a Markdown report.
+
+use chrono::NaiveDate;
+
+use crate::config::ExportFormat;
+use crate::data::{Entry, RHR, Unit, WEIGHT, weekly_change};
+
+fn parse_date(entry: &Entry) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y").ok()
+}
+
+/// `text` as a JSON string literal.
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            ch if ch.is_control() => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The entries as a JSON object with ISO dates, e.g.
+/// `{"unit": "kg", "entries": [{"date": "2024-05-03", "weight": 87.9, ...}]}`.
+pub fn to_json(data: &[Entry], unit: Unit) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("null"));
+    let entries = data
+        .iter()
+        .filter_map(|entry| {
+            let date = parse_date(entry)?;
+            Some(format!(
+                "    {{\"date\": \"{}\", \"weight\": {}, \"rhr\": {}, \"excluded\": {}, \"source\": {}, \"device\": {}}}",
+                date.format("%Y-%m-%d"),
+                WEIGHT.format(entry.weight),
+                optional(entry.rhr.map(|x| RHR.format(x))),
+                entry.excluded,
+                optional(entry.source.as_deref().map(json_string)),
+                optional(entry.device.as_deref().map(json_string)),
+            ))
+        })
+        .collect::<Vec<_>>();
+    format!("{{\n  \"unit\": \"{}\",\n  \"entries\": [\n{}\n  ]\n}}\n", unit.name(), entries.join(",\n"))
+}
+
+/// One all-day event per entry, titled with the weight, for calendar apps.
+pub fn to_ics(data: &[Entry], unit: Unit) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//weight-tracker//EN"),
+    ];
+    for entry in data {
+        let Some(date) = parse_date(entry) else {
+            continue;
+        };
+        let stamp = entry.modified.map_or_else(
+            || date.format("%Y%m%dT000000Z").to_string(),
+            |x| x.format("%Y%m%dT%H%M%SZ").to_string(),
+        );
+        let uid = entry.id.clone().unwrap_or_else(|| date.format("%Y%m%d").to_string());
+        let excluded = if entry.excluded { " (excluded)" } else { "" };
+        lines.extend([
+            String::from("BEGIN:VEVENT"),
+            format!("UID:{}@weight-tracker", uid),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")),
+            format!("SUMMARY:Weight {} {}{}", WEIGHT.format(entry.weight), unit.name(), excluded),
+            String::from("END:VEVENT"),
+        ]);
+    }
+    lines.push(String::from("END:VCALENDAR"));
+    // Lines of iCalendar files end in CRLF.
+    lines.join("\r\n") + "\r\n"
+}
+
+/// Summary and the entries newest first, with the change to the previous one.
+pub fn to_markdown(data: &[Entry], unit: Unit, today: NaiveDate) -> String {
+    let signed = |x: f64| format!("{:+.1}", x);
+    let mut text = format!("# Weight report\n\nUpdated {}.\n\n", today.format("%d-%m-%Y"));
+    let included = data.iter().filter(|x| !x.excluded).collect::<Vec<_>>();
+    if let (Some(first), Some(last)) = (included.first(), included.last()) {
+        text += &format!("- Latest: {} {} on {}\n", WEIGHT.format(last.weight), unit.name(), last.date);
+        if let Some(change) = weekly_change(data) {
+            text += &format!("- Last 7 days: {} {}\n", signed(change), unit.name());
+        }
+        text += &format!("- Since {}: {} {}\n", first.date, signed(last.weight - first.weight), unit.name());
+    }
+    text += &format!("- Entries: {}\n\n", data.len());
+    text += "| Date | Weight | Change |\n|---|---:|---:|\n";
+    for (i, entry) in data.iter().enumerate().rev() {
+        let change = match i.checked_sub(1).map(|x| &data[x]) {
+            Some(previous) => signed(entry.weight - previous.weight),
+            None => String::from("-"),
+        };
+        let excluded = if entry.excluded { " (excluded)" } else { "" };
+        text += &format!("| {} | {}{} | {} |\n", entry.date, WEIGHT.format(entry.weight), excluded, change);
+    }
+    text
+}
+
+pub fn render(format: ExportFormat, data: &[Entry], unit: Unit, today: NaiveDate) -> String {
+    match format {
+        ExportFormat::Json => to_json(data, unit),
+        ExportFormat::Ics => to_ics(data, unit),
+        ExportFormat::Markdown => to_markdown(data, unit, today),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Entry> {
+        let mut data = [("01-05-2024", 88.4), ("02-05-2024", 88.9), ("03-05-2024", 87.9)]
+            .map(|(d, w)| Entry::new(d.to_string(), w))
+            .to_vec();
+        data[1].excluded = true;
+        data[2].source = Some(String::from("say \"hi\""));
+        data
+    }
+
+    #[test]
+    fn exports_in_every_format() {
+        let json = to_json(&entries(), Unit::Kg);
+        assert!(json.starts_with("{\n  \"unit\": \"kg\""));
+        let row = "{\"date\": \"2024-05-02\", \"weight\": 88.9, \"rhr\": null, \"excluded\": true";
+        assert!(json.contains(row));
+        assert!(json.contains("\"source\": \"say \\\"hi\\\"\""));
+
+        let ics = to_ics(&entries(), Unit::Lb);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n") && ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 3);
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240503\r\nSUMMARY:Weight 87.9 lb\r\n"));
+
+        let today = NaiveDate::from_ymd_opt(2024, 5, 5).unwrap();
+        let markdown = to_markdown(&entries(), Unit::Kg, today);
+        assert!(markdown.contains("- Latest: 87.9 kg on 03-05-2024\n- Since 01-05-2024: -0.5 kg\n"));
+        assert!(markdown.contains("| 03-05-2024 | 87.9 | -1.0 |\n| 02-05-2024 | 88.9 (excluded) | +0.5 |"));
+    }
+}
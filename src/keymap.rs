@@ -0,0 +1,419 @@
+//! The key bindings of every window, in one table. Dispatch looks keys up in
+//! it and the hint bar lists it, so the two cannot drift apart.
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{App, FrameType, WindowType};
+
+/// A key as bound in the table. Letters are case sensitive, so `Char('A')`
+/// is Shift+a.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Esc,
+    Enter,
+    Tab,
+    Up,
+    Down,
+    F1,
+}
+
+impl Key {
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match (self, key.code) {
+            (Key::Char(c), KeyCode::Char(x)) => *c == x && !ctrl,
+            (Key::Ctrl(c), KeyCode::Char(x)) => *c == x && ctrl,
+            (Key::Esc, KeyCode::Esc)
+            | (Key::Enter, KeyCode::Enter)
+            | (Key::Tab, KeyCode::Tab)
+            | (Key::Up, KeyCode::Up)
+            | (Key::Down, KeyCode::Down)
+            | (Key::F1, KeyCode::F(1)) => true,
+            _ => false,
+        }
+    }
+
+    /// Name in the hint bar, e.g. "C-s".
+    pub fn label(&self) -> String {
+        match self {
+            Key::Char(c) => c.to_string(),
+            Key::Ctrl(c) => format!("C-{}", c),
+            Key::Esc => String::from("Esc"),
+            Key::Enter => String::from("Enter"),
+            Key::Tab => String::from("Tab"),
+            Key::Up => String::from("Up"),
+            Key::Down => String::from("Down"),
+            Key::F1 => String::from("F1"),
+        }
+    }
+}
+
+/// Where a binding applies: a window, or a frame and state of the main window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Keys that work everywhere unless a mode binds them differently.
+    Global,
+    /// Keys shared by both frames of the main window.
+    Main,
+    Chart,
+    ChartCompare,
+    ChartSimulator,
+    ChartInspect,
+    Table,
+    InlineEdit,
+    ClosePopup,
+    InputPopup,
+    FilterPopup,
+    ImportPathPopup,
+    DiffPathPopup,
+    DiffView,
+    ImportPreview,
+    ImportProgress,
+    ImportSummary,
+    WeeklySummary,
+    AchievementUnlocked,
+    Achievements,
+    Correlation,
+    Challenges,
+    EntryDetails,
+    Trash,
+    Settings,
+    SettingEditor,
+    Heatmap,
+    StagedChanges,
+}
+
+impl Mode {
+    /// Mode whose bindings apply when this one doesn't bind a key.
+    pub fn parent(&self) -> Option<Mode> {
+        match self {
+            Mode::Global => None,
+            Mode::Chart | Mode::Table => Some(Mode::Main),
+            Mode::ChartCompare | Mode::ChartSimulator | Mode::ChartInspect => Some(Mode::Chart),
+            _ => Some(Mode::Global),
+        }
+    }
+
+    /// Whether unbound keys are typed into a text box.
+    pub fn takes_text(&self) -> bool {
+        matches!(
+            self,
+            Mode::InlineEdit
+                | Mode::InputPopup
+                | Mode::FilterPopup
+                | Mode::ImportPathPopup
+                | Mode::DiffPathPopup
+                | Mode::SettingEditor
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Save,
+    Paste,
+    MoreHints,
+    /// Back to the main window.
+    Close,
+    AskQuit,
+    Add,
+    SubmitInput,
+    CancelInput,
+    NextField,
+    /// Steps the weight up (`true`) or down, by 1.0 with Shift.
+    SpinWeight(bool),
+    ApplyFilter,
+    OpenImport,
+    StartImport,
+    OpenDiff,
+    Compare,
+    CancelImport,
+    ConfirmImport,
+    SwitchFormat,
+    StopImport,
+    DismissWeek,
+    DismissAchievement,
+    /// Moves the selection of the window's list down (`true`) or up.
+    Select(bool),
+    Restore,
+    EmptyTrash,
+    SubmitSetting,
+    CancelSetting,
+    HeatmapYear(bool),
+    HeatmapColors,
+    Commit,
+    Abort,
+    CloseCompare,
+    PanCompare(bool),
+    CycleCompare,
+    ToggleSimulator,
+    SimulatedRate(bool),
+    CycleChart(bool),
+    Pan(bool),
+    Page(bool),
+    Today,
+    Smoothing(bool),
+    ToggleInspect,
+    InspectStep(bool),
+    Edit,
+    Delete,
+    Seasonal,
+    RateView,
+    Marker,
+    GraphType,
+    ToggleFrame,
+    InlineEdit,
+    CommitInline,
+    CancelInline,
+    SpinInline(bool),
+    Details,
+    Exclude,
+    Filter,
+    Trash,
+    Heatmap,
+    Settings,
+    Achievements,
+    Correlation,
+    Challenges,
+}
+
+/// One key of one mode. An empty hint keeps the binding out of the hint bar.
+pub struct Binding {
+    pub mode: Mode,
+    pub key: Key,
+    pub action: Action,
+    pub hint: &'static str,
+}
+
+const fn bind(mode: Mode, key: Key, action: Action, hint: &'static str) -> Binding {
+    Binding { mode, key, action, hint }
+}
+
+/// All bindings, each mode's in hint bar order: most used first, and keys
+/// next to each other with the same hint are shown together, e.g. "j/k".
+pub const BINDINGS: &[Binding] = &[
+    bind(Mode::Global, Key::Ctrl('c'), Action::Quit, ""),
+    bind(Mode::Global, Key::Ctrl('s'), Action::Save, ""),
+    bind(Mode::Global, Key::Ctrl('v'), Action::Paste, ""),
+    bind(Mode::Global, Key::F1, Action::MoreHints, ""),
+    bind(Mode::Global, Key::Char('?'), Action::MoreHints, ""),
+    bind(Mode::ClosePopup, Key::Esc, Action::Close, "back to main window"),
+    bind(Mode::ClosePopup, Key::Char('n'), Action::Close, "back to main window"),
+    bind(Mode::ClosePopup, Key::Enter, Action::Quit, "quit app"),
+    bind(Mode::ClosePopup, Key::Char('y'), Action::Quit, "quit app"),
+    bind(Mode::ClosePopup, Key::Char('l'), Action::Add, "log today"),
+    bind(Mode::InputPopup, Key::Enter, Action::SubmitInput, "submit form"),
+    bind(Mode::InputPopup, Key::Esc, Action::CancelInput, "go to main window"),
+    bind(Mode::InputPopup, Key::Tab, Action::NextField, "switch input box"),
+    bind(Mode::InputPopup, Key::Up, Action::SpinWeight(true), "weight +/-0.1 (Shift: 1.0)"),
+    bind(Mode::InputPopup, Key::Down, Action::SpinWeight(false), "weight +/-0.1 (Shift: 1.0)"),
+    bind(Mode::InputPopup, Key::Ctrl('v'), Action::Paste, "paste"),
+    bind(Mode::FilterPopup, Key::Enter, Action::ApplyFilter, "apply filter, empty to clear"),
+    bind(Mode::FilterPopup, Key::Esc, Action::Close, "go to main window"),
+    bind(Mode::ImportPathPopup, Key::Esc, Action::Close, "go to main window"),
+    bind(Mode::ImportPathPopup, Key::Enter, Action::StartImport, "start import"),
+    bind(Mode::DiffPathPopup, Key::Esc, Action::Close, "go to main window"),
+    bind(Mode::DiffPathPopup, Key::Enter, Action::Compare, "compare"),
+    bind(Mode::DiffView, Key::Esc, Action::Close, "close comparison"),
+    bind(Mode::DiffView, Key::Enter, Action::Close, "close comparison"),
+    bind(Mode::DiffView, Key::Char('j'), Action::Select(true), "select change"),
+    bind(Mode::DiffView, Key::Char('k'), Action::Select(false), "select change"),
+    bind(Mode::ImportPreview, Key::Esc, Action::CancelImport, "cancel import"),
+    bind(Mode::ImportPreview, Key::Enter, Action::ConfirmImport, "import"),
+    bind(Mode::ImportPreview, Key::Char('f'), Action::SwitchFormat, "switch date format"),
+    bind(Mode::ImportProgress, Key::Esc, Action::StopImport, "stop importing"),
+    bind(Mode::ImportSummary, Key::Esc, Action::Close, "close summary"),
+    bind(Mode::ImportSummary, Key::Enter, Action::Close, "close summary"),
+    bind(Mode::WeeklySummary, Key::Esc, Action::Close, "close summary"),
+    bind(Mode::WeeklySummary, Key::Enter, Action::Close, "close summary"),
+    bind(Mode::WeeklySummary, Key::Char('d'), Action::DismissWeek, "don't show again this week"),
+    bind(Mode::AchievementUnlocked, Key::Esc, Action::DismissAchievement, "continue"),
+    bind(Mode::AchievementUnlocked, Key::Enter, Action::DismissAchievement, "continue"),
+    bind(Mode::Achievements, Key::Esc, Action::Close, "close achievements"),
+    bind(Mode::Achievements, Key::Enter, Action::Close, "close achievements"),
+    bind(Mode::Correlation, Key::Esc, Action::Close, "close correlation view"),
+    bind(Mode::Correlation, Key::Enter, Action::Close, "close correlation view"),
+    bind(Mode::Challenges, Key::Esc, Action::Close, "close challenges"),
+    bind(Mode::Challenges, Key::Enter, Action::Close, "close challenges"),
+    bind(Mode::EntryDetails, Key::Esc, Action::Close, "close details"),
+    bind(Mode::EntryDetails, Key::Enter, Action::Close, "close details"),
+    bind(Mode::Trash, Key::Esc, Action::Close, "close trash"),
+    bind(Mode::Trash, Key::Enter, Action::Close, "close trash"),
+    bind(Mode::Trash, Key::Char('j'), Action::Select(true), "select entry"),
+    bind(Mode::Trash, Key::Char('k'), Action::Select(false), "select entry"),
+    bind(Mode::Trash, Key::Char('r'), Action::Restore, "restore entry"),
+    bind(Mode::Trash, Key::Char('E'), Action::EmptyTrash, "empty trash"),
+    bind(Mode::SettingEditor, Key::Enter, Action::SubmitSetting, "save setting, empty to unset"),
+    bind(Mode::SettingEditor, Key::Esc, Action::CancelSetting, "cancel"),
+    bind(Mode::SettingEditor, Key::Ctrl('v'), Action::Paste, "paste"),
+    bind(Mode::Settings, Key::Esc, Action::Close, "close settings"),
+    bind(Mode::Settings, Key::Char('q'), Action::Close, "close settings"),
+    bind(Mode::Settings, Key::Char('j'), Action::Select(true), "select setting"),
+    bind(Mode::Settings, Key::Char('k'), Action::Select(false), "select setting"),
+    bind(Mode::Settings, Key::Enter, Action::SubmitSetting, "change setting"),
+    bind(Mode::Heatmap, Key::Esc, Action::Close, "close heat map"),
+    bind(Mode::Heatmap, Key::Enter, Action::Close, "close heat map"),
+    bind(Mode::Heatmap, Key::Char('h'), Action::HeatmapYear(false), "(-/+) year"),
+    bind(Mode::Heatmap, Key::Char('l'), Action::HeatmapYear(true), "(-/+) year"),
+    bind(Mode::Heatmap, Key::Char('c'), Action::HeatmapColors, "color by weigh-in/change"),
+    bind(Mode::StagedChanges, Key::Esc, Action::Close, "keep editing"),
+    bind(Mode::StagedChanges, Key::Enter, Action::Close, "keep editing"),
+    bind(Mode::StagedChanges, Key::Char('j'), Action::Select(true), "select change"),
+    bind(Mode::StagedChanges, Key::Char('k'), Action::Select(false), "select change"),
+    bind(Mode::StagedChanges, Key::Char('c'), Action::Commit, "commit changes"),
+    bind(Mode::StagedChanges, Key::Char('a'), Action::Abort, "abort changes"),
+    bind(Mode::ChartCompare, Key::Esc, Action::CloseCompare, "close comparison"),
+    bind(Mode::ChartCompare, Key::Char('h'), Action::PanCompare(false), "(-/+) month"),
+    bind(Mode::ChartCompare, Key::Char('l'), Action::PanCompare(true), "(-/+) month"),
+    bind(Mode::ChartCompare, Key::Char('c'), Action::CycleCompare, "cycle previous month/year"),
+    bind(Mode::ChartSimulator, Key::Esc, Action::ToggleSimulator, "close simulator"),
+    bind(Mode::ChartSimulator, Key::Char('w'), Action::ToggleSimulator, "close simulator"),
+    bind(Mode::ChartSimulator, Key::Char('+'), Action::SimulatedRate(true), "change weekly rate by 0.1"),
+    bind(Mode::ChartSimulator, Key::Char('-'), Action::SimulatedRate(false), "change weekly rate by 0.1"),
+    bind(Mode::ChartSimulator, Key::Char('='), Action::SimulatedRate(true), ""),
+    bind(Mode::ChartSimulator, Key::Char('j'), Action::CycleChart(true), "cycle chart"),
+    bind(Mode::ChartSimulator, Key::Char('k'), Action::CycleChart(false), "cycle chart"),
+    bind(Mode::ChartSimulator, Key::Char('h'), Action::Pan(false), "(-/+)x-axis"),
+    bind(Mode::ChartSimulator, Key::Char('l'), Action::Pan(true), "(-/+)x-axis"),
+    bind(Mode::ChartInspect, Key::Esc, Action::ToggleInspect, "stop inspecting"),
+    bind(Mode::ChartInspect, Key::Char('i'), Action::ToggleInspect, "stop inspecting"),
+    bind(Mode::ChartInspect, Key::Char('h'), Action::InspectStep(false), "prev/next entry"),
+    bind(Mode::ChartInspect, Key::Char('l'), Action::InspectStep(true), "prev/next entry"),
+    bind(Mode::ChartInspect, Key::Char('e'), Action::Edit, "edit entry"),
+    bind(Mode::ChartInspect, Key::Char('d'), Action::Delete, "delete entry"),
+    bind(Mode::Chart, Key::Esc, Action::AskQuit, "quit app"),
+    bind(Mode::Chart, Key::Char('q'), Action::AskQuit, "quit app"),
+    bind(Mode::Chart, Key::Ctrl('s'), Action::Save, "save"),
+    bind(Mode::Chart, Key::Tab, Action::ToggleFrame, "table"),
+    bind(Mode::Chart, Key::Char('j'), Action::CycleChart(true), "cycle chart"),
+    bind(Mode::Chart, Key::Char('k'), Action::CycleChart(false), "cycle chart"),
+    bind(Mode::Chart, Key::Char('h'), Action::Pan(false), "(-/+)x-axis"),
+    bind(Mode::Chart, Key::Char('l'), Action::Pan(true), "(-/+)x-axis"),
+    bind(Mode::Chart, Key::Char('H'), Action::Page(false), "(-/+)page"),
+    bind(Mode::Chart, Key::Char('L'), Action::Page(true), "(-/+)page"),
+    bind(Mode::Chart, Key::Char('t'), Action::Today, "today"),
+    bind(Mode::Chart, Key::Char('['), Action::Smoothing(false), "smoothing"),
+    bind(Mode::Chart, Key::Char(']'), Action::Smoothing(true), "smoothing"),
+    bind(Mode::Chart, Key::Char('i'), Action::ToggleInspect, "inspect points"),
+    bind(Mode::Chart, Key::Char('c'), Action::CycleCompare, "compare months"),
+    bind(Mode::Chart, Key::Char('w'), Action::ToggleSimulator, "what-if"),
+    bind(Mode::Chart, Key::Char('s'), Action::Seasonal, "seasonal adjustment"),
+    bind(Mode::Chart, Key::Char('r'), Action::RateView, "rate of change"),
+    bind(Mode::Chart, Key::Char('m'), Action::Marker, "cycle marker/graph type"),
+    bind(Mode::Chart, Key::Char('g'), Action::GraphType, "cycle marker/graph type"),
+    bind(Mode::InlineEdit, Key::Enter, Action::CommitInline, "save weight"),
+    bind(Mode::InlineEdit, Key::Esc, Action::CancelInline, "cancel"),
+    bind(Mode::InlineEdit, Key::Up, Action::SpinInline(true), "(+/-) 0.1"),
+    bind(Mode::InlineEdit, Key::Down, Action::SpinInline(false), "(+/-) 0.1"),
+    bind(Mode::Table, Key::Esc, Action::AskQuit, "quit app"),
+    bind(Mode::Table, Key::Char('q'), Action::AskQuit, "quit app"),
+    bind(Mode::Table, Key::Ctrl('s'), Action::Save, "save"),
+    bind(Mode::Table, Key::Char('a'), Action::Add, "add entry"),
+    bind(Mode::Table, Key::Char('e'), Action::Edit, "edit selected row"),
+    bind(Mode::Table, Key::Char('d'), Action::Delete, "delete 1 row"),
+    bind(Mode::Table, Key::Char('j'), Action::Select(true), "(down/up) 1 row"),
+    bind(Mode::Table, Key::Char('k'), Action::Select(false), "(down/up) 1 row"),
+    bind(Mode::Table, Key::Tab, Action::ToggleFrame, "chart"),
+    bind(Mode::Table, Key::Char('i'), Action::InlineEdit, "quick edit weight"),
+    bind(Mode::Table, Key::Enter, Action::Details, "entry details"),
+    bind(Mode::Table, Key::Char('x'), Action::Exclude, "(un)exclude row from trend"),
+    bind(Mode::Table, Key::Char('/'), Action::Filter, "filter rows"),
+    bind(Mode::Table, Key::Char('I'), Action::OpenImport, "import CSV"),
+    bind(Mode::Table, Key::Char('D'), Action::OpenDiff, "compare with file"),
+    bind(Mode::Main, Key::Char('T'), Action::Trash, "trash"),
+    bind(Mode::Main, Key::Char('Y'), Action::Heatmap, "year heat map"),
+    bind(Mode::Main, Key::Char('O'), Action::Settings, "settings"),
+    bind(Mode::Main, Key::Char('A'), Action::Achievements, "achievements"),
+    bind(Mode::Main, Key::Char('R'), Action::Correlation, "heart rate correlation"),
+    bind(Mode::Main, Key::Char('C'), Action::Challenges, "challenges"),
+];
+
+/// Action of `key` in `mode`, falling back to the modes it inherits from.
+pub fn lookup(mode: Mode, key: &KeyEvent) -> Option<Action> {
+    let mut mode = Some(mode);
+    while let Some(current) = mode {
+        let found = BINDINGS.iter().find(|x| x.mode == current && x.key.matches(key));
+        if let Some(binding) = found {
+            return Some(binding.action);
+        }
+        mode = current.parent();
+    }
+    None
+}
+
+/// Bindings listed in the hint bar of `mode`: its own, then the ones the
+/// frames of the main window share.
+pub fn hinted(mode: Mode) -> impl Iterator<Item = &'static Binding> {
+    let shared = (mode.parent() == Some(Mode::Main)).then_some(Mode::Main);
+    let own = BINDINGS.iter().filter(move |x| x.mode == mode);
+    let shared = BINDINGS.iter().filter(move |x| Some(x.mode) == shared);
+    own.chain(shared).filter(|x| !x.hint.is_empty())
+}
+
+impl App<'_> {
+    /// Mode the keys are looked up in, from the window, frame and state.
+    pub fn key_mode(&self) -> Mode {
+        match self.current_window {
+            WindowType::ClosePopup => Mode::ClosePopup,
+            WindowType::InputPopup => Mode::InputPopup,
+            WindowType::FilterPopup => Mode::FilterPopup,
+            WindowType::ImportPathPopup => Mode::ImportPathPopup,
+            WindowType::DiffPathPopup => Mode::DiffPathPopup,
+            WindowType::DiffView => Mode::DiffView,
+            WindowType::ImportPreview => Mode::ImportPreview,
+            WindowType::ImportProgress => Mode::ImportProgress,
+            WindowType::ImportSummary => Mode::ImportSummary,
+            WindowType::WeeklySummary => Mode::WeeklySummary,
+            WindowType::AchievementUnlocked => Mode::AchievementUnlocked,
+            WindowType::Achievements => Mode::Achievements,
+            WindowType::Correlation => Mode::Correlation,
+            WindowType::Challenges => Mode::Challenges,
+            WindowType::EntryDetails => Mode::EntryDetails,
+            WindowType::Trash => Mode::Trash,
+            WindowType::Settings if self.settings_area.is_some() => Mode::SettingEditor,
+            WindowType::Settings => Mode::Settings,
+            WindowType::Heatmap => Mode::Heatmap,
+            WindowType::StagedChanges => Mode::StagedChanges,
+            WindowType::MainWindow => match self.current_frame {
+                FrameType::Chart if self.compare.is_some() => Mode::ChartCompare,
+                FrameType::Chart if self.simulated_rate.is_some() => Mode::ChartSimulator,
+                FrameType::Chart if self.chart_inspect => Mode::ChartInspect,
+                FrameType::Chart => Mode::Chart,
+                FrameType::Table if self.inline_edit.is_some() => Mode::InlineEdit,
+                FrameType::Table => Mode::Table,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_bound_once_per_mode() {
+        for (i, x) in BINDINGS.iter().enumerate() {
+            assert!(
+                !BINDINGS[..i].iter().any(|y| y.mode == x.mode && y.key == x.key),
+                "{:?} is bound twice in {:?}",
+                x.key,
+                x.mode
+            );
+        }
+    }
+
+    #[test]
+    fn modes_inherit_unbound_keys() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(lookup(Mode::ChartCompare, &key('h')), Some(Action::PanCompare(false)));
+        assert_eq!(lookup(Mode::ChartCompare, &key('j')), Some(Action::CycleChart(true)));
+        assert_eq!(lookup(Mode::Chart, &key('T')), Some(Action::Trash));
+        assert_eq!(lookup(Mode::Trash, &key('?')), Some(Action::MoreHints));
+        assert_eq!(lookup(Mode::Trash, &key('T')), None);
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(lookup(Mode::Heatmap, &ctrl_c), Some(Action::Quit));
+    }
+}
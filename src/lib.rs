@@ -0,0 +1,56 @@
+//! Data handling, storage and the terminal UI of weight-tracker. The binary
+//! only adds the command line on top.
+
+pub mod app;
+pub mod config;
+pub mod data;
+mod events;
+pub mod export;
+mod keymap;
+pub mod remote;
+pub mod share;
+pub mod storage;
+pub mod tracker;
+mod ui;
+
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum WtError {
+    /// Malformed content in a data file, with the 1-based line number.
+    Parse { line: usize, reason: String },
+    Storage(String),
+    Validation(String),
+    Config(String),
+}
+
+impl fmt::Display for WtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WtError::Parse { line, reason } => write!(f, "Line {}: {}", line, reason),
+            WtError::Storage(e) => write!(f, "Storage error: {}", e),
+            WtError::Validation(e) => write!(f, "{}", e),
+            WtError::Config(e) => write!(f, "Config error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WtError {}
+
+impl From<io::Error> for WtError {
+    fn from(e: io::Error) -> Self {
+        WtError::Storage(e.to_string())
+    }
+}
+
+impl WtError {
+    /// Process exit code, following the sysexits.h conventions.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            WtError::Parse { .. } => 65,
+            WtError::Storage(_) => 74,
+            WtError::Validation(_) => 65,
+            WtError::Config(_) => 78,
+        }
+    }
+}
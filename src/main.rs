@@ -1,1149 +1,880 @@
-use chrono::{Datelike, Days, Local, Months, NaiveDate};
-use directories::BaseDirs;
-use ratatui::{
-    DefaultTerminal, Frame,
-    crossterm::event::{self, Event, KeyCode, KeyModifiers},
-    layout::{Alignment, Constraint, Direction, Flex, Layout, Rect},
-    style::{Color, Modifier, Style, Stylize},
-    symbols::Marker,
-    text::{Line, Span, Text},
-    widgets::{
-        Axis, Block, Borders, Chart, Clear, Dataset, GraphType, Paragraph, Row, Table, TableState,
-    },
-};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use ratatui::crossterm::{event::{DisableBracketedPaste, EnableBracketedPaste}, execute};
 use std::{
-    cell::RefCell, cmp::Ordering, collections::VecDeque, fs::{self, File, OpenOptions}, io::{self, Error, Read, Write}, time::{Duration, Instant}
+    fs::{self, File, OpenOptions},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
 };
-use tui_textarea::{CursorMove, Input, TextArea};
-
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-enum FrameType {
-    Table,
-    Chart,
-}
-
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-enum ChartTimeFrame {
-    Month,
-    Year,
-    WindowYear,
+use tracing::{info, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+use weight_tracker::WtError;
+use weight_tracker::app::{App, MessageType, WindowType, merge_message};
+use weight_tracker::config::{StorageBackend, Theme, get_config_file, hash_passphrase, load_config};
+use weight_tracker::data::{
+    DateFormat, Entry, ImportReport, PendingChange, RHR, SOURCE_MANUAL, Unit, VOLATILITY_DAYS, WEIGHT,
+    backfill, changed_fields, detect_date_formats, edit_entry, find_entry, format_age, goal_etas, import_lines,
+    import_source,
+    insert_entry, last_entry_age, pending_changes, read_import_source, resolve_date, schedule_streak,
+    split_import_lines, volatility, week_label, weekly_change,
+};
+use weight_tracker::share::render_share_svg;
+use weight_tracker::storage::{CsvStorage, SqliteStorage, backups, get_data_file, read_entries};
+
+#[derive(Parser)]
+#[command(version, about = "A simple weight tracking app.")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Render without colors and with ASCII-only borders and markers. Also
+    /// enabled by setting the `NO_COLOR` environment variable.
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Stage every edit of this session and save them only once they are
+    /// committed in the review screen (C-s).
+    #[arg(long)]
+    staged: bool,
+    /// Log more details to the log file in the data directory (`-vv` for even more).
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-enum WindowType {
-    ClosePopup,
-    InputPopup,
-    MainWindow,
+#[derive(Subcommand)]
+enum Command {
+    /// Import `date, weight` lines from a CSV file or URL into the data file.
+    Import {
+        /// CSV file to import.
+        #[arg(required_unless_present = "url")]
+        path: Option<String>,
+        /// Fetch the CSV file over HTTP(S) instead.
+        #[arg(long, conflicts_with = "path")]
+        url: Option<String>,
+        /// Record the entries as coming from `import:<SOURCE>` instead of
+        /// `import:<file name>`.
+        #[arg(long)]
+        source: Option<String>,
+        /// Unit of the rows without a unit column (`kg` or `lb`). They keep it
+        /// in the data file and are converted for display.
+        #[arg(long, value_parser = parse_unit)]
+        unit: Option<Unit>,
+        /// Format of the date column: `%d-%m-%Y`, `%Y-%m-%d`, `%d/%m/%Y`,
+        /// `%m/%d/%Y` or `epoch`. Detected from the rows if not given.
+        #[arg(long, value_parser = parse_date_format)]
+        date_format: Option<DateFormat>,
+        /// Print the entries that would be added without changing the data file.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Log today's weight. Prompts for it on stdin if no weight is given.
+    Add {
+        /// Weight to log, e.g. `82.4`.
+        weight: Option<String>,
+        /// Read the weight from stdin without prompting, for pipes and scripts.
+        #[arg(long, conflicts_with = "weight")]
+        stdin: bool,
+        /// Where the weight came from, e.g. `ble-scale` or `api`.
+        #[arg(long, default_value = SOURCE_MANUAL)]
+        source: String,
+        /// Scale the weight was taken on, e.g. `gym`; defaults to `devices.default`.
+        #[arg(long)]
+        device: Option<String>,
+    },
+    /// Change the weight of an existing entry.
+    Edit {
+        /// Day of the entry, e.g. `5-3`, `05-03-2024` or `2024-03-05`.
+        date: String,
+        /// New weight, e.g. `81.9`.
+        weight: String,
+        /// New resting heart rate; the logged one is kept if not given.
+        #[arg(long)]
+        rhr: Option<String>,
+    },
+    /// Move an entry to the trash.
+    Delete {
+        /// Day of the entry, e.g. `5-3`, `05-03-2024` or `2024-03-05`.
+        date: String,
+        /// Don't ask for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Print the latest entry and trend; exits with 1 if the data is stale.
+    Status,
+    /// Print the last entries with the change to the one before, e.g. for a
+    /// shell prompt hook.
+    Last {
+        /// Number of entries to print.
+        #[arg(default_value_t = 5)]
+        count: usize,
+    },
+    /// Exit with 0 if today has an entry and 1 otherwise, for shell prompts,
+    /// cron jobs and login scripts.
+    CheckToday {
+        /// Print nothing, only set the exit code.
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Print a narrow summary, e.g. for receipt printers or MOTD scripts.
+    Report {
+        /// ASCII only output.
+        #[arg(long)]
+        plain: bool,
+        /// Maximum line width in characters.
+        #[arg(long, default_value_t = 40, value_parser = clap::value_parser!(u16).range(20..))]
+        width: u16,
+    },
+    /// Export the data to a CSV file, or an Excel workbook with a chart if the
+    /// path ends in `.xlsx`.
+    Export {
+        path: String,
+    },
+    /// Draw a square SVG image of the weight curve and the change, for
+    /// sharing. Absolute weights are left out unless `--weights` is given.
+    Share {
+        path: String,
+        /// Label the first and last weight.
+        #[arg(long)]
+        weights: bool,
+        /// Only show the last DAYS days instead of all entries.
+        #[arg(long)]
+        days: Option<u64>,
+    },
+    /// Copy the stored data to a timestamped backup next to it.
+    Backup {
+        /// Also upload the data file and the backup to the configured remote.
+        #[arg(long)]
+        remote: bool,
+    },
+    /// Read a passphrase from stdin and print the `[lock]` setting that asks
+    /// for it on start.
+    Passphrase,
+    /// Write the weight of a day into its daily note, as set in `[daily_note]`.
+    Note {
+        /// Day to write, e.g. `5`, `5-3` or `05-03-2024`; today by default.
+        date: Option<String>,
+    },
+    /// Print where the config and data live and what state they are in, to
+    /// find out where data went missing. Exits with 1 if a problem was found.
+    Doctor,
+    /// Compare two data files, backups or exports entry by entry, e.g. before
+    /// merging in another device's copy. Exits with 1 if they differ.
+    Diff {
+        /// The older file, e.g. a backup.
+        old: String,
+        /// The newer file, whose changes are listed.
+        new: String,
+    },
+    /// Print a completion script for the given shell.
+    Completions {
+        shell: Shell,
+    },
+    /// Fill the days between two entries with interpolated entries marked as
+    /// estimated, for tools that need a daily series.
+    Backfill {
+        /// Print the entries that would be added without changing the data file.
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-enum TextMode {
-    Edit,
-    Append,
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(e) = init_logging(cli.verbose) {
+        eprintln!("weight-tracker: logging disabled: {}", e);
+    }
+    let ret = match cli.command {
+        None => {
+            let no_color = cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty());
+            run_app(no_color, cli.staged).map(|()| ExitCode::SUCCESS)
+        }
+        Some(Command::Import { path, url, source, unit, date_format, dry_run }) => {
+            let path = url.or(path).unwrap_or_default();
+            run_import(path, source, unit, date_format, dry_run).map(|()| ExitCode::SUCCESS)
+        }
+        Some(Command::Add { weight, stdin, source, device }) => {
+            run_add(weight, stdin, source, device).map(|()| ExitCode::SUCCESS)
+        }
+        Some(Command::Edit { date, weight, rhr }) => {
+            run_edit(date, weight, rhr).map(|()| ExitCode::SUCCESS)
+        }
+        Some(Command::Delete { date, yes }) => run_delete(date, yes),
+        Some(Command::Status) => run_status(),
+        Some(Command::CheckToday { quiet }) => run_check_today(quiet),
+        Some(Command::Last { count }) => run_last(count).map(|()| ExitCode::SUCCESS),
+        Some(Command::Report { plain, width }) => run_report(plain, width as usize).map(|()| ExitCode::SUCCESS),
+        Some(Command::Export { path }) => run_export(path).map(|()| ExitCode::SUCCESS),
+        Some(Command::Share { path, weights, days }) => {
+            run_share(path, weights, days).map(|()| ExitCode::SUCCESS)
+        }
+        Some(Command::Backup { remote }) => run_backup(remote).map(|()| ExitCode::SUCCESS),
+        Some(Command::Backfill { dry_run }) => run_backfill(dry_run).map(|()| ExitCode::SUCCESS),
+        Some(Command::Passphrase) => run_passphrase().map(|()| ExitCode::SUCCESS),
+        Some(Command::Note { date }) => run_note(date).map(|()| ExitCode::SUCCESS),
+        Some(Command::Doctor) => run_doctor(),
+        Some(Command::Diff { old, new }) => run_diff(&old, &new),
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "weight-tracker", &mut io::stdout());
+            Ok(ExitCode::SUCCESS)
+        }
+    };
+    match ret {
+        Ok(code) => code,
+        Err(e) => {
+            warn!("exiting with error: {}", e);
+            eprintln!("weight-tracker: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
 
-#[allow(unused)]
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-enum MessageType {
-    Info,
-    Warning,
-    Error,
+/// Sends logs to `weight-tracker.log` next to the data file, never to the
+/// terminal the TUI draws on.
+fn init_logging(verbose: u8) -> Result<(), WtError> {
+    let path = Path::new(&get_data_file()?).with_file_name("weight-tracker.log");
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let level = match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_writer(std::sync::Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(level)
+        .init();
+    Ok(())
 }
 
-#[allow(dead_code)]
-struct App<'a> {
-    close: bool,
-    current_window: WindowType,
-    data: RefCell<Vec<(String, f64)>>,
-    table_state: TableState,
-    current_frame: FrameType,
-    current_tf: ChartTimeFrame,
-    selected_date_wy: NaiveDate,
-    selected_date_y: NaiveDate,
-    selected_date_m: NaiveDate,
-    text_area: [TextArea<'a>; 2],
-    text_is_valid: [bool; 2],
-    selected_area: usize,
-    text_mode: Option<TextMode>,
-    message: Option<(String, MessageType)>,
-    msg_time_elapsed: Option<Instant>,
-    wait_time_elapsed: Option<Instant>,
-    scroll_offset: usize,
-    reversed_offset: bool,
-    rm_confirm: bool,
+/// Size, permissions and modification time of `path`, or why it can't be read.
+fn describe_file(path: &Path) -> String {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return String::from("missing"),
+        Err(e) => return format!("unreadable ({})", e),
+    };
+    let access = if meta.permissions().readonly() { "read-only" } else { "writable" };
+    #[cfg(unix)]
+    let access = {
+        use std::os::unix::fs::PermissionsExt;
+        format!("{}, mode {:o}", access, meta.permissions().mode() & 0o777)
+    };
+    let modified = meta
+        .modified()
+        .map(|x| DateTime::<Local>::from(x).format("%d-%m-%Y %H:%M:%S").to_string())
+        .unwrap_or_else(|_| String::from("unknown"));
+    format!("{} bytes, {}, last saved {}", meta.len(), access, modified)
 }
 
-fn center_text(s: String) -> Text<'static> {
-    return Text::styled(s, Style::default()).centered();
+fn run_diff(old: &str, new: &str) -> Result<ExitCode, WtError> {
+    let mut app = App::default();
+    if let Err(e) = load_config().and_then(|config| app.apply_config(config)) {
+        eprintln!("weight-tracker: {}", e);
+    }
+    let unit = app.config.display.unit;
+    let read = |path: &str| {
+        read_entries(path).map(|mut data| {
+            data.iter_mut().for_each(|x| x.normalize(unit));
+            data
+        })
+    };
+    let (before, after) = (read(old)?, read(new)?);
+    let changes = pending_changes(&before, &after);
+    println!("--- {} ({} entries)", old, before.len());
+    println!("+++ {} ({} entries)", new, after.len());
+    let weight = |x: &Entry| app.format_weight(x.weight);
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &changes {
+        match change {
+            PendingChange::Added(x) => {
+                added += 1;
+                println!("+ {}  {:>5}", x.date, weight(x));
+            }
+            PendingChange::Deleted(x) => {
+                removed += 1;
+                println!("- {}  {:>5}", x.date, weight(x));
+            }
+            PendingChange::Edited(a, b) => {
+                changed += 1;
+                let fields = changed_fields(a, b).join(", ");
+                println!("~ {}  {:>5} -> {:>5}  ({})", a.date, weight(a), weight(b), fields);
+            }
+        }
+    }
+    println!("{} added, {} removed, {} changed", added, removed, changed);
+    Ok(if changes.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
 }
 
-fn get_data_file() -> io::Result<String> {
-    let base_dirs = BaseDirs::new();
-    if let None = base_dirs {
-        return Err(Error::other("BaseDirs::new() failed"));
+fn run_doctor() -> Result<ExitCode, WtError> {
+    let mut healthy = true;
+    let config_path = get_config_file()?;
+    let config = if config_path.exists() {
+        println!("Config: {}", config_path.display());
+        load_config()
+    } else {
+        println!("Config: {} (missing, using defaults)", config_path.display());
+        Ok(Default::default())
+    };
+    // Validated like on start; an invalid config leaves the defaults in place.
+    let mut checked = App::default();
+    if let Err(e) = config.and_then(|config| checked.apply_config(config)) {
+        println!("  {}", e);
+        healthy = false;
+    }
+    let config = &checked.config;
+    let data_path = get_data_file()?;
+    let data_path = match config.storage.backend {
+        StorageBackend::Csv => Path::new(&data_path).to_path_buf(),
+        StorageBackend::Sqlite => Path::new(&data_path).with_extension("sqlite"),
+    };
+    let backend = match config.storage.backend {
+        StorageBackend::Csv => "csv",
+        StorageBackend::Sqlite => "sqlite",
+    };
+    println!("Data file: {} ({})", data_path.display(), backend);
+    println!("  {}", describe_file(&data_path));
+    healthy &= match fs::metadata(&data_path) {
+        Ok(meta) => !meta.permissions().readonly(),
+        Err(e) => e.kind() == io::ErrorKind::NotFound,
+    };
+    let journal = PathBuf::from(format!("{}.journal", data_path.display()));
+    if let Ok(text) = fs::read_to_string(&journal) {
+        println!("Journal: {} unsaved changes in {}", text.lines().count(), journal.display());
     }
-    let mut data_path = base_dirs.unwrap().data_local_dir().to_path_buf();
-    data_path.push("weight-tracker");
-    if !data_path.try_exists()? {
-        let data_path_str = data_path.to_str().unwrap();
-        fs::create_dir_all(data_path_str)?;
+    match load_app() {
+        Ok(app) => println!("Entries: {}", app.data.borrow().len()),
+        Err(e) => {
+            println!("Entries: cannot load ({})", e);
+            healthy = false;
+        }
     }
-    data_path.push("weight-tracker.csv");
-    let ret = data_path.to_str();
-    if let Some(ret) = ret {
-        return Ok(ret.to_string());
+    let dir = data_path.parent().unwrap_or(Path::new("."));
+    let backups = backups(&data_path.to_string_lossy())?;
+    match backups.last().and_then(|x| Path::new(x).file_name()) {
+        Some(newest) => println!("Backups: {} (newest {})", backups.len(), newest.to_string_lossy()),
+        None => println!("Backups: none"),
     }
-    return Err(Error::other("Cannot create path str"));
+    let lock = match (&config.lock.passphrase, config.lock.idle_minutes) {
+        (None, _) => String::from("off"),
+        (Some(_), 0) => String::from("on start"),
+        (Some(_), minutes) => format!("on start and after {} idle minutes", minutes),
+    };
+    println!("Lock screen: {}", lock);
+    println!("Log file: {}", dir.join("weight-tracker.log").display());
+    Ok(if healthy { ExitCode::SUCCESS } else { ExitCode::FAILURE })
 }
 
-fn main() -> io::Result<()> {
-    let mut term = ratatui::init();
-    let mut app = App::default();
-    let path = get_data_file()?;
-    if fs::exists(&path)? {
-        app.import_data(&path)?;
+/// Prints the messages `app` collected to stderr, where the TUI would have
+/// shown them.
+fn print_messages(app: &mut App) {
+    for (msg, _) in app.messages.take_all() {
+        eprintln!("weight-tracker: {}", msg);
     }
-    app.table_state.select_last();
-    let ret = app.run(&mut term);
-    let mut out_file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(&path)?;
-    app.export_data(&mut out_file)?;
-    ratatui::try_restore()?;
-    return ret;
 }
 
-impl App<'_> {
-    #[allow(dead_code)]
-    fn default_test() -> Self {
-        let now = Local::now().date_naive();
-        return App {
-            close: false,
-            current_window: WindowType::MainWindow,
-            data: RefCell::new(vec![
-                ("26-04-2024".to_string(), 90.1),
-                ("27-04-2024".to_string(), 89.9),
-                ("01-05-2024".to_string(), 91.5),
-                ("02-05-2024".to_string(), 94.1),
-                ("03-05-2024".to_string(), 87.9),
-            ]),
-            table_state: TableState::default(),
-            current_frame: FrameType::Table,
-            current_tf: ChartTimeFrame::Month,
-            selected_date_wy: now.clone(),
-            selected_date_y: now.clone(),
-            selected_date_m: now,
-            text_area: [TextArea::default(), TextArea::default()],
-            text_is_valid: [false, false],
-            selected_area: 1,
-            text_mode: None,
-            message: None,
-            msg_time_elapsed: None,
-            wait_time_elapsed: None,
-            scroll_offset: 0,
-            reversed_offset: false,
-            rm_confirm: false,
-        };
-    }
+/// Loads config and data without touching the terminal.
+fn load_app<'a>() -> Result<App<'a>, WtError> {
+    let mut app = open_app()?;
+    app.load()?;
+    // Saving writes the whole trash, so a delete must add to the stored one.
+    app.trash = app.load_state()?.trash;
+    Ok(app)
+}
 
-    #[allow(dead_code)]
-    fn default() -> Self {
-        let now = Local::now().date_naive();
-        return App {
-            close: false,
-            current_window: WindowType::MainWindow,
-            data: RefCell::new(Vec::new()),
-            table_state: TableState::default(),
-            current_frame: FrameType::Table,
-            current_tf: ChartTimeFrame::Month,
-            selected_date_wy: now.clone(),
-            selected_date_y: now.clone(),
-            selected_date_m: now,
-            text_area: [TextArea::default(), TextArea::default()],
-            text_is_valid: [false, false],
-            selected_area: 1,
-            text_mode: None,
-            message: None,
-            msg_time_elapsed: None,
-            wait_time_elapsed: None,
-            scroll_offset: 0,
-            reversed_offset: false,
-            rm_confirm: false,
-        };
+/// The app with its configuration and storage, but no entries loaded yet.
+fn open_app<'a>() -> Result<App<'a>, WtError> {
+    let mut app = App::default();
+    if let Err(e) = load_config().and_then(|config| app.apply_config(config)) {
+        app.show_error(e);
     }
-
-    fn modify_data(&mut self, element: (String, Option<f64>)) -> bool {
-        let idx = self.table_state.selected_mut();
-        if let None = idx {
-            return false;
+    app.config_path = get_config_file()?.to_string_lossy().into_owned();
+    app.path = get_data_file()?;
+    app.storage = match app.config.storage.backend {
+        StorageBackend::Csv => {
+            Box::new(CsvStorage::new(app.path.clone(), app.config.storage.compress_threshold))
         }
-        let idx = idx.unwrap();
-        let data_ref = self.data.get_mut();
-        if let (s, Some(num)) = element {
-            if self.text_mode == Some(TextMode::Edit) {
-                data_ref[idx].1 = num;
-            } else if self.text_mode == Some(TextMode::Append) {
-                let l_bound = data_ref.binary_search_by(|x| {
-                    // Format should already checked beforehand.
-                    let lhs = NaiveDate::parse_from_str(x.0.as_str(), "%d-%m-%Y").unwrap();
-                    let rhs = NaiveDate::parse_from_str(s.as_str(), "%d-%m-%Y").unwrap();
-                    if lhs < rhs {
-                        Ordering::Less
-                    } else if lhs == rhs {
-                        Ordering::Equal
-                    } else {
-                        Ordering::Greater
-                    }
-                });
-                if let Ok(_) = l_bound {
-                    self.message = Some((String::from("Cannot add element. Did you mean to edit?"), MessageType::Error));
-                    return false;
-                } else {
-                    data_ref.insert(l_bound.unwrap_err(), (s, num));
-                }
-            }
-        } else if let (_, None) = element {
-            data_ref.remove(idx);
-            self.rm_confirm = false;
-            self.message = None;
-            self.msg_time_elapsed = None;
+        StorageBackend::Sqlite => {
+            let path = Path::new(&app.path).with_extension("sqlite");
+            Box::new(SqliteStorage::open(path.to_string_lossy().into_owned())?)
         }
-        return true;
+    };
+    Ok(app)
+}
+
+fn parse_unit(text: &str) -> Result<Unit, String> {
+    Unit::parse(text).ok_or_else(|| format!("unknown unit '{}', expected kg or lb", text))
+}
+
+fn parse_date_format(text: &str) -> Result<DateFormat, String> {
+    DateFormat::from_name(text).ok_or_else(|| {
+        let names = DateFormat::ALL.map(|x| x.name()).join(", ");
+        format!("unknown date format '{}', expected one of {}", text, names)
+    })
+}
+
+/// Picks the date format of an import: the detected one, or the user's
+/// choice on a terminal if several fit.
+fn choose_date_format(lines: &[String]) -> Result<DateFormat, WtError> {
+    let formats = detect_date_formats(lines);
+    if formats.len() < 2 {
+        return Ok(formats.first().copied().unwrap_or_default());
+    }
+    if !io::stdin().is_terminal() {
+        let names = formats.iter().map(|x| x.name()).collect::<Vec<_>>().join(" and ");
+        return Err(WtError::Validation(format!("the dates fit {}; pass one with --date-format", names)));
+    }
+    for (i, format) in formats.iter().enumerate() {
+        eprintln!("{}: {}", i + 1, format.name());
     }
+    eprint!("The dates fit several formats, which one? [1] ");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    let choice = match line.trim() {
+        "" => 1,
+        text => text.parse::<usize>().unwrap_or_default(),
+    };
+    formats
+        .get(choice.wrapping_sub(1))
+        .copied()
+        .ok_or_else(|| WtError::Validation(format!("invalid choice '{}'", line.trim())))
+}
 
-    fn import_data(&mut self, path: &String) -> io::Result<()> {
-        let file = File::open(&path);
-        if let Err(_) = file {
-            // Do nothing in case of file does not exist
-            return Ok(());
-        }
-        let mut file = file.unwrap();
-        let mut lines = String::new();
-        file.read_to_string(&mut lines)?;
-        let mut ret = lines
-            .split(['\r', '\n'])
-            .filter_map(|x| {
-                if x.is_empty() {
-                    None
-                } else {
-                    Some(
-                        x.trim()
-                            .split(',')
-                            .filter_map(|x| if x.is_empty() { None } else { Some(x.trim()) })
-                            .collect::<Vec<_>>(),
-                    )
-                }
-            })
-            .collect::<VecDeque<_>>();
-        let header = ret.pop_front();
-        if let Some(header) = header {
-            if header.len() != 2 {
-                return Err(Error::other("Invalid Header"));
-            }
-            if header[0] != "Date" && header[1] != "Weight" {
-                return Err(Error::other("Invalid Header"));
-            }
-            let temp = ret
-                .iter()
-                .filter_map(|x| {
-                    if let Ok(num) = x[1].trim().parse::<f64>() {
-                        Some((String::from(x[0]), num))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-            self.data = RefCell::new(temp);
+fn run_import(
+    source: String,
+    label: Option<String>,
+    unit: Option<Unit>,
+    date_format: Option<DateFormat>,
+    dry_run: bool,
+) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let lines = split_import_lines(&read_import_source(&source)?);
+    let format = match date_format {
+        Some(format) => format,
+        None => choose_date_format(&lines)?,
+    };
+    let mut report = ImportReport::default();
+    let entry_source = import_source(&source, label.as_deref());
+    let added = import_lines(app.data.get_mut(), &lines, 0, &entry_source, format, &mut report);
+    let display_unit = app.config.display.unit;
+    for entry in app.data.get_mut().iter_mut().filter(|x| added.contains(&x.date)) {
+        if let (Some(unit), None) = (unit, entry.original) {
+            entry.original = Some((unit, entry.weight));
         }
-        return Ok(());
+        entry.normalize(display_unit);
     }
-
-    fn export_data(&self, file: &mut File) -> io::Result<()> {
-        let cloned_data = RefCell::clone(&self.data);
-        write!(file, "Date, Weight\n")?;
-        for (date, weight) in cloned_data.into_inner().iter() {
-            write!(file, "{}, {:.1}\n", date, weight)?;
+    info!(
+        "import {} (dry run: {}): {} added, {} duplicates, {} failed",
+        source,
+        dry_run,
+        report.added,
+        report.duplicates,
+        report.failed.len()
+    );
+    if dry_run {
+        let data_ref = app.data.borrow();
+        for entry in data_ref.iter().filter(|x| added.contains(&x.date)) {
+            println!("+ {}, {}", entry.date, WEIGHT.format(entry.weight));
         }
-        return Ok(());
+    } else {
+        app.journal(&added);
+        app.save()?;
+        print_messages(&mut app);
+    }
+    println!(
+        "{} {}: {} added, {} duplicates skipped, {} failed",
+        if dry_run { "Would import" } else { "Imported" },
+        source,
+        report.added,
+        report.duplicates,
+        report.failed.len()
+    );
+    for (line_no, reason) in &report.failed {
+        eprintln!("line {}: {}", line_no, reason);
+    }
+    for (line_no, text) in &report.skipped {
+        eprintln!("line {}: skipped '{}'", line_no, text);
+    }
+    if report.stripped > 0 {
+        eprintln!("dropped empty trailing columns from {} lines", report.stripped);
     }
+    Ok(())
+}
 
-    fn run(&mut self, term: &mut DefaultTerminal) -> io::Result<()> {
-        let tick_rate = Duration::from_micros(16667);
-        let mut now = Instant::now();
-        while !self.close {
-            term.draw(|f| self.draw(f))?;
-            let timeout = tick_rate.saturating_add(now.elapsed());
-            if event::poll(timeout)? {
-                self.handle_events()?;
-            }
-            if now.elapsed() >= tick_rate {
-                now = Instant::now();
+fn run_add(
+    weight: Option<String>,
+    stdin: bool,
+    source: String,
+    device: Option<String>,
+) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let text = match weight {
+        Some(text) => text,
+        None => {
+            if !stdin {
+                eprint!("Weight: ");
+                io::stderr().flush()?;
             }
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            line
         }
-        return Ok(());
+    };
+    let weight = app.parse_weight(&text).map_err(WtError::Validation)?;
+    if device.as_ref().is_some_and(|x| x.contains(',')) {
+        return Err(WtError::Validation(String::from("device must not contain a comma")));
     }
+    let today = Local::now().date_naive();
+    let date = today.format("%d-%m-%Y").to_string();
+    let data = app.data.get_mut();
+    let (idx, replaced) = match find_entry(data, today) {
+        Ok(idx) => {
+            let rhr = data[idx].rhr;
+            edit_entry(&mut data[idx], weight, rhr);
+            (idx, true)
+        }
+        Err(idx) => {
+            insert_entry(data, Entry::new(date.clone(), weight));
+            (idx, false)
+        }
+    };
+    data[idx].source = Some(source);
+    data[idx].device = device.or_else(|| app.config.devices.default.clone());
+    let stored = WEIGHT.format(data[idx].weight);
+    let stored = app.localize(stored);
+    info!("add {}: {} (replaced: {})", date, stored, replaced);
+    app.journal(std::slice::from_ref(&date));
+    app.save()?;
+    print_messages(&mut app);
+    println!("{} {}, {}", if replaced { "Updated" } else { "Added" }, date, stored);
+    if app.config.daily_note.as_ref().is_some_and(|x| x.on_add) {
+        println!("Wrote {}", app.write_daily_note(today)?);
+    }
+    Ok(())
+}
 
-    fn init_text_area(&mut self) {
-        let date_text = match self.text_mode {
-            Some(TextMode::Edit) => {
-                let idx = self.table_state.selected();
-                if let Some(idx) = idx {
-                    let data_ref = self.data.get_mut();
-                    self.text_is_valid[0] = true;
-                    data_ref[idx].0.clone()
-                } else {
-                    self.text_is_valid[0] = false;
-                    String::from("")
-                }
-            }
-            Some(TextMode::Append) => {
-                self.text_is_valid[0] = true;
-                Local::now().date_naive().format("%d-%m-%Y").to_string()
-            }
-            None => {
-                self.text_is_valid[0] = false;
-                String::from("")
-            }
-        };
-
-        let weight_text = match self.text_mode {
-            Some(TextMode::Edit) => {
-                let idx = self.table_state.selected();
-                if let Some(idx) = idx {
-                    let data_ref = self.data.get_mut();
-                    self.text_is_valid[0] = true;
-                    format!("{:.1}", data_ref[idx].1)
-                } else {
-                    self.text_is_valid[0] = false;
-                    String::from("")
-                }
-            }
-            _ => {
-                self.text_is_valid[0] = false;
-                String::from("")
-            }
-        };
+fn run_edit(date: String, weight: String, rhr: Option<String>) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let date = resolve_date(&date, Local::now().date_naive())
+        .ok_or_else(|| WtError::Validation(format!("invalid date '{}'", date)))?;
+    let weight = app.parse_weight(&weight).map_err(WtError::Validation)?;
+    let idx = app.select_entry(date)?;
+    let rhr = match rhr {
+        Some(text) => Some(RHR.validate(&text).map_err(WtError::Validation)?),
+        None => app.data.borrow()[idx].rhr,
+    };
+    let date = date.format("%d-%m-%Y").to_string();
+    app.modify_data((date.clone(), Some((weight, rhr))));
+    let stored = app.format_weight(app.data.borrow()[idx].weight);
+    info!("edit {}: {}", date, stored);
+    app.save()?;
+    print_messages(&mut app);
+    println!("Updated {}, {}", date, stored);
+    Ok(())
+}
 
-        self.selected_area = 1;
+fn run_delete(date: String, yes: bool) -> Result<ExitCode, WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let date = resolve_date(&date, Local::now().date_naive())
+        .ok_or_else(|| WtError::Validation(format!("invalid date '{}'", date)))?;
+    let idx = app.select_entry(date)?;
+    let date = date.format("%d-%m-%Y").to_string();
+    let weight = app.format_weight(app.data.borrow()[idx].weight);
+    if !yes {
+        eprint!("Delete {}, {}? [y/N] ", date, weight);
+        io::stderr().flush()?;
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)?;
+        if !matches!(line.trim(), "y" | "Y" | "yes") {
+            eprintln!("Nothing deleted");
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    app.modify_data((date.clone(), None));
+    info!("delete {}: {}", date, weight);
+    app.save()?;
+    print_messages(&mut app);
+    println!("Deleted {}, {} (restore it from the trash with T)", date, weight);
+    Ok(ExitCode::SUCCESS)
+}
 
-        self.text_area[0] = TextArea::new(vec![date_text]);
-        self.text_area[1] = TextArea::new(vec![weight_text]);
+fn run_note(date: Option<String>) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let today = Local::now().date_naive();
+    let date = match date {
+        Some(text) => resolve_date(&text, today)
+            .ok_or_else(|| WtError::Validation(format!("invalid date '{}'", text)))?,
+        None => today,
+    };
+    println!("Wrote {}", app.write_daily_note(date)?);
+    Ok(())
+}
 
-        self.text_area[0].move_cursor(CursorMove::End);
-        self.text_area[1].move_cursor(CursorMove::End);
+fn run_export(path: String) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    if path.to_lowercase().ends_with(".xlsx") {
+        app.export_xlsx(&path)?;
+    } else {
+        let mut file = File::create(&path)?;
+        app.export_data(&mut file)?;
     }
+    println!("Exported {} entries to {}", app.data.borrow().len(), path);
+    Ok(())
+}
 
-    fn activate_text(&mut self) {
-        if self.selected_area == 0 {
-            let text = self.text_area[0].lines()[0].clone();
-            let date = NaiveDate::parse_from_str(text.as_str(), "%d-%m-%Y");
-            if let Ok(_) = date {
-                self.text_area[0].set_cursor_line_style(Style::default().fg(Color::LightGreen));
-                self.text_area[0]
-                    .set_cursor_style(Style::default().add_modifier(Modifier::REVERSED));
-                self.text_area[0].set_block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_style(Color::LightGreen)
-                        .title(" Date ")
-                        .title_bottom(" Valid "),
-                );
-                self.text_is_valid[0] = true;
-            } else {
-                self.text_area[0].set_cursor_line_style(Style::default().fg(Color::LightRed));
-                self.text_area[0]
-                    .set_cursor_style(Style::default().add_modifier(Modifier::REVERSED));
-                self.text_area[0].set_block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_style(Color::LightRed)
-                        .title(" Date ")
-                        .title_bottom(" Invalid "),
-                );
-                self.text_is_valid[0] = false;
-            }
-        } else if self.selected_area == 1 {
-            let text = self.text_area[1].lines()[0].clone();
-            let weight = text.parse::<f64>();
-            match weight {
-                Ok(w) if w > 0f64 => {
-                    self.text_area[1].set_cursor_line_style(Style::default().fg(Color::LightGreen));
-                    self.text_area[1]
-                        .set_cursor_style(Style::default().add_modifier(Modifier::REVERSED));
-                    self.text_area[1].set_block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .border_style(Color::LightGreen)
-                            .title(" Weight ")
-                            .title_bottom(" Valid "),
-                    );
-                    self.text_is_valid[0] = true;
-                }
-                _ => {
-                    self.text_area[1].set_cursor_line_style(Style::default().fg(Color::LightRed));
-                    self.text_area[1]
-                        .set_cursor_style(Style::default().add_modifier(Modifier::REVERSED));
-                    self.text_area[1].set_block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .border_style(Color::LightRed)
-                            .title(" Weight ")
-                            .title_bottom(" Invalid "),
-                    );
-                    self.text_is_valid[1] = false;
-                }
-            }
+fn run_share(path: String, weights: bool, days: Option<u64>) -> Result<(), WtError> {
+    let app = load_app()?;
+    let since = days.and_then(|x| Local::now().date_naive().checked_sub_days(Days::new(x)));
+    let points = app
+        .stats_data()
+        .iter()
+        .filter(|x| !x.excluded)
+        .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.weight)))
+        .filter(|x| since.is_none_or(|since| x.0 >= since))
+        .collect::<Vec<_>>();
+    let svg = render_share_svg(&points, app.config.display.unit, weights)
+        .ok_or_else(|| WtError::Validation(String::from("need entries on at least two days to share")))?;
+    fs::write(&path, svg)?;
+    println!("Wrote {}", path);
+    Ok(())
+}
+
+fn run_backfill(dry_run: bool) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let added = backfill(app.data.get_mut());
+    info!("backfill (dry run: {}): {} estimated entries", dry_run, added.len());
+    if dry_run {
+        let data_ref = app.data.borrow();
+        for entry in data_ref.iter().filter(|x| added.contains(&x.date)) {
+            println!("+ {}, {}", entry.date, WEIGHT.format(entry.weight));
         }
+    } else {
+        app.journal(&added);
+        app.save()?;
+        print_messages(&mut app);
     }
+    println!(
+        "{} {} estimated entries",
+        if dry_run { "Would add" } else { "Added" },
+        added.len()
+    );
+    Ok(())
+}
 
-    fn inactivate_text(&mut self) {
-        let inactive_area = (self.selected_area + 1) % 2;
-        self.text_area[inactive_area].set_cursor_line_style(Style::default());
-        self.text_area[inactive_area].set_cursor_style(Style::default());
-        let title = if inactive_area == 0 {
-            " Date "
-        } else if inactive_area == 1 {
-            " Weight "
-        } else {
-            unreachable!("Invalid index");
-        };
-        if inactive_area == 0 {
-            let text = self.text_area[0].lines()[0].clone();
-            let date = NaiveDate::parse_from_str(text.as_str(), "%d-%m-%Y");
-            if let Ok(_) = date {
-                self.text_is_valid[0] = true;
-            } else {
-                self.text_is_valid[0] = false;
-            }
-        } else if inactive_area == 1 {
-            let text = self.text_area[1].lines()[0].parse::<f64>();
-            if let Ok(_) = text {
-                self.text_is_valid[1] = true;
-            } else {
-                self.text_is_valid[1] = false;
-            }
+fn run_backup(remote: bool) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    let dest = app.storage.backup()?;
+    info!("backed up {} to {}", app.storage.location(), dest);
+    println!("Backed up {} entries to {}", app.saved_data.len(), dest);
+    if remote {
+        for path in [app.storage.location(), dest] {
+            println!("Uploaded {}", app.upload(&path)?);
         }
-        self.text_area[inactive_area].set_block(
-            Block::default()
-                .borders(Borders::ALL)
-                .style(Style::default().fg(Color::DarkGray))
-                .title(title),
-        );
     }
+    Ok(())
+}
 
-    fn draw(&mut self, frame: &mut Frame) {
-        let area = frame.area();
-        let w = area.width;
-        let h = area.height;
-        if w >= MIN_WIDTH && h >= MIN_HEIGHT {
-            // Vertical split
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(vec![
-                    Constraint::Length(3),
-                    Constraint::Min(10),
-                    Constraint::Length(3),
-                ])
-                .split(frame.area());
-
-            // Title
-            self.render_title(chunks[0], frame);
-
-            // Middle split
-            {
-                let mid_chunks = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints(vec![Constraint::Length(21), Constraint::Min(20)])
-                    .split(chunks[1]);
-
-                self.render_table(mid_chunks[0], frame);
-                self.render_chart(mid_chunks[1], frame);
-            }
+fn run_report(plain: bool, width: usize) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    print!("{}", app.report(width, plain));
+    Ok(())
+}
 
-            // Key hint
-            self.render_message_box(chunks[2], frame);
-            if self.current_window == WindowType::ClosePopup {
-                self.render_close_popup(frame);
-            } else if self.current_window == WindowType::InputPopup {
-                self.render_input_popup(frame);
-            }
-        } else {
-            self.render_window_too_small(frame, w, h);
-        }
+fn run_passphrase() -> Result<(), WtError> {
+    eprint!("Passphrase (shown as typed): ");
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    let passphrase = line.trim_end_matches(['\r', '\n']);
+    if passphrase.is_empty() {
+        return Err(WtError::Validation(String::from("empty passphrase")));
     }
+    let salt = uuid::Uuid::new_v4().simple().to_string();
+    println!("[lock]");
+    println!("passphrase = \"{}\"", hash_passphrase(passphrase, &salt[..16]));
+    Ok(())
+}
 
-    fn render_window_too_small(&self, frame: &mut Frame, w: u16, h: u16) {
-        let layout = Layout::vertical([Constraint::Length(4)]).flex(Flex::Center);
-        let w_span = if w < MIN_WIDTH {
-            Span::styled(format!("{}", w), Style::new().light_red())
-        } else {
-            Span::styled(format!("{}", w), Style::new().light_green())
-        };
-        let h_span = if h < MIN_HEIGHT {
-            Span::styled(format!("{}", h), Style::new().light_red())
-        } else {
-            Span::styled(format!("{}", h), Style::new().light_green())
-        };
-        let lines = vec![
-            Line::from("Terminal size too small:"),
-            Line::from(vec![w_span, Span::raw(" x "), h_span]),
-            Line::from("Required size:"),
-            Line::from(format!("{} x {}", MIN_WIDTH, MIN_HEIGHT)),
-        ];
-        let [area] = layout.areas(frame.area());
-        frame.render_widget(Paragraph::new(lines).alignment(Alignment::Center), area);
+fn run_last(count: usize) -> Result<(), WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let data_ref = app.data.borrow();
+    let start = data_ref.len().saturating_sub(count);
+    let rows = data_ref
+        .iter()
+        .enumerate()
+        .skip(start)
+        .map(|(i, x)| {
+            let weight = app.format_weight(x.weight);
+            let delta = i
+                .checked_sub(1)
+                .map(|prev| app.localize(format!("{:+.1}", x.weight - data_ref[prev].weight)))
+                .unwrap_or_default();
+            (x, weight, delta)
+        })
+        .collect::<Vec<_>>();
+    let weight_width = rows.iter().map(|x| x.1.chars().count()).max().unwrap_or_default();
+    let delta_width = rows.iter().map(|x| x.2.chars().count()).max().unwrap_or_default();
+    for (entry, weight, delta) in &rows {
+        let flag = if entry.excluded { " excluded" } else { "" };
+        let line = format!(
+            "{}  {:>w$}  {:>d$}{}",
+            entry.date,
+            weight,
+            delta,
+            flag,
+            w = weight_width,
+            d = delta_width
+        );
+        println!("{}", line.trim_end());
     }
+    Ok(())
+}
 
-    fn render_input_popup(&mut self, frame: &mut Frame) {
-        let area = frame.area();
-        let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
-        let horizontal = Layout::horizontal([Constraint::Length(25)]).flex(Flex::Center);
-        let [area_popup] = vertical.areas(area);
-        let [area_popup] = horizontal.areas(area_popup);
-        let horizontal =
-            Layout::horizontal([Constraint::Length(15), Constraint::Length(11)]).flex(Flex::Center);
-        let area: [Rect; 2] = horizontal.areas(area_popup);
-
-        self.activate_text();
-        self.inactivate_text();
-
-        frame.render_widget(Clear, area_popup); //this clears out the background
-        frame.render_widget(&self.text_area[0], area[0]);
-        frame.render_widget(&self.text_area[1], area[1]);
+fn run_check_today(quiet: bool) -> Result<ExitCode, WtError> {
+    let mut app = load_app()?;
+    if !quiet {
+        print_messages(&mut app);
     }
-    fn render_close_popup(&self, frame: &mut Frame) {
-        let area = frame.area();
-        let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
-        let horizontal = Layout::horizontal([Constraint::Length(21)]).flex(Flex::Center);
-        let [area] = vertical.areas(area);
-        let [area] = horizontal.areas(area);
-        let block = Block::bordered();
-        let text = Paragraph::new(center_text(String::from("Quit the app? [Y/n]"))).block(block);
-        frame.render_widget(Clear, area); //this clears out the background
-        frame.render_widget(text, area);
+    let today = Local::now().date_naive();
+    let data_ref = app.data.borrow();
+    let entry = find_entry(&data_ref, today).ok().map(|idx| &data_ref[idx]);
+    if !quiet {
+        match entry {
+            Some(entry) => println!("Today: {}", app.format_weight(entry.weight)),
+            None => println!("No entry for today yet"),
+        }
     }
+    Ok(if entry.is_some() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
 
-    fn render_title(&self, area: Rect, frame: &mut Frame) {
-        let title_block = Block::default()
-            .borders(Borders::ALL)
-            .style(Style::default());
-        let title = Paragraph::new(Text::styled("Weight Tracker", Style::default()))
-            .centered()
-            .block(title_block.clone());
-        frame.render_widget(title, area);
+fn run_status() -> Result<ExitCode, WtError> {
+    let mut app = load_app()?;
+    print_messages(&mut app);
+    let data_ref = app.data.borrow();
+    let Some(last) = data_ref.last() else {
+        println!("No entries yet");
+        return Ok(ExitCode::FAILURE);
+    };
+    let today = Local::now().date_naive();
+    let age = last_entry_age(&data_ref, today).unwrap_or_default();
+    println!("Last entry: {} ({})", last.date, format_age(age));
+    println!("Weight: {}", app.format_weight(last.weight));
+    if let Some(change) = weekly_change(&app.stats_data()) {
+        println!("{}", app.localize(format!("7-day change: {:+.1}", change)));
     }
-
-    fn render_table(&mut self, area: Rect, frame: &mut Frame) {
-        let style = match self.current_frame {
-            FrameType::Table => Style::default(),
-            _ => Style::default().dark_gray(),
-        };
-        let table_block = Block::default().borders(Borders::ALL).style(style);
-        let widths = [Constraint::Length(12), Constraint::Length(7)];
-        let cloned_data = RefCell::clone(&self.data).into_inner();
-        let rows = cloned_data
-            .iter()
-            .map(|x| Row::new([center_text(x.0.clone()), center_text(format!("{:.1}", x.1))]));
-        let table = Table::new(rows, widths)
-            .header(
-                Row::new([
-                    center_text(String::from("Date")),
-                    center_text(String::from("Weight")),
-                ])
-                .bottom_margin(1)
-                .style(Style::default().on_blue().dark_gray()),
-            )
-            .block(table_block)
-            .row_highlight_style(Style::new().on_dark_gray().white())
-            .highlight_symbol("→");
-        frame.render_stateful_widget(table, area, &mut self.table_state);
+    if let Some(deviation) = volatility(&app.stats_data()) {
+        println!("{}", app.localize(format!("{}-day volatility: ±{:.1}", VOLATILITY_DAYS, deviation)));
     }
-
-    fn render_chart(&mut self, area: Rect, frame: &mut Frame) {
-        let style = match self.current_frame {
-            FrameType::Table => Style::default().dark_gray(),
-            _ => Style::default(),
-        };
-        match self.current_tf {
-            ChartTimeFrame::WindowYear => {
-                let date_right = self.selected_date_wy;
-                let date_left = date_right.checked_sub_months(Months::new(12)).unwrap();
-                let delta = (date_right - date_left).num_days() as f64;
-                let x_label = vec![
-                    Span::styled(
-                        format!("{}", date_left.format("%d-%m-%Y").to_string()),
-                        Style::default(),
-                    ),
-                    Span::styled(
-                        format!(
-                            "{}",
-                            date_left
-                                .checked_add_months(Months::new(4))
-                                .unwrap()
-                                .format("%d-%m-%Y")
-                                .to_string()
-                        ),
-                        Style::default(),
-                    ),
-                    Span::styled(
-                        format!(
-                            "{}",
-                            date_left
-                                .checked_add_months(Months::new(8))
-                                .unwrap()
-                                .format("%d-%m-%Y")
-                                .to_string()
-                        ),
-                        Style::default(),
-                    ),
-                    Span::styled(
-                        format!("{}", date_right.format("%d-%m-%Y").to_string()),
-                        Style::default(),
-                    ),
-                ];
-                let cloned_data = RefCell::clone(&self.data).into_inner();
-                let data_points = cloned_data
-                    .iter()
-                    .filter_map(|x| {
-                        let date_point =
-                            NaiveDate::parse_from_str(x.0.as_str(), "%d-%m-%Y").unwrap();
-                        let diff = (date_point - date_left).num_days() as f64;
-                        if diff >= 0f64 && diff <= delta {
-                            Some((diff, x.1.clone()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                let min_weight = if !data_points.is_empty() {
-                    data_points
-                        .iter()
-                        .fold(f64::MAX, |acc, x| x.1.clone().min(acc))
-                } else {
-                    0f64 + OFFSET_MIN
-                };
-                let max_weight = if !data_points.is_empty() {
-                    data_points.iter().fold(0f64, |acc, x| x.1.clone().max(acc))
-                } else {
-                    100f64 - OFFSET_MAX
-                };
-                let dataset = Dataset::default()
-                    .marker(Marker::Dot)
-                    .style(Style::new().blue())
-                    .graph_type(GraphType::Scatter)
-                    .data(&data_points);
-                let chart = Chart::new(vec![dataset])
-                    .block(
-                        Block::bordered()
-                            .title_top(Line::from("One Year Window").cyan().bold().centered())
-                            .style(style),
-                    )
-                    .x_axis(
-                        Axis::default()
-                            .style(Style::default().gray())
-                            .bounds([0.0, delta])
-                            .labels(x_label)
-                            .labels_alignment(Alignment::Right),
-                    )
-                    .y_axis(
-                        Axis::default()
-                            .style(Style::default().gray())
-                            .bounds([min_weight - OFFSET_MIN, max_weight + OFFSET_MAX])
-                            .labels([
-                                format!("{:.1}", min_weight - OFFSET_MIN).bold(),
-                                format!("{:.1}", max_weight + OFFSET_MAX).bold(),
-                            ]),
-                    )
-                    .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)));
-                frame.render_widget(chart, area);
-            }
-            ChartTimeFrame::Year => {
-                let y = self.selected_date_y.year_ce().1;
-                let date_left = NaiveDate::from_ymd_opt(y.try_into().unwrap(), 1, 1).unwrap();
-                let date_right = NaiveDate::from_ymd_opt(y.try_into().unwrap(), 12, 31).unwrap();
-                let delta = (date_right - date_left).num_days() as f64;
-                let x_label = vec![
-                    Span::styled(
-                        format!("{}", date_left.format("%b").to_string()),
-                        Style::default(),
-                    ),
-                    Span::styled(
-                        format!(
-                            "{}",
-                            date_left
-                                .checked_add_months(Months::new(4))
-                                .unwrap()
-                                .format("%b")
-                                .to_string()
-                        ),
-                        Style::default(),
-                    ),
-                    Span::styled(
-                        format!(
-                            "{}",
-                            date_left
-                                .checked_add_months(Months::new(8))
-                                .unwrap()
-                                .format("%b")
-                                .to_string()
-                        ),
-                        Style::default(),
-                    ),
-                    Span::styled(
-                        format!("{}", date_right.format("%b").to_string()),
-                        Style::default(),
-                    ),
-                ];
-                let cloned_data = RefCell::clone(&self.data).into_inner();
-                let data_points = cloned_data
-                    .iter()
-                    .filter_map(|x| {
-                        let date_point =
-                            NaiveDate::parse_from_str(x.0.as_str(), "%d-%m-%Y").unwrap();
-                        let diff = (date_point - date_left).num_days() as f64;
-                        if diff >= 0f64 && diff <= delta {
-                            Some((diff, x.1.clone()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                let min_weight = if !data_points.is_empty() {
-                    data_points
-                        .iter()
-                        .fold(f64::MAX, |acc, x| x.1.clone().min(acc))
-                } else {
-                    0f64 + OFFSET_MIN
-                };
-                let max_weight = if !data_points.is_empty() {
-                    data_points.iter().fold(0f64, |acc, x| x.1.clone().max(acc))
-                } else {
-                    100f64 - OFFSET_MAX
-                };
-                let dataset = Dataset::default()
-                    .marker(Marker::Dot)
-                    .style(Style::new().blue())
-                    .graph_type(GraphType::Scatter)
-                    .data(&data_points);
-                let chart = Chart::new(vec![dataset])
-                    .block(
-                        Block::bordered()
-                            .title_top(
-                                Line::from(self.selected_date_y.format("%Y").to_string())
-                                    .cyan()
-                                    .bold()
-                                    .centered(),
-                            )
-                            .style(style),
-                    )
-                    .x_axis(
-                        Axis::default()
-                            .style(Style::default().gray())
-                            .bounds([0.0, delta])
-                            .labels(x_label)
-                            .labels_alignment(Alignment::Right),
-                    )
-                    .y_axis(
-                        Axis::default()
-                            .style(Style::default().gray())
-                            .bounds([min_weight - OFFSET_MIN, max_weight + OFFSET_MAX])
-                            .labels([
-                                format!("{:.1}", min_weight - OFFSET_MIN).bold(),
-                                format!("{:.1}", max_weight + OFFSET_MAX).bold(),
-                            ]),
-                    )
-                    .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)));
-                frame.render_widget(chart, area);
-            }
-            ChartTimeFrame::Month => {
-                let y = self.selected_date_m.year_ce().1;
-                let m = self.selected_date_m.month();
-                let date_left = NaiveDate::from_ymd_opt(y.try_into().unwrap(), m, 1).unwrap();
-                let date_right = NaiveDate::from_ymd_opt(y.try_into().unwrap(), (m % 12) + 1, 1)
-                    .unwrap()
-                    .checked_sub_days(Days::new(1))
-                    .unwrap();
-                let delta = (date_right - date_left).num_days() as f64;
-                let x_label = vec![
-                    Span::styled(
-                        format!("{}", date_left.format("%d").to_string()),
-                        Style::default(),
-                    ),
-                    Span::styled(
-                        format!("{}", date_right.format("%d").to_string()),
-                        Style::default(),
-                    ),
-                ];
-                let cloned_data = RefCell::clone(&self.data).into_inner();
-                let data_points = cloned_data
-                    .iter()
-                    .filter_map(|x| {
-                        let date_point =
-                            NaiveDate::parse_from_str(x.0.as_str(), "%d-%m-%Y").unwrap();
-                        let diff = (date_point - date_left).num_days() as f64;
-                        if diff >= 0f64 && diff <= delta {
-                            Some((diff, x.1.clone()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                let min_weight = if !data_points.is_empty() {
-                    data_points
-                        .iter()
-                        .fold(f64::MAX, |acc, x| x.1.clone().min(acc))
-                } else {
-                    0f64 + OFFSET_MIN
-                };
-                let max_weight = if !data_points.is_empty() {
-                    data_points.iter().fold(0f64, |acc, x| x.1.clone().max(acc))
-                } else {
-                    100f64 - OFFSET_MAX
-                };
-                let dataset = Dataset::default()
-                    // .marker(Marker::HalfBlock)
-                    .marker(Marker::Dot)
-                    .style(Style::new().blue())
-                    // .graph_type(GraphType::Bar) // Bar is fucked on v0.29.0
-                    .graph_type(GraphType::Scatter)
-                    .data(&data_points);
-                let chart = Chart::new(vec![dataset])
-                    .block(
-                        Block::bordered()
-                            .title_top(
-                                Line::from(self.selected_date_m.format("%b %Y").to_string())
-                                    .cyan()
-                                    .bold()
-                                    .centered(),
-                            )
-                            .style(style),
-                    )
-                    .x_axis(
-                        Axis::default()
-                            .style(Style::default().gray())
-                            .bounds([0.0, delta])
-                            .labels(x_label)
-                            .labels_alignment(Alignment::Right),
-                    )
-                    .y_axis(
-                        Axis::default()
-                            .style(Style::default().gray())
-                            .bounds([min_weight - OFFSET_MIN, max_weight + OFFSET_MAX])
-                            // .bounds([0.0, max_weight + OFFSET_MAX])
-                            .labels([
-                                format!("{:.1}", min_weight - OFFSET_MIN).bold(),
-                                format!("{:.1}", max_weight + OFFSET_MAX).bold(),
-                            ]),
-                    );
-                frame.render_widget(chart, area);
-            }
-        };
+    println!("Streak: {}", schedule_streak(&data_ref, &app.schedule, today));
+    let today_text = today.format("%d-%m-%Y").to_string();
+    if app.reminder_due(today) && last.date != today_text {
+        println!("Reminder: weigh in today");
     }
-
-    fn render_message_box(&mut self, area: Rect, frame: &mut Frame) {
-        let title_block = Block::default()
-            .borders(Borders::ALL)
-            .style(Style::default());
-        if let Some((msg, msg_type)) = &self.message {
-            let msg_str = msg.as_str();
-            let style = match msg_type {
-                MessageType::Warning => {
-                    Style::default().fg(Color::LightYellow)
-                }
-                MessageType::Info => {
-                    Style::default().fg(Color::LightGreen)
-                }
-                MessageType::Error => {
-                    Style::default().fg(Color::LightRed)
-                }
-            };
-            let message = Paragraph::new(Span::styled(msg_str, style))
-                .centered()
-                .block(title_block);
-            frame.render_widget(message, area);
-            if let Some(msg_time_elapsed) = self.msg_time_elapsed {
-                if msg_time_elapsed.elapsed() >= MSG_TIMEOUT {
-                    self.rm_confirm = false;
-                    self.message = None;
-                    self.msg_time_elapsed = None;
-                }
-            } else {
-                self.msg_time_elapsed = Some(Instant::now());
-            }
-        } else {
-            let message = match self.current_window {
-                WindowType::ClosePopup => {
-                    String::from("Esc/n => back to main window | Enter/y => quit app")
-                }
-                WindowType::InputPopup => String::from(
-                    "Esc => go to main window | Tab => switch input box | Enter => submit form",
-                ),
-                WindowType::MainWindow => match self.current_frame {
-                    FrameType::Chart => String::from(
-                        "Esc/q: quit app | j/k: cycle chart | h/l: (-/+)x-axis",
-                    ),
-                    FrameType::Table => String::from(
-                        "Esc/q: quit app | a: add entry | e: edit selected row | j/k: (down/up) 1 row | d: delete 1 row",
-                    ),
-                },
-            };
-            let tick_count = 3;
-            let max_offset = (message.len() + 2).saturating_sub(usize::from(area.width));
-            self.scroll_offset = self.scroll_offset.clamp(0, max_offset * tick_count);
-            let message = if usize::from(area.width) >= message.len() + 2 {
-                self.wait_time_elapsed = None;
-                Paragraph::new(Span::styled(message, Style::default()))
-                    .centered()
-                    .block(title_block)
-            } else {
-                Paragraph::new(Span::styled(message, Style::default()))
-                    .scroll((
-                        0,
-                        (self.scroll_offset / tick_count)
-                            .clamp(0, usize::from(u16::MAX))
-                            .try_into()
-                            .unwrap(),
-                    ))
-                    .block(title_block)
-            };
-            if let Some(wait_time_elapsed) = self.wait_time_elapsed {
-                if wait_time_elapsed.elapsed() > Duration::from_secs(1) {
-                    self.wait_time_elapsed = None;
-                }
-            } else {
-                if self.reversed_offset {
-                    if self.scroll_offset > 0 {
-                        self.scroll_offset -= 1;
-                    } else {
-                        self.reversed_offset = false;
-                        if self.wait_time_elapsed == None {
-                            self.wait_time_elapsed = Some(Instant::now());
-                        }
-                    }
-                } else {
-                    if self.scroll_offset + 1 <= max_offset * tick_count {
-                        self.scroll_offset += 1;
-                    } else {
-                        self.scroll_offset = max_offset * tick_count;
-                        self.reversed_offset = true;
-                        if self.wait_time_elapsed == None {
-                            self.wait_time_elapsed = Some(Instant::now());
-                        }
-                    }
-                }
-            }
-            frame.render_widget(message, area);
+    if let Some(target) = app.config.goal.target {
+        println!("Goal: {}", app.format_weight(target));
+        if let Some(countdown) = app.goal_countdown() {
+            println!("To go: {}", app.countdown_text(&countdown));
         }
-    }
-
-    fn toggle_frame(&mut self) {
-        self.current_frame = match self.current_frame {
-            FrameType::Chart => FrameType::Table,
-            FrameType::Table => FrameType::Chart,
+        if let Some((scale, trend)) = goal_etas(&app.stats_data(), target, app.config.goal.trend_alpha) {
+            println!("Scale goal: {}", scale.describe());
+            println!("Trend goal: {}", trend.describe());
         }
     }
-
-    fn cycle_next_tf(&mut self) {
-        self.current_tf = match self.current_tf {
-            ChartTimeFrame::WindowYear => ChartTimeFrame::Month,
-            ChartTimeFrame::Year => ChartTimeFrame::WindowYear,
-            ChartTimeFrame::Month => ChartTimeFrame::Year,
-        };
+    if app.is_stale() {
+        println!(
+            "Warning: no entry for {} days (threshold {})",
+            age, app.config.staleness.days
+        );
+        return Ok(ExitCode::FAILURE);
     }
+    Ok(ExitCode::SUCCESS)
+}
 
-    fn cycle_prev_tf(&mut self) {
-        self.current_tf = match self.current_tf {
-            ChartTimeFrame::Month => ChartTimeFrame::WindowYear,
-            ChartTimeFrame::WindowYear => ChartTimeFrame::Year,
-            ChartTimeFrame::Year => ChartTimeFrame::Month,
-        };
+fn run_app(no_color: bool, staged: bool) -> Result<(), WtError> {
+    let mut app = open_app()?;
+    let csv = app.config.storage.backend == StorageBackend::Csv;
+    match app.load() {
+        // A CSV file that exists but can't be read gets the recovery screen
+        // rather than an error before the TUI starts.
+        Err(e @ (WtError::Parse { .. } | WtError::Storage(_))) if csv && Path::new(&app.path).exists() => {
+            warn!("cannot load {}: {}", app.storage.location(), e);
+            app.load_error = Some(e);
+        }
+        ret => ret?,
     }
-
-    fn handle_events(&mut self) -> io::Result<()> {
-        if let Event::Key(key) = event::read()? {
-            if key.kind == event::KeyEventKind::Release {
-                return Ok(());
-            }
-            match (key.modifiers, key.code) {
-                (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
-                    self.close = true;
+    if no_color {
+        app.theme = Theme::Monochrome;
+    }
+    app.table_state.select_last();
+    if !app.data.borrow().is_empty() || app.load_error.is_some() {
+        let week = week_label(Local::now().date_naive().iso_week());
+        match app.load_state() {
+            Ok(state) => {
+                if state.dismissed_week.as_deref() != Some(week.as_str()) && app.load_error.is_none() {
+                    app.current_window = WindowType::WeeklySummary;
                 }
-                (_, KeyCode::Esc) => match self.current_window {
-                    WindowType::MainWindow => {
-                        self.current_window = WindowType::ClosePopup;
-                        self.scroll_offset = 0;
-                    }
-                    _ => {
-                        self.current_window = WindowType::MainWindow;
-                        self.scroll_offset = 0;
-                    }
-                },
-                (_, KeyCode::Enter) => match self.current_window {
-                    WindowType::MainWindow => {}
-                    WindowType::ClosePopup => self.close = true,
-                    WindowType::InputPopup => {
-                        let (date, weight) = (
-                            self.text_area[0].lines()[0].clone(),
-                            self.text_area[1].lines()[0].parse::<f64>(),
-                        );
-                        let date_is_valid = if let Ok(_) =
-                            NaiveDate::parse_from_str(date.clone().as_str(), "%d-%m-%Y")
-                        {
-                            true
-                        } else {
-                            false
-                        };
-                        let weight_is_valid = if let Ok(w) = weight { w > 0f64 } else { false };
-                        if date_is_valid && weight_is_valid {
-                            if self.modify_data((date, Some(weight.unwrap()))) {
-                                self.current_window = WindowType::MainWindow;
-                                self.scroll_offset = 0;
-                                self.table_state.select_last();
-                                self.text_mode = None;
-                            }
-                        } else if date_is_valid {
-                            self.message = Some((String::from("Invalid weight format!"), MessageType::Error));
-                        } else if weight_is_valid {
-                            self.message = Some((String::from("Invalid date format!"), MessageType::Error));
-                        } else {
-                            self.message = Some((String::from("Invalid weight & date format!"), MessageType::Error));
-                        }
-                    }
-                },
-                (_, KeyCode::Tab) => match self.current_window {
-                    WindowType::MainWindow => self.toggle_frame(),
-                    WindowType::InputPopup => match self.text_mode {
-                        Some(TextMode::Append) => self.selected_area = (self.selected_area + 1) % 2,
-                        _ => {}
-                    },
-                    _ => {}
-                },
-                (_, KeyCode::Backspace) => match self.current_window {
-                    WindowType::InputPopup => {
-                        let _ = self.text_area[self.selected_area].delete_char();
-                    }
-                    _ => {}
-                },
-                (_, KeyCode::Char(ch)) => {
-                    // Local key-binds
-                    match self.current_window {
-                        WindowType::MainWindow => {
-                            if self.current_frame == FrameType::Table {
-                                match ch {
-                                    'q' => {
-                                        self.current_window = WindowType::ClosePopup;
-                                        self.scroll_offset = 0;
-                                    }
-                                    'k' => self.table_state.select_previous(),
-                                    'j' => self.table_state.select_next(),
-                                    'a' => {
-                                        self.current_window = WindowType::InputPopup;
-                                        self.scroll_offset = 0;
-                                        self.text_mode = Some(TextMode::Append);
-                                        self.init_text_area();
-                                    }
-                                    'e' => {
-                                        self.current_window = WindowType::InputPopup;
-                                        self.scroll_offset = 0;
-                                        self.text_mode = Some(TextMode::Edit);
-                                        self.init_text_area();
-                                    }
-                                    'd' => {
-                                        if self.rm_confirm {
-                                            let idx = self.table_state.selected_mut();
-                                            if let None = idx {
-                                                return Err(Error::other("No row is selected."));
-                                            }
-                                            let idx = idx.unwrap();
-                                            let data_ref = self.data.get_mut();
-                                            let selected = data_ref[idx].clone();
-                                            self.modify_data((selected.0, None));
-                                        } else {
-                                            self.rm_confirm = true;
-                                            self.message = Some((String::from("Press 'd' again to confirm deletion"), MessageType::Warning));
-                                        }
-                                    }
-                                    _ => {}
-                                };
-                            } else if self.current_frame == FrameType::Chart {
-                                match ch {
-                                    'q' => {
-                                        self.current_window = WindowType::ClosePopup;
-                                        self.scroll_offset = 0;
-                                    }
-                                    'k' => self.cycle_prev_tf(),
-                                    'j' => self.cycle_next_tf(),
-                                    'h' => match self.current_tf {
-                                        ChartTimeFrame::Month => {
-                                            self.selected_date_m = self
-                                                .selected_date_m
-                                                .checked_sub_months(Months::new(1))
-                                                .unwrap()
-                                        }
-                                        ChartTimeFrame::Year => {
-                                            self.selected_date_y = self
-                                                .selected_date_y
-                                                .checked_sub_months(Months::new(12))
-                                                .unwrap()
-                                        }
-                                        ChartTimeFrame::WindowYear => {
-                                            self.selected_date_wy = self
-                                                .selected_date_wy
-                                                .checked_sub_days(Days::new(1))
-                                                .unwrap()
-                                        }
-                                    },
-                                    'l' => match self.current_tf {
-                                        ChartTimeFrame::Month => {
-                                            self.selected_date_m = self
-                                                .selected_date_m
-                                                .checked_add_months(Months::new(1))
-                                                .unwrap()
-                                        }
-                                        ChartTimeFrame::Year => {
-                                            self.selected_date_y = self
-                                                .selected_date_y
-                                                .checked_add_months(Months::new(12))
-                                                .unwrap()
-                                        }
-                                        ChartTimeFrame::WindowYear => {
-                                            self.selected_date_wy = self
-                                                .selected_date_wy
-                                                .checked_add_days(Days::new(1))
-                                                .unwrap()
-                                        }
-                                    },
-                                    _ => {}
-                                };
-                            }
-                        }
-                        WindowType::ClosePopup => match ch {
-                            'y' => self.close = true,
-                            'n' => {
-                                self.current_window = WindowType::MainWindow;
-                                self.scroll_offset = 0;
-                            }
-                            _ => {}
-                        },
-                        WindowType::InputPopup => {
-                            let input: Input = Event::Key(key).into();
-                            if self.text_area[self.selected_area].input(input) {
-                                self.activate_text();
-                            }
-                        }
-                    }
+                app.achievements = state.achievements;
+                if let Some(view) = &state.view {
+                    app.restore_view(view);
                 }
-                _ => {}
+                app.trash = state.trash;
             }
+            Err(e) => app.show_error(e),
         }
-        return Ok(());
     }
+    app.lock();
+    app.purge_trash();
+    app.archive_challenges();
+    if staged {
+        app.start_staging();
+    }
+    if app.is_stale() {
+        let age = last_entry_age(&app.data.borrow(), Local::now().date_naive()).unwrap_or_default();
+        app.messages.push(
+            format!("Last entry is {} days old, time to weigh in!", age),
+            MessageType::Warning,
+        );
+    }
+    if let Some((msg, msg_type)) = app.trend_alert() {
+        app.messages.push(msg, msg_type);
+    }
+    let mut term = ratatui::init();
+    // Without bracketed paste, pasted text still arrives key by key.
+    if let Err(e) = execute!(io::stdout(), EnableBracketedPaste) {
+        warn!("cannot enable bracketed paste: {}", e);
+    }
+    let ret = app.run(&mut term);
+    if let Err(e) = execute!(io::stdout(), DisableBracketedPaste) {
+        warn!("cannot disable bracketed paste: {}", e);
+    }
+    // Reported after saving, so that a terminal error doesn't lose the data.
+    let restored = ratatui::try_restore();
+    // Quit on the recovery screen: the data file is left untouched.
+    if let Some(e) = app.load_error.take() {
+        return Err(e);
+    }
+    // Staged changes that were never committed are dropped.
+    if app.staging.is_some() && app.is_modified() {
+        app.abort_staged();
+    }
+    let merge = app.save()?;
+    ret?;
+    restored?;
+    print_messages(&mut app);
+    app.save_view()?;
+    if let Some(merge) = merge {
+        println!("{}", merge_message(&merge));
+    }
+    print!("{}", app.session_summary());
+    Ok(())
 }
-
-const OFFSET_MIN: f64 = 2.0;
-const OFFSET_MAX: f64 = 2.0;
-const MSG_TIMEOUT: Duration = Duration::from_secs(3);
-const MIN_WIDTH: u16 = 60u16;
-const MIN_HEIGHT: u16 = 20u16;
@@ -0,0 +1,176 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::info;
+
+use crate::WtError;
+use crate::config::{RemoteConfig, RemoteKind};
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Uploads `body` as `name` to the configured WebDAV directory or S3 bucket
+/// and returns the URL it was written to.
+pub fn upload(config: &RemoteConfig, name: &str, body: &[u8]) -> Result<String, WtError> {
+    let base = config.url.trim_end_matches('/');
+    let url = match config.kind {
+        RemoteKind::Webdav => format!("{}/{}", base, uri_encode(name)),
+        RemoteKind::S3 => format!("{}/{}/{}", base, uri_encode(&config.bucket), uri_encode(name)),
+    };
+    let agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .new_agent();
+    let mut request = agent.put(&url);
+    match config.kind {
+        RemoteKind::Webdav => {
+            if let Some(user) = &config.username {
+                let credentials = format!("{}:{}", user, config.password.as_deref().unwrap_or(""));
+                request = request.header("Authorization", format!("Basic {}", STANDARD.encode(credentials)));
+            }
+        }
+        RemoteKind::S3 => {
+            for (header, value) in s3_headers(config, &url, body, Utc::now())? {
+                request = request.header(header, value);
+            }
+        }
+    }
+    info!("uploading {} bytes to {}", body.len(), url);
+    request
+        .send(body)
+        .map_err(|e| WtError::Storage(format!("cannot upload to {}: {}", url, e)))?;
+    Ok(url)
+}
+
+/// Percent-encodes everything but the unreserved characters, as required for
+/// the canonical request of AWS signatures.
+fn uri_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Key for AWS Signature Version 4, derived from the secret key and scope.
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+/// Headers signing a path-style `PUT` of `body` to `url` with AWS Signature
+/// Version 4.
+fn s3_headers(
+    config: &RemoteConfig,
+    url: &str,
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> Result<Vec<(&'static str, String)>, WtError> {
+    let (Some(access_key), Some(secret_key)) = (&config.access_key, &config.secret_key) else {
+        return Err(WtError::Config(String::from("remote access_key and secret_key are required for s3")));
+    };
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload = hex(&Sha256::digest(body));
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical = format!(
+        "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        path, host, payload, amz_date, signed_headers, payload
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical.as_bytes()))
+    );
+    let key = signing_key(secret_key, &date, &config.region, "s3");
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key,
+        scope,
+        signed_headers,
+        hex(&hmac(&key, &to_sign))
+    );
+    Ok(vec![
+        ("x-amz-date", amz_date),
+        ("x-amz-content-sha256", payload),
+        ("Authorization", authorization),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    #[test]
+    fn signing_key_matches_aws_example() {
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex(&key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+        assert_eq!(uri_encode("data 1.csv"), "data%201.csv");
+    }
+
+    #[test]
+    fn webdav_upload_puts_the_file_with_basic_auth() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                head.push(line.trim().to_string());
+            }
+            let length = head
+                .iter()
+                .find_map(|x| x.to_lowercase().strip_prefix("content-length: ")?.parse::<usize>().ok())
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            (head, body)
+        });
+        let config = RemoteConfig {
+            kind: RemoteKind::Webdav,
+            url: format!("http://{}/backups/", addr),
+            username: Some(String::from("me")),
+            password: Some(String::from("secret")),
+            ..RemoteConfig::default()
+        };
+        let url = upload(&config, "data.csv", b"01-05-2024, 80.0\n").unwrap();
+        assert_eq!(url, format!("http://{}/backups/data.csv", addr));
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "PUT /backups/data.csv HTTP/1.1");
+        assert!(head.iter().any(|x| x == "authorization: Basic bWU6c2VjcmV0"));
+        assert_eq!(body, b"01-05-2024, 80.0\n");
+    }
+}
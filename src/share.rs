@@ -0,0 +1,101 @@
+//! Square progress image for sharing: the weight curve and a caption such as
+//! "−7.5 kg in 120 days", drawn as SVG.
+
+use chrono::NaiveDate;
+
+use crate::data::{Unit, WEIGHT};
+
+/// Width and height of the image in pixels.
+pub const SHARE_SIZE: f64 = 1080.0;
+
+/// Space between the border of the image and the plot.
+const MARGIN: f64 = 90.0;
+
+/// Height taken by the caption above the plot.
+const CAPTION_HEIGHT: f64 = 220.0;
+
+/// "−7.5 kg in 120 days", with a real minus sign.
+pub fn progress_caption(change: f64, days: i64, unit: Unit) -> String {
+    let sign = if change < 0.0 { "−" } else { "+" };
+    let noun = if days == 1 { "day" } else { "days" };
+    format!("{}{} {} in {} {}", sign, WEIGHT.format(change.abs()), unit.name(), days, noun)
+}
+
+/// The share image of `points`, sorted by date, or `None` for fewer than two
+/// points. Weights are only labelled if `show_weights` is set; otherwise the
+/// image reveals the change but not where it started.
+pub fn render_share_svg(points: &[(NaiveDate, f64)], unit: Unit, show_weights: bool) -> Option<String> {
+    let ((first_date, first), (last_date, last)) = (*points.first()?, *points.last()?);
+    let days = (last_date - first_date).num_days();
+    if points.len() < 2 || days <= 0 {
+        return None;
+    }
+    let min = points.iter().map(|x| x.1).fold(f64::INFINITY, f64::min);
+    let max = points.iter().map(|x| x.1).fold(f64::NEG_INFINITY, f64::max);
+    let span = (max - min).max(1.0);
+    let (left, right) = (MARGIN, SHARE_SIZE - MARGIN);
+    let (top, bottom) = (MARGIN + CAPTION_HEIGHT, SHARE_SIZE - MARGIN - 40.0);
+    let x = |date: NaiveDate| left + (date - first_date).num_days() as f64 / days as f64 * (right - left);
+    let y = |weight: f64| bottom - (weight - min) / span * (bottom - top);
+    let path = points
+        .iter()
+        .map(|(date, weight)| format!("{:.1},{:.1}", x(*date), y(*weight)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let color = if last <= first { "#2e9e5b" } else { "#d9822b" };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" viewBox=\"0 0 {size} {size}\">\n",
+        size = SHARE_SIZE
+    );
+    svg += &format!("<rect width=\"{0}\" height=\"{0}\" fill=\"#fafafa\"/>\n", SHARE_SIZE);
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"96\" font-weight=\"bold\" text-anchor=\"middle\" fill=\"{}\">{}</text>\n",
+        SHARE_SIZE / 2.0,
+        MARGIN + 110.0,
+        color,
+        progress_caption(last - first, days, unit)
+    );
+    svg += &format!(
+        "<line x1=\"{left}\" y1=\"{bottom}\" x2=\"{right}\" y2=\"{bottom}\" stroke=\"#bbbbbb\" stroke-width=\"2\"/>\n"
+    );
+    svg += &format!(
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"6\" stroke-linejoin=\"round\"/>\n",
+        path, color
+    );
+    let label = |x: f64, y: f64, anchor: &str, text: &str| {
+        format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-size=\"32\" text-anchor=\"{}\" fill=\"#555555\">{}</text>\n",
+            x, y, anchor, text
+        )
+    };
+    svg += &label(left, bottom + 48.0, "start", &first_date.format("%d-%m-%Y").to_string());
+    svg += &label(right, bottom + 48.0, "end", &last_date.format("%d-%m-%Y").to_string());
+    if show_weights {
+        let weight = |w: f64| format!("{} {}", WEIGHT.format(w), unit.name());
+        svg += &label(x(first_date) + 12.0, y(first) - 18.0, "start", &weight(first));
+        svg += &label(x(last_date) - 12.0, y(last) - 18.0, "end", &weight(last));
+    }
+    svg += "</svg>\n";
+    Some(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_image_hides_weights_unless_asked() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let points = [(day(1), 87.5), (day(10), 84.1), (day(21), 80.0)];
+        assert_eq!(progress_caption(-7.5, 20, Unit::Kg), "−7.5 kg in 20 days");
+        assert_eq!(progress_caption(1.0, 1, Unit::Lb), "+1.0 lb in 1 day");
+        let svg = render_share_svg(&points, Unit::Kg, false).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("−7.5 kg in 20 days"));
+        assert!(!svg.contains("87.5") && !svg.contains("80.0 kg"));
+        let svg = render_share_svg(&points, Unit::Kg, true).unwrap();
+        assert!(svg.contains("87.5 kg") && svg.contains("80.0 kg"));
+        assert!(render_share_svg(&points[..1], Unit::Kg, false).is_none());
+    }
+}
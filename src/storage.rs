@@ -0,0 +1,756 @@
+use chrono::{DateTime, Local, NaiveDate};
+use directories::BaseDirs;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    time::SystemTime,
+};
+use tracing::{debug, info};
+
+use crate::WtError;
+use crate::data::{
+    Entry, FIELD_ID, FLAG_DELETED, MergeReport, Unit, find_entry, format_modified, merge_entries, split_tombstones,
+};
+
+/// A single change to the entries, as recorded by `Storage::append`.
+#[derive(Clone, PartialEq)]
+pub enum Change {
+    Set(Entry),
+    Delete(String),
+}
+
+impl Change {
+    /// The change that brings the entry dated `date` to its state in `data`.
+    pub fn of(data: &[Entry], date: &str) -> Change {
+        match data.iter().find(|x| x.date == date) {
+            Some(e) => Change::Set(e.clone()),
+            None => Change::Delete(date.to_string()),
+        }
+    }
+
+    pub fn date(&self) -> &str {
+        match self {
+            Change::Set(e) => &e.date,
+            Change::Delete(date) => date,
+        }
+    }
+
+    /// Journal line: `set, <date>, <weight>[, <flags>]` or `del, <date>`.
+    fn to_line(&self) -> String {
+        match self {
+            Change::Set(e) => format!("set, {}", e.to_fields()),
+            Change::Delete(date) => format!("del, {}", date),
+        }
+    }
+
+    /// Parses a journal line. Returns `None` if the line is malformed.
+    pub fn parse(line: &str) -> Option<Change> {
+        let fields = line.split(',').map(|x| x.trim()).collect::<Vec<_>>();
+        NaiveDate::parse_from_str(fields.get(1)?, "%d-%m-%Y").ok()?;
+        match fields[0] {
+            "del" => Some(Change::Delete(fields[1].to_string())),
+            "set" => {
+                let weight = fields.get(2)?.parse::<f64>().ok()?;
+                let mut entry = Entry::new(fields[1].to_string(), weight);
+                entry.set_flags(&fields[3..]);
+                Some(Change::Set(entry))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, data: &mut Vec<Entry>) {
+        let Ok(date) = NaiveDate::parse_from_str(self.date(), "%d-%m-%Y") else {
+            return;
+        };
+        match (self, find_entry(data, date)) {
+            (Change::Delete(_), Ok(idx)) => {
+                data.remove(idx);
+            }
+            (Change::Delete(_), Err(_)) => {}
+            (Change::Set(entry), Ok(idx)) => data[idx] = entry.clone(),
+            (Change::Set(entry), Err(idx)) => data.insert(idx, entry.clone()),
+        }
+    }
+}
+
+/// Entries read by `Storage::load`.
+pub struct Loaded {
+    /// Entries as of the last save.
+    pub saved: Vec<Entry>,
+    /// Changes appended since the last save by a session that did not exit
+    /// cleanly.
+    pub pending: Vec<Change>,
+    /// Appended changes that could not be read back.
+    pub corrupt: usize,
+    /// Format version the data file was upgraded from, and where the
+    /// original was backed up to.
+    pub migrated: Option<(u32, String)>,
+}
+
+/// Where the entries are persisted. `App` only talks to this trait, so the
+/// backend is chosen by configuration and can be replaced in tests.
+pub trait Storage {
+    /// Human-readable location, used in messages.
+    fn location(&self) -> String;
+    fn load(&mut self) -> Result<Loaded, WtError>;
+    /// Replaces the stored entries with `data`. `base` is what was loaded or
+    /// last saved; backends that can be changed behind our back merge those
+    /// changes into `data` first and report it.
+    fn save(&mut self, data: &mut Vec<Entry>, base: &[Entry]) -> Result<Option<MergeReport>, WtError>;
+    /// Durably records changes made since the last save.
+    fn append(&mut self, changes: &[Change]) -> Result<(), WtError>;
+    /// Copies the stored entries to a new backup and returns its location.
+    fn backup(&mut self) -> Result<String, WtError>;
+}
+
+/// CSV data file, gzip-compressed above a size threshold, with a journal
+/// file next to it for appended changes.
+pub struct CsvStorage {
+    pub path: String,
+    pub compress_threshold: u64,
+    /// Modification time of the data file when it was last read or written.
+    pub disk_mtime: Option<SystemTime>,
+}
+
+impl CsvStorage {
+    pub fn new(path: String, compress_threshold: u64) -> Self {
+        CsvStorage {
+            path,
+            compress_threshold,
+            disk_mtime: None,
+        }
+    }
+
+    fn journal_path(&self) -> String {
+        format!("{}.journal", self.path)
+    }
+
+    fn mtime(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Writes the CSV content `buffer`, compressed if it exceeds the threshold.
+    fn write(&self, buffer: &[u8]) -> Result<(), WtError> {
+        let mut out_file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(&self.path)?;
+        let threshold = self.compress_threshold;
+        debug!("writing {} bytes to {}", buffer.len(), self.path);
+        if threshold > 0 && buffer.len() as u64 > threshold {
+            let mut encoder = GzEncoder::new(&mut out_file, Compression::default());
+            encoder.write_all(buffer)?;
+            encoder.finish()?;
+        } else {
+            out_file.write_all(buffer)?;
+        }
+        out_file.sync_all()?;
+        Ok(())
+    }
+
+    /// Upgrades a data file written in an older format to `FORMAT_VERSION`,
+    /// backing it up first. Returns the version it had and the backup.
+    fn migrate(&mut self) -> Result<Option<(u32, String)>, WtError> {
+        if !fs::exists(&self.path)? {
+            return Ok(None);
+        }
+        let text = decode_data(fs::read(&self.path)?)?;
+        let mut lines = text.lines().map(String::from).collect::<Vec<_>>();
+        let Some(header) = lines.first() else {
+            return Ok(None);
+        };
+        let version = data_version(header)?;
+        if version > FORMAT_VERSION {
+            return Err(WtError::Storage(format!(
+                "{} has format version {}, this version of weight-tracker reads up to {}",
+                self.path, version, FORMAT_VERSION
+            )));
+        }
+        if version == FORMAT_VERSION {
+            return Ok(None);
+        }
+        // Only a readable file is migrated; anything else is left as it is
+        // for the recovery screen.
+        parse_data(&text, &self.path)?;
+        let backup = self.backup()?;
+        info!("backed up {} to {} before migrating", self.path, backup);
+        for (from, (name, migration)) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+            info!("migrating {} from format {}: {}", self.path, from + 1, name);
+            migration(&mut lines[1..]);
+        }
+        lines[0] = data_header();
+        self.write((lines.join("\n") + "\n").as_bytes())?;
+        Ok(Some((version, backup)))
+    }
+}
+
+impl Storage for CsvStorage {
+    fn location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn load(&mut self) -> Result<Loaded, WtError> {
+        let migrated = self.migrate()?;
+        let saved = read_data_file(&self.path)?;
+        self.disk_mtime = self.mtime();
+        let (mut pending, mut corrupt) = (Vec::new(), 0);
+        if fs::exists(self.journal_path())? {
+            let text = fs::read_to_string(self.journal_path())?;
+            for line in text.lines().filter(|x| !x.trim().is_empty()) {
+                match Change::parse(line) {
+                    Some(change) => pending.push(change),
+                    None => corrupt += 1,
+                }
+            }
+        }
+        Ok(Loaded {
+            saved,
+            pending,
+            corrupt,
+            migrated,
+        })
+    }
+
+    fn save(&mut self, data: &mut Vec<Entry>, base: &[Entry]) -> Result<Option<MergeReport>, WtError> {
+        let mtime = self.mtime();
+        let mut merge = None;
+        if mtime.is_some() && mtime != self.disk_mtime {
+            let theirs = read_data_file(&self.path)?;
+            let (merged, report) = merge_entries(base, data, &theirs);
+            info!("data file changed on disk, merge: {:?}", report);
+            *data = merged;
+            merge = Some(report);
+        }
+        let mut buffer = Vec::new();
+        write_csv(data, &mut buffer)?;
+        self.write(&buffer)?;
+        self.disk_mtime = self.mtime();
+        if fs::exists(self.journal_path())? {
+            fs::remove_file(self.journal_path())?;
+        }
+        Ok(merge)
+    }
+
+    fn append(&mut self, changes: &[Change]) -> Result<(), WtError> {
+        let lines = changes.iter().map(|x| x.to_line() + "\n").collect::<String>();
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(self.journal_path())
+            .and_then(|mut file| {
+                file.write_all(lines.as_bytes())?;
+                file.sync_data()
+            })
+            .map_err(|e| WtError::Storage(format!("journal write failed: {}", e)))
+    }
+
+    fn backup(&mut self) -> Result<String, WtError> {
+        let dest = format!("{}.{}.bak", self.path, Local::now().format(BACKUP_TIMESTAMP));
+        fs::copy(&self.path, &dest)?;
+        Ok(dest)
+    }
+}
+
+/// SQLite database with one row per entry. Appended changes go straight into
+/// the database, so there is never anything left to replay.
+pub struct SqliteStorage {
+    pub path: String,
+    pub conn: rusqlite::Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(path: String) -> Result<Self, WtError> {
+        let conn = rusqlite::Connection::open(&path).map_err(sqlite_error)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS entries (
+                date TEXT PRIMARY KEY,
+                weight REAL NOT NULL,
+                excluded INTEGER NOT NULL DEFAULT 0,
+                rhr REAL,
+                estimated INTEGER NOT NULL DEFAULT 0,
+                source TEXT,
+                device TEXT,
+                unit TEXT,
+                id TEXT,
+                modified TEXT
+            )",
+            (),
+        )
+        .map_err(sqlite_error)?;
+        // Databases created by older versions lack the newer columns.
+        let columns = [
+            ("rhr", "REAL"),
+            ("estimated", "INTEGER NOT NULL DEFAULT 0"),
+            ("source", "TEXT"),
+            ("unit", "TEXT"),
+            ("id", "TEXT"),
+            ("modified", "TEXT"),
+            ("device", "TEXT"),
+        ];
+        for (column, definition) in columns {
+            if conn.prepare(&format!("SELECT {} FROM entries LIMIT 0", column)).is_err() {
+                conn.execute(&format!("ALTER TABLE entries ADD COLUMN {} {}", column, definition), ())
+                    .map_err(sqlite_error)?;
+            }
+        }
+        Ok(SqliteStorage { path, conn })
+    }
+
+    fn write_change(conn: &rusqlite::Connection, change: &Change) -> rusqlite::Result<usize> {
+        // Dates are stored as ISO 8601 so that they sort chronologically.
+        let iso = |date: &str| {
+            NaiveDate::parse_from_str(date, "%d-%m-%Y")
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default()
+        };
+        match change {
+            Change::Set(e) => conn.execute(
+                "INSERT OR REPLACE INTO entries
+                 (date, weight, excluded, rhr, estimated, source, unit, id, modified, device)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                (
+                    iso(&e.date),
+                    e.original.map_or(e.weight, |(_, w)| w),
+                    e.excluded,
+                    e.rhr,
+                    e.estimated,
+                    &e.source,
+                    e.original.map(|(unit, _)| unit.name()),
+                    &e.id,
+                    e.modified.map(format_modified),
+                    &e.device,
+                ),
+            ),
+            Change::Delete(date) => conn.execute("DELETE FROM entries WHERE date = ?1", [iso(date)]),
+        }
+    }
+}
+
+fn sqlite_error(e: rusqlite::Error) -> WtError {
+    WtError::Storage(e.to_string())
+}
+
+impl Storage for SqliteStorage {
+    fn location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn load(&mut self) -> Result<Loaded, WtError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT date, weight, excluded, rhr, estimated, source, unit, id, modified, device
+                 FROM entries ORDER BY date",
+            )
+            .map_err(sqlite_error)?;
+        let rows = stmt
+            .query_map((), |row| {
+                let mut entry = Entry::new(row.get(0)?, row.get(1)?);
+                entry.excluded = row.get(2)?;
+                entry.rhr = row.get(3)?;
+                entry.estimated = row.get(4)?;
+                entry.source = row.get(5)?;
+                let unit = row.get::<_, Option<String>>(6)?;
+                entry.original = unit.as_deref().and_then(Unit::parse).map(|u| (u, entry.weight));
+                entry.id = row.get(7)?;
+                let modified = row.get::<_, Option<String>>(8)?;
+                entry.modified = modified.and_then(|x| DateTime::parse_from_rfc3339(&x).ok()).map(|x| x.to_utc());
+                entry.device = row.get(9)?;
+                Ok(entry)
+            })
+            .map_err(sqlite_error)?;
+        let mut saved = Vec::new();
+        for entry in rows {
+            let mut entry = entry.map_err(sqlite_error)?;
+            let date = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d")
+                .map_err(|e| WtError::Storage(format!("invalid date '{}': {}", entry.date, e)))?;
+            entry.date = date.format("%d-%m-%Y").to_string();
+            saved.push(entry);
+        }
+        Ok(Loaded {
+            saved,
+            pending: Vec::new(),
+            corrupt: 0,
+            migrated: None,
+        })
+    }
+
+    fn save(&mut self, data: &mut Vec<Entry>, _base: &[Entry]) -> Result<Option<MergeReport>, WtError> {
+        let tx = self.conn.transaction().map_err(sqlite_error)?;
+        tx.execute("DELETE FROM entries", ()).map_err(sqlite_error)?;
+        // The database is never changed behind our back, so it has no merges
+        // that would need the tombstones.
+        for entry in data.iter().filter(|x| !x.deleted) {
+            Self::write_change(&tx, &Change::Set(entry.clone())).map_err(sqlite_error)?;
+        }
+        tx.commit().map_err(sqlite_error)?;
+        Ok(None)
+    }
+
+    fn append(&mut self, changes: &[Change]) -> Result<(), WtError> {
+        let tx = self.conn.transaction().map_err(sqlite_error)?;
+        for change in changes {
+            Self::write_change(&tx, change).map_err(sqlite_error)?;
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+
+    fn backup(&mut self) -> Result<String, WtError> {
+        let dest = format!("{}.{}.bak", self.path, Local::now().format(BACKUP_TIMESTAMP));
+        self.conn.execute("VACUUM INTO ?1", [&dest]).map_err(sqlite_error)?;
+        Ok(dest)
+    }
+}
+
+/// Keeps the entries in memory. Stands in until the configured backend is
+/// opened, and in tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    pub saved: Vec<Entry>,
+    pub appended: Vec<Change>,
+    pub backups: Vec<Vec<Entry>>,
+}
+
+impl Storage for MemoryStorage {
+    fn location(&self) -> String {
+        String::from("memory")
+    }
+
+    fn load(&mut self) -> Result<Loaded, WtError> {
+        Ok(Loaded {
+            saved: self.saved.clone(),
+            pending: self.appended.clone(),
+            corrupt: 0,
+            migrated: None,
+        })
+    }
+
+    fn save(&mut self, data: &mut Vec<Entry>, _base: &[Entry]) -> Result<Option<MergeReport>, WtError> {
+        self.saved = data.clone();
+        self.appended.clear();
+        Ok(None)
+    }
+
+    fn append(&mut self, changes: &[Change]) -> Result<(), WtError> {
+        self.appended.extend_from_slice(changes);
+        Ok(())
+    }
+
+    fn backup(&mut self) -> Result<String, WtError> {
+        self.backups.push(self.saved.clone());
+        Ok(format!("memory backup {}", self.backups.len()))
+    }
+}
+
+/// Header line of the data file, stamped with the format version.
+fn data_header() -> String {
+    format!("Date, Weight, Flags, {}{}", FIELD_VERSION, FORMAT_VERSION)
+}
+
+/// Format version stamped in a data file `header`. Files from before the
+/// stamp are version 1; versions start at 1, so a lower stamp is corrupt.
+fn data_version(header: &str) -> Result<u32, WtError> {
+    let version = header
+        .split(',')
+        .find_map(|x| x.trim().strip_prefix(FIELD_VERSION)?.parse().ok())
+        .unwrap_or(1);
+    if version < 1 {
+        return Err(WtError::Parse {
+            line: 1,
+            reason: format!("invalid format version {}", version),
+        });
+    }
+    Ok(version)
+}
+
+/// Version 2 gives every entry a stable id.
+fn migrate_ids(lines: &mut [String]) {
+    for line in lines.iter_mut().filter(|x| !x.trim().is_empty()) {
+        if !line.split(',').any(|x| x.trim().starts_with(FIELD_ID)) {
+            line.push_str(&format!(", {}{}", FIELD_ID, uuid::Uuid::new_v4()));
+        }
+    }
+}
+
+/// Version 3 adds tombstone rows for deleted entries; older files have none.
+fn migrate_tombstones(_: &mut [String]) {}
+
+/// Writes `data` in the data file's CSV format.
+pub fn write_csv(data: &[Entry], file: &mut impl Write) -> io::Result<()> {
+    writeln!(file, "{}", data_header())?;
+    for entry in data {
+        writeln!(file, "{}", entry.to_fields())?;
+    }
+    Ok(())
+}
+
+/// Reads the entries of a data file written by `write_csv`. A missing file
+/// has no entries.
+fn read_data_file(path: &str) -> Result<Vec<Entry>, WtError> {
+    let file = File::open(path);
+    if file.is_err() {
+        // Do nothing in case of file does not exist
+        return Ok(Vec::new());
+    }
+    let mut file = file.unwrap();
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    parse_data(&decode_data(bytes)?, path)
+}
+
+/// Parses the decoded content `text` of the data file at `path`, which is
+/// only used in errors.
+fn parse_data(text: &str, path: &str) -> Result<Vec<Entry>, WtError> {
+    let mut rows = text.lines().enumerate().filter(|(_, x)| !x.trim().is_empty()).map(|(i, x)| {
+        let fields = x.trim().split(',').map(str::trim).filter(|x| !x.is_empty()).collect::<Vec<_>>();
+        (i + 1, fields)
+    });
+    let Some((_, header)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let invalid_header = WtError::Parse {
+        line: 1,
+        reason: format!("invalid header in {}", path),
+    };
+    if !(2..=4).contains(&header.len()) {
+        return Err(invalid_header);
+    }
+    if header[0] != "Date" || header[1] != "Weight" {
+        return Err(invalid_header);
+    }
+    // A bad row is an error rather than skipped, so that saving doesn't
+    // drop it from the file and a bad date can't reach the rest of the app.
+    rows.map(|(line, x)| {
+        let parse = |reason: String| WtError::Parse { line, reason };
+        let date = x.first().copied().unwrap_or_default();
+        if NaiveDate::parse_from_str(date, "%d-%m-%Y").is_err() {
+            return Err(parse(format!("invalid date '{}' in {}", date, path)));
+        }
+        let weight = x.get(1).ok_or_else(|| parse(format!("missing weight in {}", path)))?;
+        let deleted = *weight == FLAG_DELETED;
+        let weight = match deleted {
+            true => 0.0,
+            false => weight
+                .parse::<f64>()
+                .map_err(|_| parse(format!("invalid weight '{}' in {}", weight, path)))?,
+        };
+        let mut entry = Entry::new(String::from(date), weight);
+        entry.deleted = deleted;
+        entry.set_flags(&x[2..]);
+        Ok(entry)
+    })
+    .collect()
+}
+
+/// Entries of a data file, backup or export as stored, without touching it
+/// or its journal, and without tombstones. CSV files may be compressed;
+/// SQLite databases are recognized by their header.
+pub fn read_entries(path: &str) -> Result<Vec<Entry>, WtError> {
+    let bytes = fs::read(path).map_err(|e| WtError::Storage(format!("cannot read {}: {}", path, e)))?;
+    if bytes.starts_with(SQLITE_MAGIC) {
+        let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY;
+        let conn = rusqlite::Connection::open_with_flags(path, flags).map_err(sqlite_error)?;
+        return Ok(SqliteStorage { path: path.to_string(), conn }.load()?.saved);
+    }
+    Ok(split_tombstones(read_data_file(path)?).0)
+}
+
+/// `bytes` decompressed if they start with the gzip magic bytes.
+pub fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, WtError> {
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return Ok(bytes);
+    }
+    let mut decoded = Vec::new();
+    GzDecoder::new(bytes.as_slice())
+        .read_to_end(&mut decoded)
+        .map_err(|e| WtError::Storage(format!("corrupt compressed data file: {}", e)))?;
+    Ok(decoded)
+}
+
+/// Decodes the raw content of the data file, decompressing it if it starts
+/// with the gzip magic bytes.
+pub fn decode_data(bytes: Vec<u8>) -> Result<String, WtError> {
+    String::from_utf8(decompress(bytes)?)
+        .map_err(|_| WtError::Storage(String::from("data file is not valid UTF-8")))
+}
+
+pub fn get_data_file() -> Result<String, WtError> {
+    let base_dirs = BaseDirs::new();
+    if base_dirs.is_none() {
+        return Err(WtError::Storage(String::from("cannot find the home directory")));
+    }
+    let mut data_path = base_dirs.unwrap().data_local_dir().to_path_buf();
+    data_path.push("weight-tracker");
+    if !data_path.try_exists()? {
+        let data_path_str = data_path.to_str().unwrap();
+        fs::create_dir_all(data_path_str)?;
+    }
+    data_path.push("weight-tracker.csv");
+    let ret = data_path.to_str();
+    if let Some(ret) = ret {
+        return Ok(ret.to_string());
+    }
+    Err(WtError::Storage(String::from("data path is not valid UTF-8")))
+}
+
+/// Backups of the data file at `path`, oldest first.
+pub fn backups(path: &str) -> Result<Vec<String>, WtError> {
+    let path = Path::new(path);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let prefix = format!("{}.", path.file_name().unwrap_or_default().to_string_lossy());
+    let mut backups = fs::read_dir(dir)?
+        .filter_map(|x| Some(x.ok()?.file_name().to_string_lossy().into_owned()))
+        .filter(|x| x.starts_with(&prefix) && x.ends_with(".bak"))
+        .map(|x| dir.join(x).to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    // The timestamps sort chronologically.
+    backups.sort();
+    Ok(backups)
+}
+
+/// Renames the data file at `path` to `<path>.<timestamp>.broken`, out of
+/// the way of a fresh one, and returns the new name.
+pub fn move_aside(path: &str) -> Result<String, WtError> {
+    let stamp = Local::now().format(BACKUP_TIMESTAMP);
+    let mut dest = format!("{}.{}.broken", path, stamp);
+    // Never replace a file moved aside earlier in the same second.
+    for n in 1.. {
+        if !fs::exists(&dest)? {
+            break;
+        }
+        dest = format!("{}.{}-{}.broken", path, stamp, n);
+    }
+    fs::rename(path, &dest)?;
+    Ok(dest)
+}
+
+const BACKUP_TIMESTAMP: &str = "%Y%m%d-%H%M%S";
+
+/// Version of the data file format written by `write_csv`.
+pub const FORMAT_VERSION: u32 = 3;
+
+const FIELD_VERSION: &str = "version=";
+
+/// Rewrites the lines after the header from one format version to the next.
+type Migration = fn(&mut [String]);
+
+/// Steps from each format version to the next; `MIGRATIONS[n - 1]` upgrades
+/// version `n`. A change to the format bumps `FORMAT_VERSION` and adds its
+/// step here.
+const MIGRATIONS: [(&str, Migration); 2] = [
+    ("give every entry an id", migrate_ids),
+    ("record deleted entries as tombstones", migrate_tombstones),
+];
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Entry;
+
+    #[test]
+    fn change_line_round_trip() {
+        let mut entry = Entry::new(String::from("03-02-2024"), 80.5);
+        entry.excluded = true;
+        entry.rhr = Some(58.0);
+        entry.source = Some(String::from("import:apple-health"));
+        entry.device = Some(String::from("gym"));
+        for change in [Change::Set(entry), Change::Delete(String::from("04-02-2024"))] {
+            assert!(Change::parse(&change.to_line()) == Some(change));
+        }
+        assert!(Change::parse("set, 31-02-2024, 80").is_none());
+        assert!(Change::parse("set, 01-02-2024").is_none());
+    }
+
+    #[test]
+    fn old_data_files_are_backed_up_and_migrated() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.csv").to_string_lossy().into_owned();
+        let original = "Date, Weight\n01-01-2024, 80.0\n02-01-2024, 79.8, excluded, id=kept\n";
+        fs::write(&path, original).unwrap();
+        let mut storage = CsvStorage::new(path.clone(), 0);
+        let loaded = storage.load().unwrap();
+        let (version, backup) = loaded.migrated.unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().next(), Some("Date, Weight, Flags, version=3"));
+        assert!(loaded.saved.iter().all(|x| x.id.is_some()));
+        assert_eq!(loaded.saved[1].id.as_deref(), Some("kept"));
+        assert!(loaded.saved[1].excluded);
+        assert!(storage.load().unwrap().migrated.is_none());
+
+        fs::write(&path, "Date, Weight, Flags, version=99\n").unwrap();
+        assert!(storage.load().is_err());
+        fs::write(&path, "Date, Weight, version=0\n01-01-2024, 80.0\n").unwrap();
+        assert!(matches!(storage.load(), Err(WtError::Parse { line: 1, .. })));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_data_files_are_not_migrated() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-no-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.csv").to_string_lossy().into_owned();
+        for original in ["garbage\nmore garbage, 12\n", "Date, Weight\n01-01-2024, 80.0\nnot a row\n"] {
+            fs::write(&path, original).unwrap();
+            assert!(matches!(CsvStorage::new(path.clone(), 0).load(), Err(WtError::Parse { .. })));
+            assert_eq!(fs::read_to_string(&path).unwrap(), original);
+            assert!(backups(&path).unwrap().is_empty());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bad_rows_are_parse_errors() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-rows-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.csv").to_string_lossy().into_owned();
+        let header = "Date, Weight, Flags, version=3\n";
+        for (rows, line) in [("01-05-2024, 80.0\n\n3x-05-2024, 81.0\n", 4), ("01-05-2024, abc\n", 2)] {
+            fs::write(&path, format!("{}{}", header, rows)).unwrap();
+            assert!(matches!(read_data_file(&path), Err(WtError::Parse { line: l, .. }) if l == line));
+        }
+        fs::write(&path, format!("{}01-05-2024, 80.0, excluded\n", header)).unwrap();
+        assert!(read_data_file(&path).unwrap()[0].excluded);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entries_are_read_from_either_backend() {
+        let dir = std::env::temp_dir().join(format!("weight-tracker-read-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let data = ["01-01-2024, 80.0", "02-01-2024, 79.8, excluded"].map(|x| Entry::from_fields(x).unwrap());
+        let mut rows = data.to_vec();
+        rows.push(Entry::tombstone(String::from("03-01-2024"), chrono::Utc::now()));
+        let csv = dir.join("data.csv").to_string_lossy().into_owned();
+        CsvStorage::new(csv.clone(), 1).save(&mut rows.clone(), &[]).unwrap();
+        assert!(read_data_file(&csv).unwrap() == rows);
+        let sqlite = dir.join("data.sqlite").to_string_lossy().into_owned();
+        SqliteStorage::open(sqlite.clone()).unwrap().save(&mut rows.clone(), &[]).unwrap();
+        assert!(read_entries(&csv).unwrap() == data);
+        assert!(read_entries(&sqlite).unwrap() == data);
+        assert!(read_entries(&dir.join("missing.csv").to_string_lossy()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_keeps_the_unit_of_each_row() {
+        let mut storage = SqliteStorage::open(String::from(":memory:")).unwrap();
+        let rows = ["01-01-2024, 80.0, unit=kg", "02-01-2024, 176.4, unit=lb", "03-01-2024, 79.9, device=gym"];
+        let mut data = rows.map(|x| Entry::from_fields(x).unwrap()).to_vec();
+        data.iter_mut().for_each(|x| x.normalize(Unit::Lb));
+        storage.save(&mut data.clone(), &[]).unwrap();
+        let loaded = storage.load().unwrap().saved;
+        assert!(loaded == data);
+        assert_eq!(loaded[1].to_fields(), "02-01-2024, 176.4, unit=lb");
+    }
+}
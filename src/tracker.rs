@@ -0,0 +1,199 @@
+//! Handle on one data source shared by several front ends, e.g. the TUI and
+//! a listener logging weights from a scale. Clones of a `Tracker` share the
+//! entries; writes are serialized and every change is announced to the
+//! subscribers.
+
+use chrono::{NaiveDate, Utc};
+use std::sync::{
+    Arc, Mutex, MutexGuard,
+    mpsc::{self, Receiver, Sender},
+};
+use tracing::info;
+
+use crate::WtError;
+use crate::data::{Entry, MergeReport, find_entry, record_deletions, split_tombstones, with_tombstones};
+use crate::storage::{Change, Storage};
+
+/// What subscribers are told about, in the order it happened.
+#[derive(Clone)]
+pub enum TrackerEvent {
+    Changed(Change),
+    /// The entries were written to storage, with whatever was merged in
+    /// from disk on the way.
+    Saved,
+}
+
+struct Shared {
+    storage: Box<dyn Storage + Send>,
+    data: Vec<Entry>,
+    saved: Vec<Entry>,
+    /// Tombstones of the deleted entries, now and as last saved.
+    deleted: Vec<Entry>,
+    saved_deleted: Vec<Entry>,
+    subscribers: Vec<Sender<TrackerEvent>>,
+}
+
+impl Shared {
+    /// Sends `event` to every subscriber, forgetting the ones that are gone.
+    fn notify(&mut self, event: TrackerEvent) {
+        self.subscribers.retain(|x| x.send(event.clone()).is_ok());
+    }
+
+    /// Journals `change` before applying it, so that it survives a crash
+    /// before the next save.
+    fn apply(&mut self, change: Change) -> Result<(), WtError> {
+        self.storage.append(std::slice::from_ref(&change))?;
+        change.apply(&mut self.data);
+        record_deletions(&mut self.deleted, &self.data, &[change.date().to_string()], Utc::now());
+        self.notify(TrackerEvent::Changed(change));
+        Ok(())
+    }
+}
+
+/// Entries as stored, without unit conversion; `Send + Sync` and cheap to
+/// clone.
+#[derive(Clone)]
+pub struct Tracker {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Tracker {
+    /// Loads `storage`, replaying changes a previous session appended but
+    /// never saved.
+    pub fn open(mut storage: Box<dyn Storage + Send>) -> Result<Tracker, WtError> {
+        let loaded = storage.load()?;
+        let (saved, saved_deleted) = split_tombstones(loaded.saved);
+        let (mut data, mut deleted) = (saved.clone(), saved_deleted.clone());
+        for change in &loaded.pending {
+            change.apply(&mut data);
+        }
+        let replayed = loaded.pending.iter().map(|x| x.date().to_string()).collect::<Vec<_>>();
+        record_deletions(&mut deleted, &data, &replayed, Utc::now());
+        info!("tracker opened {} with {} entries", storage.location(), data.len());
+        let shared = Shared {
+            storage,
+            data,
+            saved,
+            deleted,
+            saved_deleted,
+            subscribers: Vec::new(),
+        };
+        Ok(Tracker { shared: Arc::new(Mutex::new(shared)) })
+    }
+
+    /// A panic while holding the lock leaves the entries consistent, as
+    /// changes are applied in one step, so a poisoned lock is taken over.
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn entries(&self) -> Vec<Entry> {
+        self.lock().data.clone()
+    }
+
+    pub fn get(&self, date: NaiveDate) -> Option<Entry> {
+        let shared = self.lock();
+        find_entry(&shared.data, date).ok().map(|idx| shared.data[idx].clone())
+    }
+
+    /// Receives every following change. Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<TrackerEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock().subscribers.push(sender);
+        receiver
+    }
+
+    /// Adds `entry` or replaces the one of its date.
+    pub fn set(&self, mut entry: Entry) -> Result<(), WtError> {
+        if NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y").is_err() {
+            return Err(WtError::Validation(format!("Invalid date '{}'", entry.date)));
+        }
+        entry.touch(Utc::now());
+        self.lock().apply(Change::Set(entry))
+    }
+
+    /// Removes the entry of `date`; returns `false` if there was none.
+    pub fn delete(&self, date: NaiveDate) -> Result<bool, WtError> {
+        let mut shared = self.lock();
+        if find_entry(&shared.data, date).is_err() {
+            return Ok(false);
+        }
+        shared.apply(Change::Delete(date.format("%d-%m-%Y").to_string()))?;
+        Ok(true)
+    }
+
+    pub fn is_modified(&self) -> bool {
+        let shared = self.lock();
+        shared.data != shared.saved
+    }
+
+    /// Writes the entries to storage, merging in changes made to it behind
+    /// our back.
+    pub fn save(&self) -> Result<Option<MergeReport>, WtError> {
+        let mut guard = self.lock();
+        let shared = &mut *guard;
+        shared.data.iter_mut().for_each(Entry::assign_id);
+        let mut rows = with_tombstones(&shared.data, &shared.deleted);
+        let base = with_tombstones(&shared.saved, &shared.saved_deleted);
+        let merge = shared.storage.save(&mut rows, &base)?;
+        (shared.data, shared.deleted) = split_tombstones(rows);
+        shared.saved = shared.data.clone();
+        shared.saved_deleted = shared.deleted.clone();
+        shared.notify(TrackerEvent::Saved);
+        Ok(merge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{CsvStorage, MemoryStorage, SqliteStorage};
+    use std::thread;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn writers_on_other_threads_are_serialized_and_announced() {
+        let storage = MemoryStorage {
+            saved: vec![Entry::new(String::from("01-05-2024"), 88.0)],
+            ..Default::default()
+        };
+        let tracker = Tracker::open(Box::new(storage)).unwrap();
+        let events = tracker.subscribe();
+        let writers = (2..=9)
+            .map(|d| {
+                let tracker = tracker.clone();
+                let entry = Entry::new(format!("{:02}-05-2024", d), 88.0 - d as f64 / 10.0);
+                thread::spawn(move || tracker.set(entry))
+            })
+            .collect::<Vec<_>>();
+        writers.into_iter().for_each(|x| x.join().unwrap().unwrap());
+        assert!(tracker.delete(day(1)).unwrap());
+        assert!(!tracker.delete(day(1)).unwrap());
+        assert!(tracker.set(Entry::new(String::from("31-02-2024"), 80.0)).is_err());
+
+        let dates = tracker.entries().iter().map(|x| x.date.clone()).collect::<Vec<_>>();
+        assert_eq!(dates.len(), 8);
+        assert!(dates.windows(2).all(|x| x[0] < x[1]));
+        assert_eq!(tracker.get(day(5)).map(|x| x.weight), Some(87.5));
+        assert!(tracker.is_modified());
+        tracker.save().unwrap();
+        assert!(!tracker.is_modified());
+
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 10);
+        assert!(matches!(&events[8], TrackerEvent::Changed(Change::Delete(date)) if date == "01-05-2024"));
+        assert!(matches!(events[9], TrackerEvent::Saved));
+    }
+
+    #[test]
+    fn tracker_is_shareable_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        fn assert_send<T: Send>() {}
+        assert_send_sync::<Tracker>();
+        assert_send::<CsvStorage>();
+        assert_send::<SqliteStorage>();
+    }
+}
@@ -0,0 +1,1192 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use ratatui::{
+    Frame,
+    layout::{Alignment, Constraint, Flex, Layout, Rect},
+    style::{Color, Style, Stylize},
+    symbols::Marker,
+    text::{Line, Span},
+    widgets::{Axis, Block, Chart, Clear, Dataset, GraphType, Paragraph},
+};
+use std::collections::BTreeMap;
+
+use crate::app::{App, ChartTimeFrame, FrameType};
+use crate::config::{AxisLabels, AxisSide, PaddingConfig, PointColors};
+use crate::data::{
+    Entry, SEASONAL_MIN_DAYS, Unit, VOLATILITY_DAYS, deseasonalize, detect_outliers, pearson, project_date, rhr_pairs,
+    rolling_averages, rolling_correlation, weekly_rates, rolling_std_devs, volatility,
+};
+
+/// Padded y-axis bounds for `values`. Ranges narrower than `MIN_CHART_SPAN`
+/// (e.g. a single point) are widened around their center first, so the axis
+/// never collapses; non-finite values are ignored.
+fn compute_bounds(values: impl Iterator<Item = f64>, padding: &PaddingConfig) -> (f64, f64) {
+    let Some((min, max)) = values
+        .filter(|x| x.is_finite())
+        .fold(None, |acc: Option<(f64, f64)>, x| match acc {
+            Some((lo, hi)) => Some((lo.min(x), hi.max(x))),
+            None => Some((x, x)),
+        })
+    else {
+        return EMPTY_CHART_BOUNDS;
+    };
+    if max - min < MIN_CHART_SPAN {
+        let mid = (min + max) / 2.0;
+        return padding.pad(mid - MIN_CHART_SPAN / 2.0, mid + MIN_CHART_SPAN / 2.0);
+    }
+    padding.pad(min, max)
+}
+
+/// Offset of `date` from `today` in whole weeks, e.g. `-8w`, or `now`.
+fn relative_label(date: NaiveDate, today: NaiveDate) -> String {
+    match ((date - today).num_days() as f64 / 7.0).round() as i64 {
+        0 => String::from("now"),
+        weeks => format!("{:+}w", weeks),
+    }
+}
+
+const PROJECTION_SAMPLES: usize = 240;
+
+const PROJECTION_DASH: usize = 4;
+
+const SIMULATOR_TARGETS: [f64; 3] = [1.0, 2.0, 5.0];
+
+const SIMULATOR_OFFSET_X: u16 = 8;
+
+const CORRELATION_DAYS: u64 = 30;
+
+const CORRELATION_MIN_POINTS: usize = 5;
+
+const EMPTY_CHART_BOUNDS: (f64, f64) = (0.0, 100.0);
+
+const MIN_CHART_SPAN: f64 = 1.0;
+
+const RIGHT_AXIS_PADDING: f64 = 0.1;
+
+const RIGHT_AXIS_MIN_PADDING: f64 = 0.5;
+
+const RATE_MIN_PADDING: f64 = 0.1;
+
+const YOY_YEARS: i32 = 5;
+
+/// Days of the made-up series previewed while there is no data.
+const SAMPLE_DAYS: usize = 60;
+
+const YOY_COLORS: [Color; 5] = [
+    Color::Blue,
+    Color::Green,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Red,
+];
+
+const DERIVED_COLORS: [Color; 4] = [Color::Green, Color::Magenta, Color::Yellow, Color::Red];
+
+/// Point colors from worst to best progress.
+const GRADIENT_COLORS: [Color; 5] = [
+    Color::Red,
+    Color::LightRed,
+    Color::Yellow,
+    Color::LightGreen,
+    Color::Green,
+];
+
+/// Change between two points (kg) that gets the full red or green, converted
+/// to the display unit.
+const GRADIENT_CHANGE_SCALE: f64 = 1.0;
+
+/// Position of each of `points` on the gradient, from 0 (red) to 1 (green),
+/// or `None` if they are all drawn in the same color. `data` sets the scale of
+/// the goal gradient, so colors don't shift while panning. Weights are in
+/// `unit`.
+fn gradient_positions(
+    points: &[(f64, f64)],
+    data: &[Entry],
+    mode: PointColors,
+    goal: Option<f64>,
+    unit: Unit,
+) -> Option<Vec<f64>> {
+    match mode {
+        PointColors::Plain => None,
+        PointColors::Goal => {
+            let goal = goal?;
+            let max = data
+                .iter()
+                .filter(|x| !x.excluded)
+                .map(|x| (x.weight - goal).abs())
+                .fold(0.0, f64::max);
+            let positions = points
+                .iter()
+                .map(|(_, w)| if max > f64::EPSILON { 1.0 - (w - goal).abs() / max } else { 1.0 })
+                .collect();
+            Some(positions)
+        }
+        PointColors::Change => {
+            let progress = |from: f64, to: f64| match goal {
+                Some(goal) => (from - goal).abs() - (to - goal).abs(),
+                None => from - to,
+            };
+            let mut positions = vec![0.5; points.len()];
+            let scale = Unit::Kg.convert(GRADIENT_CHANGE_SCALE, unit);
+            for (i, pair) in points.windows(2).enumerate() {
+                let change = progress(pair[0].1, pair[1].1) / scale;
+                positions[i + 1] = 0.5 + change.clamp(-1.0, 1.0) / 2.0;
+            }
+            Some(positions)
+        }
+    }
+}
+
+/// Lower and upper edge of the volatility band, the rolling average plus and
+/// minus the rolling standard deviation, for the entries `delta` days from
+/// `date_left`.
+fn volatility_band(data: &[Entry], date_left: NaiveDate, delta: f64) -> [Vec<(f64, f64)>; 2] {
+    let averages = rolling_averages(data, VOLATILITY_DAYS);
+    let deviations = rolling_std_devs(data, VOLATILITY_DAYS);
+    let mut band = [Vec::new(), Vec::new()];
+    for (i, entry) in data.iter().enumerate().filter(|(_, x)| !x.excluded) {
+        let (Some(avg), Some(dev)) = (averages[i], deviations[i]) else {
+            continue;
+        };
+        let Ok(date) = NaiveDate::parse_from_str(&entry.date, "%d-%m-%Y") else {
+            continue;
+        };
+        let x = (date - date_left).num_days() as f64;
+        if (0.0..=delta).contains(&x) {
+            band[0].push((x, avg - dev));
+            band[1].push((x, avg + dev));
+        }
+    }
+    band
+}
+
+type Points = Vec<(f64, f64)>;
+
+/// Points of the overview chart: entries before `split` averaged per week
+/// counted from the first entry, one x unit per week, followed by the entries
+/// from `split` on, one x unit per day. Also returns the number of weeks.
+fn overview_points(data: &[Entry], split: NaiveDate) -> (Points, Points, f64) {
+    let dated = data
+        .iter()
+        .filter(|x| !x.excluded)
+        .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.weight)))
+        .collect::<Vec<_>>();
+    let Some(first) = dated.iter().map(|x| x.0).min() else {
+        return (Vec::new(), Vec::new(), 0.0);
+    };
+    let weeks = ((split - first).num_days().max(0) + 6) / 7;
+    let mut buckets: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+    let mut daily = Vec::new();
+    for (date, weight) in dated {
+        if date < split {
+            let bucket = buckets.entry((date - first).num_days() / 7).or_default();
+            bucket.0 += weight;
+            bucket.1 += 1;
+        } else {
+            daily.push((weeks as f64 + (date - split).num_days() as f64, weight));
+        }
+    }
+    let weekly = buckets
+        .into_iter()
+        .map(|(week, (sum, count))| (week as f64, sum / count as f64))
+        .collect();
+    (weekly, daily, weeks as f64)
+}
+
+impl App<'_> {
+    /// Scatter of resting heart rate against weight next to their rolling
+    /// correlation over time.
+    pub fn render_correlation(&self, frame: &mut Frame) {
+        let data = self.stats_data();
+        let pairs = rhr_pairs(&data);
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Percentage(80)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(90)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let values = pairs.iter().map(|x| (x.1, x.2)).collect::<Vec<_>>();
+        let title = match pearson(&values) {
+            Some(r) => format!(
+                " Weight vs resting heart rate | r = {} (n = {}) ",
+                self.localize(format!("{:.2}", r)),
+                values.len()
+            ),
+            None => String::from(" Weight vs resting heart rate "),
+        };
+        let block = Block::bordered().title(title);
+        frame.render_widget(Clear, area); //this clears out the background
+        if values.len() < CORRELATION_MIN_POINTS {
+            let text = format!(
+                "Log your resting heart rate on at least {} days to see how it tracks your weight.",
+                CORRELATION_MIN_POINTS
+            );
+            frame.render_widget(Paragraph::new(text).centered().block(block), area);
+            return;
+        }
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [area_scatter, area_rolling] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(inner);
+
+        let padding = PaddingConfig::default();
+        let (x_min, x_max) = compute_bounds(values.iter().map(|x| x.0), &padding);
+        let (y_min, y_max) = compute_bounds(values.iter().map(|x| x.1), &padding);
+        let scatter = Chart::new(vec![
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Scatter)
+                .light_red()
+                .data(&values),
+        ])
+        .block(Block::bordered().title(" RHR by weight "))
+        .x_axis(
+            Axis::default()
+                .style(Style::default().gray())
+                .bounds([x_min, x_max])
+                .labels([
+                    self.localize(format!("{:.1}", x_min)).bold(),
+                    self.localize(format!("{:.1}", x_max)).bold(),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().gray())
+                .bounds([y_min, y_max])
+                .labels([format!("{:.0}", y_min).bold(), format!("{:.0}", y_max).bold()]),
+        );
+        frame.render_widget(scatter, area_scatter);
+
+        let rolling = rolling_correlation(&data, CORRELATION_DAYS, CORRELATION_MIN_POINTS);
+        let rolling_title = format!(" {}-day correlation ", CORRELATION_DAYS);
+        let (Some(first), Some(last)) = (rolling.first(), rolling.last()) else {
+            let text = Paragraph::new("Not enough entries within any window")
+                .centered()
+                .block(Block::bordered().title(rolling_title));
+            frame.render_widget(text, area_rolling);
+            return;
+        };
+        let points = rolling
+            .iter()
+            .map(|(date, r)| ((*date - first.0).num_days() as f64, *r))
+            .collect::<Vec<_>>();
+        let span = points.last().map_or(1.0, |x| x.0.max(1.0));
+        let chart = Chart::new(vec![
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .cyan()
+                .data(&points),
+        ])
+        .block(Block::bordered().title(rolling_title))
+        .x_axis(
+            Axis::default()
+                .style(Style::default().gray())
+                .bounds([0.0, span])
+                .labels([
+                    first.0.format("%d-%m-%Y").to_string().bold(),
+                    last.0.format("%d-%m-%Y").to_string().bold(),
+                ])
+                .labels_alignment(Alignment::Right),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().gray())
+                .bounds([-1.0, 1.0])
+                .labels(["-1".bold(), "0".bold(), "1".bold()]),
+        );
+        frame.render_widget(chart, area_rolling);
+    }
+
+    /// First and last day shown by the chart in the current timeframe.
+    pub fn chart_range(&self) -> (NaiveDate, NaiveDate) {
+        match self.current_tf {
+            ChartTimeFrame::WindowYear => {
+                let date_right = self.selected_date_wy;
+                let date_left = date_right.checked_sub_months(Months::new(12)).unwrap();
+                (date_left, date_right)
+            }
+            ChartTimeFrame::Year | ChartTimeFrame::YearOverYear => {
+                let y = self.selected_date_y.year_ce().1;
+                let date_left = NaiveDate::from_ymd_opt(y.try_into().unwrap(), 1, 1).unwrap();
+                let date_right = NaiveDate::from_ymd_opt(y.try_into().unwrap(), 12, 31).unwrap();
+                (date_left, date_right)
+            }
+            ChartTimeFrame::Month => {
+                let y = self.selected_date_m.year_ce().1;
+                let m = self.selected_date_m.month();
+                let date_left = NaiveDate::from_ymd_opt(y.try_into().unwrap(), m, 1).unwrap();
+                let date_right = date_left
+                    .checked_add_months(Months::new(1))
+                    .unwrap()
+                    .checked_sub_days(Days::new(1))
+                    .unwrap();
+                (date_left, date_right)
+            }
+            ChartTimeFrame::Quarter => {
+                let y = self.selected_date_m.year_ce().1;
+                let m = (self.selected_date_m.month0() / 3) * 3 + 1;
+                let date_left = NaiveDate::from_ymd_opt(y.try_into().unwrap(), m, 1).unwrap();
+                let date_right = date_left
+                    .checked_add_months(Months::new(3))
+                    .unwrap()
+                    .checked_sub_days(Days::new(1))
+                    .unwrap();
+                (date_left, date_right)
+            }
+            ChartTimeFrame::Overview => {
+                let date_right = self.today();
+                let split = date_right - Days::new(self.config.chart.overview_days);
+                let first = self
+                    .data
+                    .borrow()
+                    .iter()
+                    .filter_map(|x| NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok())
+                    .min();
+                (first.map_or(split, |x| x.min(split)), date_right)
+            }
+            ChartTimeFrame::Months => {
+                let first = self.selected_date_m.with_day(1).unwrap();
+                let date_left = first
+                    .checked_sub_months(Months::new(self.config.chart.window_months - 1))
+                    .unwrap();
+                let date_right = first
+                    .checked_add_months(Months::new(1))
+                    .unwrap()
+                    .checked_sub_days(Days::new(1))
+                    .unwrap();
+                (date_left, date_right)
+            }
+        }
+    }
+
+    /// Overlays the last `YOY_YEARS` years up to the selected one on a shared
+    /// Jan-Dec axis, one colored series per year.
+    fn render_yoy_chart(&self, area: Rect, frame: &mut Frame, style: Style, x_label: Vec<Span>) {
+        let last_year = self.selected_date_y.year();
+        let first_year = last_year - YOY_YEARS + 1;
+        let stats_data = self.stats_data();
+        let mut years: BTreeMap<i32, Vec<(f64, f64)>> = BTreeMap::new();
+        for x in stats_data.iter().filter(|x| !x.excluded) {
+            let date = NaiveDate::parse_from_str(x.date.as_str(), "%d-%m-%Y").unwrap();
+            if date.year() >= first_year && date.year() <= last_year {
+                years
+                    .entry(date.year())
+                    .or_default()
+                    .push((date.ordinal0() as f64, x.weight));
+            }
+        }
+        let (y_min, y_max) =
+            compute_bounds(years.values().flatten().map(|x| x.1), &self.config.chart.padding);
+        let chart_style = self.config.chart.style(&self.current_tf);
+        let datasets = years
+            .iter()
+            .map(|(year, points)| {
+                let color = YOY_COLORS[(last_year - year) as usize % YOY_COLORS.len()];
+                Dataset::default()
+                    .name(year.to_string())
+                    .marker(chart_style.marker.marker())
+                    .style(Style::new().fg(color))
+                    .graph_type(chart_style.graph_type.graph_type())
+                    .data(points)
+            })
+            .collect::<Vec<_>>();
+        let title = format!("Year over Year {}-{}", first_year, last_year);
+        let chart = Chart::new(datasets)
+            .block(
+                Block::bordered()
+                    .title_top(Line::from(title).cyan().bold().centered())
+                    .style(style),
+            )
+            .x_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([0.0, 365.0])
+                    .labels(x_label)
+                    .labels_alignment(Alignment::Right),
+            )
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([y_min, y_max])
+                    .labels([
+                        self.localize(format!("{:.1}", y_min)).bold(),
+                        self.localize(format!("{:.1}", y_max)).bold(),
+                    ]),
+            )
+            .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)));
+        frame.render_widget(chart, area);
+    }
+
+    /// Weekly change of the 7-day average over the period, around a zero
+    /// line: plateaus sit on it and a speeding up shows as a slope.
+    fn render_rate_chart(&mut self, area: Rect, frame: &mut Frame, style: Style, x_label: Vec<Span>, title: &str) {
+        let (date_left, date_right) = self.chart_range();
+        let delta = (date_right - date_left).num_days() as f64;
+        if self.rates.is_none() {
+            self.rates = Some(weekly_rates(&self.stats_data()));
+        }
+        let rates = self
+            .rates
+            .iter()
+            .flatten()
+            .filter(|(date, _)| (date_left..=date_right).contains(date))
+            .map(|(date, rate)| ((*date - date_left).num_days() as f64, *rate))
+            .collect::<Points>();
+        // Zero is always in range, so that gaining and losing read at a glance.
+        // The weight padding would flatten rates of a few tenths.
+        let (lo, hi) = rates.iter().fold((0.0f64, 0.0f64), |(lo, hi), x| (lo.min(x.1), hi.max(x.1)));
+        let pad = ((hi - lo) * RIGHT_AXIS_PADDING).max(RATE_MIN_PADDING);
+        let (y_min, y_max) = (lo - pad, hi + pad);
+        let zero = [(0.0, 0.0), (delta, 0.0)];
+        let chart_style = self.config.chart.style(&self.current_tf);
+        let datasets = vec![
+            Dataset::default()
+                .marker(Marker::Braille)
+                .style(Style::new().dark_gray())
+                .graph_type(GraphType::Line)
+                .data(&zero),
+            Dataset::default()
+                .marker(chart_style.marker.marker())
+                .style(Style::new().blue())
+                .graph_type(chart_style.graph_type.graph_type())
+                .data(&rates),
+        ];
+        let title = format!("{} · {}/week", title, self.config.display.unit.name());
+        let chart = Chart::new(datasets)
+            .block(
+                Block::bordered()
+                    .title_top(Line::from(title).cyan().bold().centered())
+                    .style(style),
+            )
+            .x_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([0.0, delta])
+                    .labels(x_label)
+                    .labels_alignment(Alignment::Right),
+            )
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([y_min, y_max])
+                    .labels([
+                        self.localize(format!("{:+.1}", y_min)).bold(),
+                        self.localize(format!("{:+.1}", y_max)).bold(),
+                    ]),
+            );
+        frame.render_widget(chart, area);
+    }
+
+    /// Whole history on one axis: weekly averages up to `chart.overview_days`
+    /// before today, then daily entries.
+    fn render_overview_chart(&self, area: Rect, frame: &mut Frame, style: Style, x_label: Vec<Span>) {
+        let (_, today) = self.chart_range();
+        let split = today - Days::new(self.config.chart.overview_days);
+        let (weekly, daily, weeks) = overview_points(&self.stats_data(), split);
+        let (y_min, y_max) = compute_bounds(
+            weekly.iter().chain(daily.iter()).map(|x| x.1),
+            &self.config.chart.padding,
+        );
+        let chart_style = self.config.chart.style(&self.current_tf);
+        let datasets = vec![
+            Dataset::default()
+                .name("weekly avg")
+                .marker(chart_style.marker.marker())
+                .style(Style::new().cyan())
+                .graph_type(chart_style.graph_type.graph_type())
+                .data(&weekly),
+            Dataset::default()
+                .name("daily")
+                .marker(chart_style.marker.marker())
+                .style(Style::new().blue())
+                .graph_type(chart_style.graph_type.graph_type())
+                .data(&daily),
+        ];
+        let chart = Chart::new(datasets)
+            .block(
+                Block::bordered()
+                    .title_top(Line::from("Overview").cyan().bold().centered())
+                    .style(style),
+            )
+            .x_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([0.0, weeks + self.config.chart.overview_days as f64])
+                    .labels(x_label)
+                    .labels_alignment(Alignment::Right),
+            )
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([y_min, y_max])
+                    .labels([
+                        self.localize(format!("{:.1}", y_min)).bold(),
+                        self.localize(format!("{:.1}", y_max)).bold(),
+                    ]),
+            )
+            .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)));
+        frame.render_widget(chart, area);
+    }
+
+    /// Faint made-up series showing what the chart will look like, without
+    /// any axis values that could be mistaken for data.
+    fn render_sample_chart(&self, area: Rect, frame: &mut Frame, style: Style) {
+        let points = (0..SAMPLE_DAYS)
+            .map(|i| {
+                let x = i as f64;
+                (x, 80.0 - x * 0.05 + (x / 3.0).sin() * 0.6)
+            })
+            .collect::<Vec<_>>();
+        let (y_min, y_max) = compute_bounds(points.iter().map(|x| x.1), &self.config.chart.padding);
+        let dataset = Dataset::default()
+            .marker(Marker::Braille)
+            .style(Style::new().dark_gray())
+            .graph_type(GraphType::Line)
+            .data(&points);
+        let chart = Chart::new(vec![dataset])
+            .block(
+                Block::bordered()
+                    .title_top(Line::from(" Sample chart ").dark_gray().centered())
+                    .title_bottom(Line::from(" Your weights will be charted here ").centered())
+                    .style(style),
+            )
+            .x_axis(Axis::default().bounds([0.0, (SAMPLE_DAYS - 1) as f64]))
+            .y_axis(Axis::default().bounds([y_min, y_max]));
+        frame.render_widget(chart, area);
+    }
+
+    /// `chart.title` filled in for the timeframe `label` and the weights of
+    /// the entries shown, in date order.
+    fn chart_title(&self, label: &str, weights: &[f64]) -> String {
+        let template = &self.config.chart.title;
+        let stat = |value: Option<f64>, sign: bool| match value {
+            Some(x) if sign => self.localize(format!("{:+.1}", x)),
+            Some(x) => self.localize(format!("{:.1}", x)),
+            None => String::from("-"),
+        };
+        let average = (!weights.is_empty()).then(|| weights.iter().sum::<f64>() / weights.len() as f64);
+        let change = weights.first().zip(weights.last()).map(|(first, last)| last - first);
+        template
+            .replace("{label}", label)
+            .replace("{count}", &weights.len().to_string())
+            .replace("{average}", &stat(average, false))
+            .replace("{change}", &stat(change, true))
+            .replace("{unit}", self.config.display.unit.name())
+    }
+
+    pub fn render_chart(&mut self, area: Rect, frame: &mut Frame) {
+        let style = match self.current_frame {
+            FrameType::Table => Style::default().dark_gray(),
+            _ => Style::default(),
+        };
+        if self.data.borrow().is_empty() {
+            self.render_sample_chart(area, frame, style);
+            return;
+        }
+        let (date_left, date_right) = self.chart_range();
+        let (x_label, title) = match self.current_tf {
+            ChartTimeFrame::WindowYear => {
+                let dates = [
+                    date_left,
+                    date_left.checked_add_months(Months::new(4)).unwrap(),
+                    date_left.checked_add_months(Months::new(8)).unwrap(),
+                    date_right,
+                ];
+                let x_label = dates
+                    .iter()
+                    .map(|d| match self.config.chart.x_labels {
+                        AxisLabels::Dates => d.format("%d-%m-%Y").to_string(),
+                        AxisLabels::Relative => relative_label(*d, self.today()),
+                    })
+                    .map(|x| Span::styled(x, Style::default()))
+                    .collect();
+                (x_label, String::from("One Year Window"))
+            }
+            ChartTimeFrame::Year | ChartTimeFrame::YearOverYear => {
+                let x_label = vec![
+                    Span::styled(date_left.format("%b").to_string(), Style::default()),
+                    Span::styled(
+                        date_left
+                            .checked_add_months(Months::new(4))
+                            .unwrap()
+                            .format("%b")
+                            .to_string(),
+                        Style::default(),
+                    ),
+                    Span::styled(
+                        date_left
+                            .checked_add_months(Months::new(8))
+                            .unwrap()
+                            .format("%b")
+                            .to_string(),
+                        Style::default(),
+                    ),
+                    Span::styled(date_right.format("%b").to_string(), Style::default()),
+                ];
+                (x_label, self.selected_date_y.format("%Y").to_string())
+            }
+            ChartTimeFrame::Month => {
+                let x_label = vec![
+                    Span::styled(date_left.format("%d").to_string(), Style::default()),
+                    Span::styled(date_right.format("%d").to_string(), Style::default()),
+                ];
+                (x_label, self.selected_date_m.format("%b %Y").to_string())
+            }
+            ChartTimeFrame::Quarter => {
+                let mut x_label = (0..3)
+                    .map(|i| date_left.checked_add_months(Months::new(i)).unwrap())
+                    .map(|d| Span::styled(d.format("%d %b").to_string(), Style::default()))
+                    .collect::<Vec<_>>();
+                x_label.push(Span::styled(date_right.format("%d %b").to_string(), Style::default()));
+                let title = format!("Q{} {}", date_left.month0() / 3 + 1, date_left.format("%Y"));
+                (x_label, title)
+            }
+            ChartTimeFrame::Months => {
+                let middle = date_left + (date_right - date_left) / 2;
+                let x_label = [date_left, middle, date_right]
+                    .iter()
+                    .map(|d| Span::styled(d.format("%d %b").to_string(), Style::default()))
+                    .collect();
+                let title = format!("{} - {}", date_left.format("%b %Y"), date_right.format("%b %Y"));
+                (x_label, title)
+            }
+            ChartTimeFrame::Overview => {
+                let split = date_right - Days::new(self.config.chart.overview_days);
+                let x_label = [date_left, split, date_right]
+                    .iter()
+                    .map(|d| Span::styled(d.format("%d-%m-%Y").to_string(), Style::default()))
+                    .collect();
+                (x_label, String::from("Overview"))
+            }
+        };
+        if self.current_tf == ChartTimeFrame::YearOverYear {
+            self.render_yoy_chart(area, frame, style, x_label);
+            return;
+        }
+        if self.current_tf == ChartTimeFrame::Overview {
+            self.render_overview_chart(area, frame, style, x_label);
+            return;
+        }
+        if self.rate_view {
+            self.render_rate_chart(area, frame, style, x_label, &title);
+            return;
+        }
+        let delta = (date_right - date_left).num_days() as f64;
+        let cloned_data = self.stats_data();
+        let outliers = detect_outliers(&cloned_data, &self.config.outliers);
+        let mut data_points = Vec::new();
+        let mut outlier_points = Vec::new();
+        let mut excluded_points = Vec::new();
+        let mut shown = Vec::new();
+        for (x, outlier) in cloned_data.iter().zip(outliers) {
+            let date_point = NaiveDate::parse_from_str(x.date.as_str(), "%d-%m-%Y").unwrap();
+            let diff = (date_point - date_left).num_days() as f64;
+            if diff < 0f64 || diff > delta {
+                continue;
+            }
+            // Linked views show only the rows the table filter lets through.
+            if self.config.display.linked_views && !self.shows_row(x) {
+                continue;
+            }
+            if !x.excluded {
+                shown.push(x.weight);
+            }
+            if x.excluded {
+                excluded_points.push((diff, x.weight));
+            } else if outlier {
+                outlier_points.push((diff, x.weight));
+            } else {
+                data_points.push((diff, x.weight));
+            }
+        }
+        let title = self.chart_title(&title, &shown);
+        let title = match self.smoothing_days {
+            Some(days) => format!("{} · {}-day average", title, days),
+            None => title,
+        };
+        let derived_points = self
+            .derived
+            .iter()
+            .filter(|d| d.chart)
+            .map(|d| {
+                let points = data_points
+                    .iter()
+                    .filter_map(|(x, w)| Some((*x, d.expr.eval(&|name| self.lookup_var(name, *w))?)))
+                    .collect::<Vec<_>>();
+                (d.name.clone(), d.axis, points)
+            })
+            .collect::<Vec<_>>();
+        let seasonal_points: Points = if self.config.chart.seasonal {
+            cloned_data
+                .iter()
+                .zip(deseasonalize(&cloned_data))
+                .filter_map(|(x, w)| {
+                    let date = NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?;
+                    Some(((date - date_left).num_days() as f64, w?))
+                })
+                .filter(|(x, _)| (0.0..=delta).contains(x))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let smoothed_points: Points = match self.smoothing_days {
+            Some(days) => cloned_data
+                .iter()
+                .zip(rolling_averages(&cloned_data, days))
+                .filter(|(x, _)| !x.excluded)
+                .filter_map(|(x, avg)| {
+                    let date = NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?;
+                    Some(((date - date_left).num_days() as f64, avg?))
+                })
+                .filter(|(x, _)| (0.0..=delta).contains(x))
+                .collect(),
+            None => Vec::new(),
+        };
+        let band_points = if self.config.chart.volatility {
+            volatility_band(&cloned_data, date_left, delta)
+        } else {
+            [Vec::new(), Vec::new()]
+        };
+        let all_points = data_points
+            .iter()
+            .chain(band_points.iter().flatten())
+            .chain(seasonal_points.iter())
+            .chain(smoothed_points.iter())
+            .chain(outlier_points.iter())
+            .chain(excluded_points.iter())
+            .chain(
+                derived_points
+                    .iter()
+                    .filter(|(_, axis, _)| *axis == AxisSide::Left)
+                    .flat_map(|(_, _, points)| points.iter()),
+            );
+        let mut all_points = all_points.map(|x| x.1).peekable();
+        // An empty period keeps the axis around the known weights rather than
+        // showing a made-up range.
+        let (y_min, y_max) = if all_points.peek().is_some() {
+            compute_bounds(all_points, &self.config.chart.padding)
+        } else {
+            compute_bounds(cloned_data.iter().map(|x| x.weight), &self.config.chart.padding)
+        };
+        // Right-axis series get their own bounds and are rescaled onto the
+        // left axis, since the chart widget only draws a single y-axis.
+        let right_bounds = derived_points
+            .iter()
+            .filter(|(_, axis, _)| *axis == AxisSide::Right)
+            .flat_map(|(_, _, points)| points.iter())
+            .fold(None, |acc: Option<(f64, f64)>, x| match acc {
+                Some((lo, hi)) => Some((lo.min(x.1), hi.max(x.1))),
+                None => Some((x.1, x.1)),
+            })
+            .map(|(lo, hi)| {
+                let pad = ((hi - lo) * RIGHT_AXIS_PADDING).max(RIGHT_AXIS_MIN_PADDING);
+                (lo - pad, hi + pad)
+            });
+        let derived_points = derived_points
+            .into_iter()
+            .map(|(name, axis, points)| match (axis, right_bounds) {
+                (AxisSide::Right, Some((r_min, r_max))) => {
+                    let scaled = points
+                        .into_iter()
+                        .map(|(x, v)| (x, y_min + (v - r_min) / (r_max - r_min) * (y_max - y_min)))
+                        .collect();
+                    (format!("{} (R)", name), scaled)
+                }
+                _ => (name, points),
+            })
+            .collect::<Vec<_>>();
+        let chart_style = self.config.chart.style(&self.current_tf);
+        let mut gradient_points = vec![Vec::new(); GRADIENT_COLORS.len()];
+        let positions = gradient_positions(
+            &data_points,
+            &cloned_data,
+            self.config.chart.colors,
+            self.config.goal.target,
+            self.config.display.unit,
+        );
+        for (point, t) in data_points.iter().zip(positions.iter().flatten()) {
+            let idx = (t * (GRADIENT_COLORS.len() - 1) as f64).round() as usize;
+            gradient_points[idx.min(GRADIENT_COLORS.len() - 1)].push(*point);
+        }
+        let fade_days = self.config.chart.fade_after_days;
+        let faded_points = if self.current_tf == ChartTimeFrame::WindowYear && fade_days > 0 {
+            let cutoff = (self.today() - Days::new(fade_days as u64) - date_left).num_days() as f64;
+            data_points.iter().filter(|(x, _)| *x < cutoff).copied().collect()
+        } else {
+            Vec::new()
+        };
+        let mut dataset = Dataset::default()
+            .marker(chart_style.marker.marker())
+            .style(Style::new().blue())
+            // .graph_type(GraphType::Bar) // Bar is fucked on v0.29.0
+            .graph_type(chart_style.graph_type.graph_type())
+            .data(&data_points);
+        if !derived_points.is_empty() {
+            dataset = dataset.name("weight");
+        }
+        let inspected = self
+            .table_state
+            .selected()
+            .filter(|_| self.chart_inspect)
+            .and_then(|idx| cloned_data.get(idx))
+            .map(|x| {
+                let date_point = NaiveDate::parse_from_str(x.date.as_str(), "%d-%m-%Y").unwrap();
+                let diff = (date_point - date_left).num_days() as f64;
+                (x.date.clone(), [(diff, x.weight)])
+            });
+        let mut datasets = band_points
+            .iter()
+            .map(|points| {
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .style(Style::new().dark_gray())
+                    .graph_type(GraphType::Line)
+                    .data(points)
+            })
+            .collect::<Vec<_>>();
+        datasets.push(
+            Dataset::default()
+                .marker(Marker::Braille)
+                .style(Style::new().light_cyan())
+                .graph_type(GraphType::Line)
+                .data(&seasonal_points),
+        );
+        datasets.push(
+            Dataset::default()
+                .marker(Marker::Braille)
+                .style(Style::new().magenta())
+                .graph_type(GraphType::Line)
+                .data(&smoothed_points),
+        );
+        datasets.extend([
+            dataset,
+            Dataset::default()
+                .marker(Marker::Dot)
+                .style(Style::new().dark_gray())
+                .graph_type(GraphType::Scatter)
+                .data(&excluded_points),
+            Dataset::default()
+                .marker(Marker::Block)
+                .style(Style::new().light_red())
+                .graph_type(GraphType::Scatter)
+                .data(&outlier_points),
+        ]);
+        // Drawn over the weight dataset, which still draws the line if any.
+        for (color, points) in GRADIENT_COLORS.iter().zip(&gradient_points) {
+            datasets.push(
+                Dataset::default()
+                    .marker(chart_style.marker.marker())
+                    .style(Style::new().fg(*color))
+                    .graph_type(GraphType::Scatter)
+                    .data(points),
+            );
+        }
+        datasets.push(
+            Dataset::default()
+                .marker(chart_style.marker.marker())
+                .style(Style::new().gray())
+                .graph_type(GraphType::Scatter)
+                .data(&faded_points),
+        );
+        for (i, (name, points)) in derived_points.iter().enumerate() {
+            datasets.push(
+                Dataset::default()
+                    .name(name.clone())
+                    .marker(chart_style.marker.marker())
+                    .style(Style::new().fg(DERIVED_COLORS[i % DERIVED_COLORS.len()]))
+                    .graph_type(chart_style.graph_type.graph_type())
+                    .data(points),
+            );
+        }
+        let projection = self.projection_points(&cloned_data, date_left, delta, (y_min, y_max));
+        if !projection.is_empty() {
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .style(Style::new().light_magenta())
+                    .graph_type(GraphType::Scatter)
+                    .data(&projection),
+            );
+        }
+        let mut block = Block::bordered()
+            .title_top(Line::from(title).cyan().bold().centered())
+            .style(style);
+        if let Some(deviation) = volatility(&cloned_data).filter(|_| self.config.chart.volatility) {
+            let label = self.localize(format!(" volatility ±{:.1} ", deviation));
+            block = block.title_bottom(Line::from(label).dark_gray().left_aligned());
+        }
+        if self.config.chart.seasonal {
+            let label = if seasonal_points.is_empty() {
+                format!(" seasonal: needs {} days of data ", SEASONAL_MIN_DAYS)
+            } else {
+                String::from(" seasonally adjusted ")
+            };
+            block = block.title_bottom(Line::from(label).light_cyan().left_aligned());
+        }
+        if let Some(count) = self.pending_count.filter(|_| self.current_frame == FrameType::Chart) {
+            block = block.title_bottom(Line::from(format!(" {} ", count)).right_aligned());
+        }
+        if let Some((date, point)) = &inspected {
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Block)
+                    .style(Style::new().light_yellow())
+                    .graph_type(GraphType::Scatter)
+                    .data(point),
+            );
+            block = block.title_bottom(
+                Line::from(format!(" {}: {} ", date, self.format_weight(point[0].1)))
+                    .light_yellow()
+                    .centered(),
+            );
+        }
+        let mut chart = Chart::new(datasets)
+            .x_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([0.0, delta])
+                    .labels(x_label)
+                    .labels_alignment(Alignment::Right),
+            )
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().gray())
+                    .bounds([y_min, y_max])
+                    .labels([
+                        self.localize(format!("{:.1}", y_min)).bold(),
+                        self.localize(format!("{:.1}", y_max)).bold(),
+                    ]),
+            )
+            .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)));
+        let Some((r_min, r_max)) = right_bounds else {
+            chart = chart.block(block);
+            frame.render_widget(chart, area);
+            return;
+        };
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let (top, bottom) = (
+            self.localize(format!("{:.1}", r_max)),
+            self.localize(format!("{:.1}", r_min)),
+        );
+        let label_width = top.chars().count().max(bottom.chars().count()) as u16 + 1;
+        let [chart_area, label_area] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(label_width)]).areas(inner);
+        frame.render_widget(chart, chart_area);
+        // The plot spans from the top row down to just above the x-axis line
+        // and its labels, matching where the left axis labels are drawn.
+        let mut lines = vec![Line::from(top.bold())];
+        let plot_rows = label_area.height.saturating_sub(2) as usize;
+        if plot_rows > 1 {
+            lines.resize(plot_rows - 1, Line::default());
+            lines.push(Line::from(bottom.bold()));
+        }
+        frame.render_widget(
+            Paragraph::new(lines).style(Style::default().gray()).right_aligned(),
+            label_area,
+        );
+    }
+
+    /// Dashed line from the last entry at the simulated rate, in chart
+    /// coordinates, clipped to the visible window.
+    fn projection_points(
+        &self,
+        data: &[Entry],
+        date_left: NaiveDate,
+        delta: f64,
+        (y_min, y_max): (f64, f64),
+    ) -> Vec<(f64, f64)> {
+        let (Some(rate), Some(last)) = (self.simulated_rate, data.iter().rev().find(|x| !x.excluded))
+        else {
+            return Vec::new();
+        };
+        let last_date = NaiveDate::parse_from_str(&last.date, "%d-%m-%Y").unwrap();
+        let start = (last_date - date_left).num_days() as f64;
+        (0..=PROJECTION_SAMPLES)
+            .filter(|i| (i / PROJECTION_DASH).is_multiple_of(2))
+            .map(|i| start + (delta - start) * i as f64 / PROJECTION_SAMPLES as f64)
+            .filter(|x| *x >= 0.0 && *x <= delta)
+            .map(|x| (x, last.weight + rate / 7.0 * (x - start)))
+            .filter(|(_, y)| *y >= y_min && *y <= y_max)
+            .collect()
+    }
+
+    /// Overlay listing when the simulated rate reaches the goal and a few
+    /// round targets.
+    pub fn render_simulator(&self, area: Rect, frame: &mut Frame) {
+        let Some(rate) = self.simulated_rate else {
+            return;
+        };
+        let data = self.stats_data();
+        let Some(last) = data.iter().rev().find(|x| !x.excluded) else {
+            return;
+        };
+        let from = NaiveDate::parse_from_str(&last.date, "%d-%m-%Y").unwrap();
+        let step = if rate < 0.0 { -1.0 } else { 1.0 };
+        let mut targets = SIMULATOR_TARGETS
+            .iter()
+            .map(|k| ((last.weight / step).floor() * step + step * k).round())
+            .collect::<Vec<_>>();
+        if let Some(goal) = self.config.goal.target {
+            targets.push(goal);
+        }
+        targets.sort_by(|a, b| (a - last.weight).abs().total_cmp(&(b - last.weight).abs()));
+        let mut lines = vec![
+            Line::from(self.localize(format!(
+                "Rate: {:+.1} {}/week",
+                rate,
+                self.config.display.unit.name()
+            )))
+            .bold(),
+        ];
+        for target in targets {
+            let when = match project_date(from, last.weight, rate, target) {
+                Some(date) => date.format("%d-%m-%Y").to_string(),
+                None => String::from("never"),
+            };
+            let label = if Some(target) == self.config.goal.target { " (goal)" } else { "" };
+            lines.push(Line::from(self.localize(format!("{:.1}{}: {}", target, label, when))));
+        }
+        let width = lines.iter().map(|x| x.width()).max().unwrap_or(0) as u16 + 4;
+        let popup = Rect {
+            x: area.x + SIMULATOR_OFFSET_X,
+            y: area.y + 1,
+            width: width.min(area.width.saturating_sub(SIMULATOR_OFFSET_X + 1)),
+            height: (lines.len() as u16 + 2).min(area.height.saturating_sub(2)),
+        };
+        let text = Paragraph::new(lines).block(
+            Block::bordered()
+                .title(" What if ")
+                .border_style(Style::new().light_magenta()),
+        );
+        frame.render_widget(Clear, popup); //this clears out the background
+        frame.render_widget(text, popup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PaddingConfig, PaddingMode};
+
+    fn padding(mode: PaddingMode, value: f64) -> PaddingConfig {
+        PaddingConfig { mode, value: Some(value) }
+    }
+
+    #[test]
+    fn relative_labels_count_weeks() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 5).unwrap();
+        let label = |days: i64| relative_label(today + chrono::Duration::days(days), today);
+        assert_eq!(label(0), "now");
+        assert_eq!(label(-3), "now");
+        assert_eq!(label(-4), "-1w");
+        assert_eq!(label(-56), "-8w");
+        assert_eq!(label(-366), "-52w");
+        assert_eq!(label(14), "+2w");
+    }
+
+    #[test]
+    fn compute_bounds_empty() {
+        let bounds = compute_bounds(std::iter::empty(), &PaddingConfig::default());
+        assert_eq!(bounds, EMPTY_CHART_BOUNDS);
+    }
+
+    #[test]
+    fn compute_bounds_single_point() {
+        let bounds = compute_bounds([80.0].into_iter(), &padding(PaddingMode::Snug, 0.1));
+        assert_eq!(bounds, (79.4, 80.6));
+    }
+
+    #[test]
+    fn compute_bounds_identical_values() {
+        let bounds = compute_bounds([75.0, 75.0, 75.0].into_iter(), &PaddingConfig::default());
+        assert_eq!(bounds, (72.5, 77.5));
+    }
+
+    #[test]
+    fn compute_bounds_narrow_range_is_centered() {
+        let (lo, hi) = compute_bounds([80.0, 80.2].into_iter(), &padding(PaddingMode::Absolute, 0.0));
+        assert!((lo - 79.6).abs() < 1e-9 && (hi - 80.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compute_bounds_absolute() {
+        let bounds = compute_bounds([80.0, 90.0, 85.0].into_iter(), &PaddingConfig::default());
+        assert_eq!(bounds, (78.0, 92.0));
+    }
+
+    #[test]
+    fn compute_bounds_percent() {
+        let bounds = compute_bounds([50.0, 100.0].into_iter(), &padding(PaddingMode::Percent, 10.0));
+        assert_eq!(bounds, (45.0, 110.0));
+    }
+
+    #[test]
+    fn compute_bounds_snug() {
+        let bounds = compute_bounds([80.0, 90.0].into_iter(), &padding(PaddingMode::Snug, 0.5));
+        assert_eq!(bounds, (75.0, 95.0));
+    }
+
+    #[test]
+    fn compute_bounds_ignores_non_finite() {
+        let values = [f64::NAN, 80.0, f64::INFINITY, 90.0, f64::NEG_INFINITY];
+        let bounds = compute_bounds(values.into_iter(), &padding(PaddingMode::Absolute, 1.0));
+        assert_eq!(bounds, (79.0, 91.0));
+    }
+
+    #[test]
+    fn compute_bounds_negative_values() {
+        let bounds = compute_bounds([-5.0, -1.0].into_iter(), &padding(PaddingMode::Absolute, 1.0));
+        assert_eq!(bounds, (-6.0, 0.0));
+    }
+
+    #[test]
+    fn gradient_positions_by_goal_and_change() {
+        let data = [("01-01-2024", 90.0), ("02-01-2024", 85.0), ("03-01-2024", 80.0), ("04-01-2024", 81.0)]
+            .map(|(d, w)| Entry::new(d.to_string(), w));
+        let points = data.iter().enumerate().map(|(i, x)| (i as f64, x.weight)).collect::<Vec<_>>();
+        assert!(gradient_positions(&points, &data, PointColors::Plain, Some(80.0), Unit::Kg).is_none());
+        assert!(gradient_positions(&points, &data, PointColors::Goal, None, Unit::Kg).is_none());
+        let goal = gradient_positions(&points, &data, PointColors::Goal, Some(80.0), Unit::Kg).unwrap();
+        assert_eq!(goal, [0.0, 0.5, 1.0, 0.9]);
+        let change = gradient_positions(&points, &data, PointColors::Change, None, Unit::Kg).unwrap();
+        assert_eq!(change, [0.5, 1.0, 1.0, 0.0]);
+        // Gaining is progress when the goal is above.
+        let change = gradient_positions(&points[2..], &data, PointColors::Change, Some(85.0), Unit::Kg).unwrap();
+        assert_eq!(change, [0.5, 1.0]);
+        // The full color takes a kilogram in pounds too.
+        let change = gradient_positions(&points[2..], &data, PointColors::Change, None, Unit::Lb).unwrap();
+        assert!((change[1] - (0.5 - 0.5 / Unit::Kg.convert(1.0, Unit::Lb))).abs() < 1e-9);
+    }
+
+    #[test]
+    fn quarter_and_months_ranges() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let mut app = App::default_test();
+        app.selected_date_m = date(2024, 5, 17);
+        app.current_tf = ChartTimeFrame::Quarter;
+        assert_eq!(app.chart_range(), (date(2024, 4, 1), date(2024, 6, 30)));
+        app.current_tf = ChartTimeFrame::Months;
+        app.config.chart.window_months = 3;
+        assert_eq!(app.chart_range(), (date(2024, 3, 1), date(2024, 5, 31)));
+        app.selected_date_m = date(2024, 1, 31);
+        assert_eq!(app.chart_range(), (date(2023, 11, 1), date(2024, 1, 31)));
+    }
+
+    #[test]
+    fn overview_averages_weeks_before_the_split() {
+        let data = [
+            ("01-01-2024", 90.0),
+            ("03-01-2024", 88.0),
+            ("09-01-2024", 86.0),
+            ("15-01-2024", 85.0),
+            ("17-01-2024", 84.0),
+        ]
+        .map(|(d, w)| Entry::new(d.to_string(), w));
+        let split = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let (weekly, daily, weeks) = overview_points(&data, split);
+        assert_eq!(weeks, 2.0);
+        assert_eq!(weekly, [(0.0, 89.0), (1.0, 86.0)]);
+        assert_eq!(daily, [(2.0, 85.0), (4.0, 84.0)]);
+        assert_eq!(overview_points(&[], split), (Vec::new(), Vec::new(), 0.0));
+    }
+}
@@ -0,0 +1,145 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::Span,
+    widgets::{Block, Borders, Paragraph},
+};
+
+use crate::app::{App, MessageType};
+use crate::config::Theme;
+use crate::keymap::{self, Action, Mode};
+
+/// A key binding as listed in the hint bar: the keys and what they do.
+pub type KeyHint = (String, &'static str);
+
+/// Filter syntax, listed after the keys of the filter popup.
+const FILTER_SYNTAX: [(&str, &str); 2] = [(">=N/<=N", "weight"), (">=DATE/<=DATE", "date")];
+
+/// Splits `hints` into pages of at most `width` characters, in order, so that
+/// the common keys come first and the rest stay a keypress away. Pages after
+/// the first are announced with `more_key`, e.g. "?: more 1/3".
+pub fn hint_pages(hints: &[(impl AsRef<str>, &str)], width: usize, more_key: &str, ascii: bool) -> Vec<String> {
+    let items = hints.iter().map(|(keys, action)| format!("{}: {}", keys.as_ref(), action)).collect::<Vec<_>>();
+    let single = items.join(" | ");
+    if single.chars().count() <= width {
+        return vec![single];
+    }
+    let ellipsis = if ascii { "..." } else { "…" };
+    let cut = |text: String, width: usize| {
+        if text.chars().count() <= width {
+            return text;
+        }
+        let kept = width.saturating_sub(ellipsis.chars().count());
+        text.chars().take(kept).collect::<String>() + ellipsis
+    };
+    if items.len() == 1 {
+        return vec![cut(single, width)];
+    }
+    let paginate = |last: usize| {
+        // Room for the page marker, as long as it gets with up to `last` pages.
+        let marker = format!(" | {}: more {}/{}", more_key, last, last);
+        let budget = width.saturating_sub(marker.chars().count());
+        let mut pages: Vec<Vec<String>> = vec![Vec::new()];
+        for item in &items {
+            let item = cut(item.clone(), budget);
+            let page = pages.last_mut().unwrap();
+            let len = page.iter().map(|x| x.chars().count() + 3).sum::<usize>() + item.chars().count();
+            if !page.is_empty() && len > budget {
+                pages.push(vec![item]);
+            } else {
+                page.push(item);
+            }
+        }
+        pages
+    };
+    let mut pages = paginate(9);
+    if pages.len() > 9 {
+        pages = paginate(99);
+    }
+    let count = pages.len();
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, page)| format!("{} | {}: more {}/{}", page.join(" | "), more_key, i + 1, count))
+        .collect()
+}
+
+impl App<'_> {
+    /// Keys of the current window, frame and mode, most used first.
+    pub fn key_hints(&self) -> Vec<KeyHint> {
+        let mode = self.key_mode();
+        let mut hints: Vec<KeyHint> = Vec::new();
+        for binding in keymap::hinted(mode) {
+            let hint = match binding.action {
+                Action::Save if self.staging.is_some() => "review staged changes",
+                _ => binding.hint,
+            };
+            match hints.last_mut() {
+                Some((keys, last)) if *last == hint => *keys = format!("{}/{}", keys, binding.key.label()),
+                _ => hints.push((binding.key.label(), hint)),
+            }
+        }
+        if mode == Mode::FilterPopup {
+            hints.extend(FILTER_SYNTAX.iter().map(|(keys, hint)| (keys.to_string(), *hint)));
+        }
+        hints
+    }
+
+    pub fn render_message_box(&mut self, area: Rect, frame: &mut Frame) {
+        let title_block = Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default());
+        if self.messages.tick() {
+            self.rm_confirm = false;
+        }
+        if let Some((msg, msg_type)) = self.messages.current() {
+            let style = match msg_type {
+                MessageType::Warning => {
+                    Style::default().fg(Color::LightYellow)
+                }
+                MessageType::Info => {
+                    Style::default().fg(Color::LightGreen)
+                }
+                MessageType::Error => {
+                    Style::default().fg(Color::LightRed)
+                }
+            };
+            let message = Paragraph::new(Span::styled(msg, style))
+                .centered()
+                .block(title_block);
+            frame.render_widget(message, area);
+        } else {
+            let more_key = if self.accepts_paste() { "F1" } else { "?" };
+            let width = usize::from(area.width).saturating_sub(2);
+            let pages = hint_pages(&self.key_hints(), width, more_key, self.theme == Theme::Monochrome);
+            let page = &pages[self.hint_page % pages.len()];
+            let message = Paragraph::new(Span::styled(page.as_str(), Style::default()))
+                .centered()
+                .block(title_block);
+            frame.render_widget(message, area);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_are_split_into_pages_that_fit() {
+        let hints = [("a", "add entry"), ("e", "edit selected row"), ("d", "delete 1 row"), ("T", "trash")];
+        let single = "a: add entry | e: edit selected row | d: delete 1 row | T: trash";
+        assert_eq!(hint_pages(&hints, 80, "?", false), [single]);
+        let pages = hint_pages(&hints, 40, "?", false);
+        assert_eq!(pages, [
+            "a: add entry | ?: more 1/3",
+            "e: edit selected row | ?: more 2/3",
+            "d: delete 1 row | T: trash | ?: more 3/3",
+        ]);
+        assert!(pages.iter().all(|x| x.chars().count() <= 40));
+        assert_eq!(hint_pages(&hints[1..2], 10, "F1", true), ["e: edit..."]);
+        let pages = hint_pages(&hints[..2], 22, "F1", false);
+        assert_eq!(pages, ["a: add… | F1: more 1/2", "e: edi… | F1: more 2/2"]);
+    }
+}
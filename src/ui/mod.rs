@@ -0,0 +1,473 @@
+mod chart;
+mod message;
+mod popups;
+mod table;
+
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Flex, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Paragraph},
+};
+
+use crate::app::{App, ChartTimeFrame, WindowType};
+use crate::config::Theme;
+use crate::data::{format_age, last_entry_age, schedule_streak};
+
+impl Theme {
+    /// Final pass over a rendered frame. Widgets draw with their regular
+    /// styles; the theme then rewrites what the terminal can't or shouldn't show.
+    pub fn apply(&self, buf: &mut Buffer) {
+        if *self != Theme::Monochrome {
+            return;
+        }
+        for cell in buf.content.iter_mut() {
+            if let Some(symbol) = cell.symbol().chars().next().and_then(ascii_symbol) {
+                cell.set_symbol(symbol);
+            }
+            // Highlighted rows and headers are only distinguishable by their
+            // background, so keep them visible by reversing them instead.
+            if cell.bg != Color::Reset {
+                cell.modifier.insert(Modifier::REVERSED);
+            }
+            cell.fg = Color::Reset;
+            cell.bg = Color::Reset;
+        }
+    }
+}
+
+/// ASCII replacement of box-drawing, block, braille and other symbols.
+fn ascii_symbol(c: char) -> Option<&'static str> {
+    match c {
+        '─' | '━' | '═' | '╌' | '┄' => Some("-"),
+        '│' | '┃' | '║' | '╎' | '┆' => Some("|"),
+        '\u{2500}'..='\u{257f}' => Some("+"),
+        '\u{2800}' => Some(" "),
+        '\u{2801}'..='\u{28ff}' | '•' => Some("*"),
+        '\u{2580}'..='\u{259f}' => Some("#"),
+        '→' => Some(">"),
+        '■' => Some("#"),
+        '▲' => Some("^"),
+        '▼' => Some("v"),
+        _ => None,
+    }
+}
+
+pub fn center_text(s: String) -> Text<'static> {
+    Text::styled(s, Style::default()).centered()
+}
+
+const MIN_WIDTH: u16 = 60u16;
+
+const MIN_HEIGHT: u16 = 20u16;
+
+impl App<'_> {
+    pub fn draw(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let w = area.width;
+        let h = area.height;
+        if self.locked {
+            self.render_lock_screen(frame);
+        } else if self.load_error.is_some() {
+            self.render_recovery(frame);
+        } else if w >= MIN_WIDTH && h >= MIN_HEIGHT {
+            // Vertical split
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![
+                    Constraint::Length(3),
+                    Constraint::Min(10),
+                    Constraint::Length(3),
+                ])
+                .split(frame.area());
+
+            // Title
+            self.render_title(chunks[0], frame);
+
+            // Middle split
+            {
+                let mid_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints(vec![Constraint::Length(self.table_width()), Constraint::Min(20)])
+                    .split(chunks[1]);
+
+                self.render_table(mid_chunks[0], frame);
+                if let Some(mode) = self.compare {
+                    self.render_compare_table(mid_chunks[1], frame, mode);
+                } else {
+                    self.render_chart(mid_chunks[1], frame);
+                    if !matches!(self.current_tf, ChartTimeFrame::YearOverYear | ChartTimeFrame::Overview) {
+                        self.render_simulator(mid_chunks[1], frame);
+                    }
+                }
+            }
+
+            // Key hint
+            self.render_message_box(chunks[2], frame);
+            match self.current_window {
+                WindowType::ClosePopup => self.render_close_popup(frame),
+                WindowType::InputPopup => self.render_input_popup(frame),
+                WindowType::ImportPathPopup => self.render_import_path_popup(frame),
+                WindowType::FilterPopup => self.render_filter_popup(frame),
+                WindowType::DiffPathPopup => self.render_diff_path_popup(frame),
+                WindowType::DiffView => self.render_diff(frame),
+                WindowType::ImportPreview => self.render_import_preview(frame),
+                WindowType::ImportProgress => self.render_import_progress(frame),
+                WindowType::ImportSummary => self.render_import_summary(frame),
+                WindowType::WeeklySummary => self.render_weekly_summary(frame),
+                WindowType::AchievementUnlocked => self.render_achievement_unlocked(frame),
+                WindowType::Achievements => self.render_achievements(frame),
+                WindowType::Correlation => self.render_correlation(frame),
+                WindowType::Challenges => self.render_challenges(frame),
+                WindowType::EntryDetails => self.render_entry_details(frame),
+                WindowType::Trash => self.render_trash(frame),
+                WindowType::StagedChanges => self.render_staged_changes(frame),
+                WindowType::Settings => self.render_settings(frame),
+                WindowType::Heatmap => self.render_heatmap(frame),
+                WindowType::MainWindow => {}
+            }
+        } else {
+            self.render_window_too_small(frame, w, h);
+        }
+        self.theme.apply(frame.buffer_mut());
+    }
+
+    fn render_window_too_small(&self, frame: &mut Frame, w: u16, h: u16) {
+        let layout = Layout::vertical([Constraint::Length(4)]).flex(Flex::Center);
+        let w_span = if w < MIN_WIDTH {
+            Span::styled(format!("{}", w), Style::new().light_red())
+        } else {
+            Span::styled(format!("{}", w), Style::new().light_green())
+        };
+        let h_span = if h < MIN_HEIGHT {
+            Span::styled(format!("{}", h), Style::new().light_red())
+        } else {
+            Span::styled(format!("{}", h), Style::new().light_green())
+        };
+        let lines = vec![
+            Line::from("Terminal size too small:"),
+            Line::from(vec![w_span, Span::raw(" x "), h_span]),
+            Line::from("Required size:"),
+            Line::from(format!("{} x {}", MIN_WIDTH, MIN_HEIGHT)),
+        ];
+        let [area] = layout.areas(frame.area());
+        frame.render_widget(Paragraph::new(lines).alignment(Alignment::Center), area);
+    }
+
+    fn render_title(&self, area: Rect, frame: &mut Frame) {
+        let title_block = Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default());
+        let mut text = match (&self.staging, self.is_modified()) {
+            (Some(_), true) => format!("Weight Tracker [staged: {}]", self.pending_changes().len()),
+            (Some(_), false) => String::from("Weight Tracker [staged]"),
+            (None, true) => String::from("Weight Tracker [+]"),
+            (None, false) => String::from("Weight Tracker"),
+        };
+        let data_ref = self.data.borrow();
+        let today = self.today();
+        let mut age = None;
+        if !data_ref.is_empty() {
+            let streak = schedule_streak(&data_ref, &self.schedule, today);
+            text.push_str(&format!(" | Streak: {}", streak));
+            match self.goal_countdown() {
+                Some(countdown) if countdown.remaining <= 0.0 => text.push_str(" | Goal reached"),
+                Some(countdown) => {
+                    text.push_str(&format!(" | Goal: {} to go", self.countdown_text(&countdown)))
+                }
+                None => {}
+            }
+            age = last_entry_age(&data_ref, today);
+        }
+        let mut spans = vec![Span::raw(text)];
+        if let Some(age) = age {
+            let span = Span::raw(format!(" | Last entry: {}", format_age(age)));
+            spans.push(if age > self.config.staleness.days {
+                span.light_red().bold()
+            } else {
+                span
+            });
+        }
+        let title = Paragraph::new(Line::from(spans))
+            .centered()
+            .block(title_block.clone());
+        frame.render_widget(title, area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Days, NaiveDate};
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use crate::app::{FrameType, ImportJob, TextMode, TrashedEntry};
+    use crate::config::PointColors;
+    use crate::data::{Challenge, ChallengeResult, DateFormat, Entry, ImportReport, PendingChange};
+
+    /// The minimum supported size, a common default and a large terminal.
+    const SIZES: [(u16, u16); 3] = [(MIN_WIDTH, MIN_HEIGHT), (100, 30), (160, 45)];
+
+    fn app() -> App<'static> {
+        let mut app = App::default_test();
+        let today = NaiveDate::from_ymd_opt(2024, 5, 5).unwrap();
+        app.today = Some(today);
+        app.selected_date_wy = today;
+        app.selected_date_y = today;
+        app.selected_date_m = today;
+        app
+    }
+
+    fn import_job() -> ImportJob {
+        let report = || ImportReport {
+            added: 12,
+            duplicates: 3,
+            failed: vec![(4, String::from("invalid date '31-02-2024'"))],
+            skipped: vec![(1, String::from("Table 1,,"))],
+            stripped: 15,
+        };
+        ImportJob {
+            path: String::from("export.csv"),
+            source: String::from("import:export"),
+            lines: vec![String::new(); 40],
+            pos: 16,
+            report: report(),
+            preview: report(),
+            format: DateFormat::DmySlash,
+            formats: vec![DateFormat::DmySlash, DateFormat::MdySlash],
+        }
+    }
+
+    /// Renders `app` at every size in `SIZES` and compares each screen,
+    /// styles included, with its stored snapshot.
+    fn assert_screens(name: &str, app: &mut App) {
+        for (width, height) in SIZES {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal.draw(|frame| app.draw(frame)).unwrap();
+            let snapshot = format!("{}_{}x{}", name, width, height);
+            insta::assert_debug_snapshot!(snapshot, terminal.backend().buffer());
+        }
+    }
+
+    #[test]
+    fn main_window() {
+        let mut app = app();
+        assert_screens("table_frame", &mut app);
+        app.hint_page = 1;
+        assert_screens("table_frame_hints", &mut app);
+        app.hint_page = 0;
+        app.current_frame = FrameType::Chart;
+        assert_screens("chart_frame", &mut app);
+        app.config.chart.colors = PointColors::Goal;
+        app.config.goal.target = Some(88.0);
+        assert_screens("goal_gradient", &mut app);
+        app.config.chart.colors = PointColors::Plain;
+        app.config.goal.target = None;
+        app.config.chart.volatility = true;
+        assert_screens("volatility", &mut app);
+        app.config.chart.volatility = false;
+        app.smoothing_days = Some(3);
+        assert_screens("smoothing", &mut app);
+        app.smoothing_days = None;
+        app.config.chart.title = String::from("{label} · {count} entries · ø {average} {unit} · {change}");
+        assert_screens("chart_title", &mut app);
+        app.config.chart.title = String::from("{label}");
+        app.theme = Theme::Monochrome;
+        assert_screens("monochrome", &mut app);
+    }
+
+    #[test]
+    fn empty_state() {
+        let mut app = app();
+        app.data.get_mut().clear();
+        app.table_state.select(None);
+        assert_screens("empty_state", &mut app);
+    }
+
+    #[test]
+    fn recovery_screen() {
+        let mut app = app();
+        app.data.get_mut().clear();
+        app.load_error = Some(crate::WtError::Storage(String::from("data file is not valid UTF-8")));
+        assert_screens("recovery", &mut app);
+    }
+
+    #[test]
+    fn rate_of_change() {
+        let mut app = app();
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        *app.data.get_mut() = (0..66u64)
+            .map(|day| {
+                let weight = 95.0 - 0.1 * day.min(40) as f64 + if day % 3 == 0 { 0.4 } else { 0.0 };
+                Entry::new((start + Days::new(day)).format("%d-%m-%Y").to_string(), weight)
+            })
+            .collect();
+        app.current_frame = FrameType::Chart;
+        app.current_tf = ChartTimeFrame::Months;
+        app.rate_view = true;
+        assert_screens("rate_of_change", &mut app);
+    }
+
+    #[test]
+    fn stones() {
+        let mut app = app();
+        app.config.display.unit = crate::data::Unit::Lb;
+        app.config.display.stones = true;
+        assert_screens("stones", &mut app);
+        app.current_window = WindowType::InputPopup;
+        app.text_area[1] = tui_textarea::TextArea::new(vec![String::from("14st2")]);
+        assert_screens("stones_input", &mut app);
+    }
+
+    #[test]
+    fn timeframes() {
+        let mut app = app();
+        app.current_frame = FrameType::Chart;
+        for (name, tf) in [
+            ("month", ChartTimeFrame::Month),
+            ("quarter", ChartTimeFrame::Quarter),
+            ("months", ChartTimeFrame::Months),
+            ("year", ChartTimeFrame::Year),
+            ("window_year", ChartTimeFrame::WindowYear),
+            ("year_over_year", ChartTimeFrame::YearOverYear),
+            ("overview", ChartTimeFrame::Overview),
+        ] {
+            app.current_tf = tf;
+            assert_screens(&format!("timeframe_{}", name), &mut app);
+        }
+        app.current_tf = ChartTimeFrame::WindowYear;
+        app.config.chart.fade_after_days = 7;
+        assert_screens("timeframe_window_year_faded", &mut app);
+        app.config.chart.x_labels = crate::config::AxisLabels::Relative;
+        assert_screens("timeframe_window_year_relative", &mut app);
+    }
+
+    #[test]
+    fn popups() {
+        let mut app = app();
+        for (entry, rhr) in app.data.get_mut().iter_mut().zip([62.0, 61.0, 64.0, 66.0, 60.0]) {
+            entry.rhr = Some(rhr);
+        }
+        app.text_mode = Some(TextMode::Append);
+        app.init_text_area();
+        app.import_job = Some(import_job());
+        for (date, deleted) in [("24-04-2024", "01-05-2024"), ("25-04-2024", "04-05-2024")] {
+            app.trash.push(TrashedEntry {
+                deleted: String::from(deleted),
+                entry: format!("{}, 90.4, source=manual", date),
+            });
+        }
+        app.trash_state.select(Some(1));
+        let entry = |date: &str, weight| crate::data::Entry::new(String::from(date), weight);
+        let mut excluded = entry("27-04-2024", 89.9);
+        excluded.excluded = true;
+        app.diff = Some((
+            String::from("/backups/weight.csv.bak"),
+            vec![
+                PendingChange::Edited(entry("27-04-2024", 89.9), excluded),
+                PendingChange::Deleted(entry("01-05-2024", 91.5)),
+                PendingChange::Edited(entry("02-05-2024", 94.1), entry("02-05-2024", 93.1)),
+                PendingChange::Added(entry("06-05-2024", 87.2)),
+            ],
+        ));
+        app.diff_state.select(Some(0));
+        app.pending_achievements.push_back(0);
+        app.achievements.insert(String::from("first_entry"), String::from("26-04-2024"));
+        app.challenges.push(Challenge {
+            name: String::from("May cut"),
+            start: NaiveDate::from_ymd_opt(2024, 4, 29).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 5, 26).unwrap(),
+            change: -2.0,
+        });
+        app.challenge_results.push(ChallengeResult {
+            name: String::from("April"),
+            start: String::from("01-04-2024"),
+            end: String::from("28-04-2024"),
+            target: -1.0,
+            change: Some(-1.4),
+            success: true,
+        });
+        for (name, window) in [
+            ("close", WindowType::ClosePopup),
+            ("input", WindowType::InputPopup),
+            ("import_path", WindowType::ImportPathPopup),
+            ("filter", WindowType::FilterPopup),
+            ("diff_path", WindowType::DiffPathPopup),
+            ("diff", WindowType::DiffView),
+            ("import_preview", WindowType::ImportPreview),
+            ("import_progress", WindowType::ImportProgress),
+            ("import_summary", WindowType::ImportSummary),
+            ("weekly_summary", WindowType::WeeklySummary),
+            ("achievement_unlocked", WindowType::AchievementUnlocked),
+            ("achievements", WindowType::Achievements),
+            ("correlation", WindowType::Correlation),
+            ("challenges", WindowType::Challenges),
+            ("trash", WindowType::Trash),
+        ] {
+            app.current_window = window;
+            assert_screens(&format!("popup_{}", name), &mut app);
+        }
+        app.data.get_mut()[3].source = Some(String::from("import:apple-health"));
+        app.data.get_mut()[3].excluded = true;
+        app.table_state.select(Some(3));
+        app.config.display.source_column = true;
+        app.current_window = WindowType::EntryDetails;
+        assert_screens("popup_entry_details", &mut app);
+        app.text_area[1] = tui_textarea::TextArea::new(vec![String::from("97.9")]);
+        app.current_window = WindowType::InputPopup;
+        assert_screens("popup_input_typo", &mut app);
+        app.config.lock.passphrase = Some(crate::config::hash_passphrase("secret", "salt"));
+        app.lock();
+        app.lock_area.insert_str("wrong");
+        app.lock_failed = true;
+        assert_screens("lock_screen", &mut app);
+    }
+
+    #[test]
+    fn staged_session() {
+        let mut app = app();
+        app.saved_data = app.data.borrow().clone();
+        app.start_staging();
+        let data = app.data.get_mut();
+        data[1].weight = 89.4;
+        data.remove(3);
+        data.push(crate::data::Entry::new(String::from("04-05-2024"), 87.6));
+        assert_screens("staged_table", &mut app);
+        app.current_window = WindowType::StagedChanges;
+        app.staged_state.select(Some(0));
+        assert_screens("staged_changes", &mut app);
+        app.current_window = WindowType::ClosePopup;
+        assert_screens("staged_close", &mut app);
+    }
+
+    #[test]
+    fn heatmap() {
+        let mut app = app();
+        app.current_window = WindowType::Heatmap;
+        app.heatmap_year = 2024;
+        assert_screens("heatmap", &mut app);
+        app.heatmap_deltas = true;
+        assert_screens("heatmap_deltas", &mut app);
+    }
+
+    #[test]
+    fn settings() {
+        let mut app = app();
+        app.config.goal.target = Some(85.0);
+        app.current_window = WindowType::Settings;
+        app.settings_state.select(Some(1));
+        assert_screens("settings", &mut app);
+        app.settings_state.select(Some(4));
+        app.settings_area = Some(tui_textarea::TextArea::new(vec![String::from("1.8")]));
+        assert_screens("settings_edit", &mut app);
+    }
+
+    #[test]
+    fn window_too_small() {
+        let mut terminal = Terminal::new(TestBackend::new(MIN_WIDTH - 1, MIN_HEIGHT)).unwrap();
+        terminal.draw(|frame| app().draw(frame)).unwrap();
+        insta::assert_debug_snapshot!(terminal.backend().buffer());
+    }
+}
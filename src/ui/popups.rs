@@ -0,0 +1,841 @@
+use chrono::{Datelike, Days, Local, NaiveDate};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Margin, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Gauge, Paragraph, Row, Table, TableState, Wrap},
+};
+use std::path::Path;
+
+use crate::app::{ACHIEVEMENTS, App, InputField, Setting};
+use crate::data::{
+    Entry, FLAG_ESTIMATED, FLAG_EXCLUDED, ImportReport, RHR, challenge_progress, week_label,
+    PendingChange, changed_fields, weekly_change, weekly_summary, HeatDay, year_heatmap,
+};
+use super::center_text;
+
+const IMPORT_SUMMARY_MAX_FAILED: usize = 5;
+
+/// Changes of the heat map smaller than this count as steady, larger ones
+/// than `HEATMAP_STRONG` are highlighted.
+const HEATMAP_STEADY: f64 = 0.05;
+const HEATMAP_STRONG: f64 = 0.5;
+
+/// Rows an import skipped or cleaned up, shown only when there are any.
+fn repair_lines(report: &ImportReport) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    if !report.skipped.is_empty() {
+        lines.push(Line::from(format!("Rows without data skipped: {}", report.skipped.len())).light_blue());
+        for (line_no, text) in report.skipped.iter().take(IMPORT_SUMMARY_MAX_FAILED) {
+            lines.push(Line::from(format!("  line {}: {}", line_no, text)));
+        }
+    }
+    if report.stripped > 0 {
+        lines.push(Line::from(format!("Empty trailing columns dropped: {} lines", report.stripped)).light_blue());
+    }
+    lines
+}
+
+impl App<'_> {
+    pub fn activate_text(&mut self) {
+        let idx = self.selected_area;
+        let Some(field) = self.input_fields.get(idx).copied() else {
+            return;
+        };
+        let valid = self.field_is_valid(idx);
+        let (color, status) = if valid {
+            (Color::LightGreen, " Valid ")
+        } else {
+            (Color::LightRed, " Invalid ")
+        };
+        let title = self.field_title(field);
+        self.text_area[idx].set_cursor_line_style(Style::default().fg(color));
+        self.text_area[idx].set_cursor_style(Style::default().add_modifier(Modifier::REVERSED));
+        self.text_area[idx].set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(color)
+                .title(title)
+                .title_bottom(status),
+        );
+        self.text_is_valid[idx] = valid;
+    }
+
+    /// Title of a box; the date box shows the resolved date once it's valid.
+    fn field_title(&self, field: InputField) -> String {
+        match (field, self.input_date()) {
+            (InputField::Date, Some(date)) => format!(" {} ", date.format("%d-%m-%Y")),
+            _ => String::from(field.title()),
+        }
+    }
+
+    fn inactivate_text(&mut self) {
+        for inactive_area in (0..self.text_area.len()).filter(|x| *x != self.selected_area) {
+            self.text_area[inactive_area].set_cursor_line_style(Style::default());
+            self.text_area[inactive_area].set_cursor_style(Style::default());
+            let title = self.field_title(self.input_fields[inactive_area]);
+            self.text_is_valid[inactive_area] = self.field_is_valid(inactive_area);
+            self.text_area[inactive_area].set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::DarkGray))
+                    .title(title),
+            );
+        }
+    }
+
+    pub fn render_input_popup(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Length(3), Constraint::Length(5)]).flex(Flex::Center);
+        let widths = self
+            .input_fields
+            .iter()
+            .map(|x| if *x == InputField::Weight && self.stones() { 15 } else { x.width() })
+            .collect::<Vec<_>>();
+        let width = widths.iter().sum::<u16>().max(INPUT_CONTEXT_WIDTH);
+        let horizontal = Layout::horizontal([Constraint::Length(width)]).flex(Flex::Center);
+        let [area_popup, area_context] = vertical.areas(area);
+        let [area_popup] = horizontal.areas(area_popup);
+        let [area_context] = horizontal.areas(area_context);
+        let areas = Layout::horizontal(widths.into_iter().map(Constraint::Length))
+            .flex(Flex::Center)
+            .split(area_popup);
+
+        self.activate_text();
+        self.inactivate_text();
+
+        frame.render_widget(Clear, area_popup); //this clears out the background
+        for (text_area, area) in self.text_area.iter().zip(areas.iter()) {
+            frame.render_widget(text_area, *area);
+        }
+
+        let context = Paragraph::new(self.input_context()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::DarkGray)),
+        );
+        frame.render_widget(Clear, area_context);
+        frame.render_widget(context, area_context);
+    }
+
+    /// Previous weight, 7-day average and the change the typed weight would
+    /// make, to catch typos before they are saved.
+    fn input_context(&self) -> Vec<Line<'static>> {
+        let date = self.input_date();
+        let data = self.stats_data();
+        let before = data
+            .iter()
+            .filter(|x| !x.excluded)
+            .filter_map(|x| Some((NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").ok()?, x.weight)))
+            .filter(|(d, _)| date.is_some_and(|date| *d < date))
+            .collect::<Vec<_>>();
+        let previous = before.last().copied();
+        let week = before
+            .iter()
+            .filter(|(d, _)| date.is_some_and(|date| (date - *d).num_days() <= 7))
+            .map(|(_, w)| *w)
+            .collect::<Vec<_>>();
+        let average = (!week.is_empty()).then(|| week.iter().sum::<f64>() / week.len() as f64);
+        let weight = self.parse_weight(&self.text_area[1].lines()[0]).ok();
+        let format = |x: Option<f64>| x.map_or(String::from("-"), |x| self.format_weight(x));
+        let mut lines = vec![
+            Line::from(match previous {
+                Some((d, w)) => format!("Previous: {} ({})", format(Some(w)), d.format("%d-%m")),
+                None => String::from("Previous: -"),
+            }),
+            Line::from(format!("7-day average: {}", format(average))),
+        ];
+        lines.push(match (weight, previous) {
+            (Some(weight), Some((_, w))) => {
+                let delta = weight - w;
+                let line = Line::from(self.localize(format!("Change: {:+.1}", delta)));
+                if delta.abs() > w * INPUT_CHANGE_WARNING / 100.0 {
+                    line.light_yellow()
+                } else {
+                    line
+                }
+            }
+            _ => Line::from("Change: -"),
+        });
+        lines
+    }
+
+    /// Passphrase prompt drawn over an empty screen, so nothing of the data
+    /// shows while locked.
+    pub fn render_lock_screen(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let block = Block::bordered()
+            .title(" Weight Tracker - locked ")
+            .border_style(Style::new().dark_gray());
+        frame.render_widget(block, area);
+        let vertical = Layout::vertical([Constraint::Length(3), Constraint::Length(1)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Length(40)]).flex(Flex::Center);
+        let [input, hint] = vertical.areas(area);
+        let [input] = horizontal.areas(input);
+        let [hint] = horizontal.areas(hint);
+        self.lock_area.set_cursor_line_style(Style::default());
+        self.lock_area.set_block(Block::bordered().title(" Passphrase "));
+        frame.render_widget(&self.lock_area, input);
+        let text = if self.lock_failed {
+            Line::from("Wrong passphrase").light_red()
+        } else {
+            Line::from("Enter: unlock | C-c: quit").dark_gray()
+        };
+        frame.render_widget(Paragraph::new(text.centered()), hint);
+    }
+
+    /// Choices for a data file that exists but cannot be read.
+    pub fn render_recovery(&self, frame: &mut Frame) {
+        let area = frame.area();
+        let block = Block::bordered()
+            .title(" Weight Tracker - cannot read the data file ")
+            .border_style(Style::new().light_red());
+        frame.render_widget(block, area);
+        let error = self.load_error.as_ref().map(|x| x.to_string()).unwrap_or_default();
+        let backup = match self.newest_backup() {
+            Some(path) => {
+                let name = Path::new(&path).file_name().unwrap_or_default().to_string_lossy().into_owned();
+                Line::from(format!("b: open the newest backup, {}", name))
+            }
+            None => Line::from("No backup found").dark_gray(),
+        };
+        let lines = vec![
+            Line::from(self.storage.location()).bold(),
+            Line::from(error).light_red(),
+            Line::from(""),
+            backup,
+            Line::from("n: start fresh, keeping the file as .broken"),
+            Line::from("q: quit and leave it as it is"),
+        ];
+        let vertical = Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let [text] = vertical.areas(area.inner(Margin::new(2, 1)));
+        frame.render_widget(Paragraph::new(lines).centered().wrap(Wrap { trim: true }), text);
+    }
+
+    pub fn render_close_popup(&self, frame: &mut Frame) {
+        let data_ref = self.data.borrow();
+        let today = self.today().format("%d-%m-%Y").to_string();
+        let today_entry = data_ref.iter().rev().find(|x| x.date == today);
+        let due_today = self.reminder_due(self.today());
+        let mut lines = vec![match today_entry {
+            Some(entry) => {
+                let weight = self.format_weight(entry.weight);
+                Line::from(format!("Today: {}", weight)).light_green()
+            }
+            None if due_today => Line::from("No entry for today yet").light_yellow(),
+            None => Line::from("No weigh-in scheduled today"),
+        }];
+        if let Some(change) = weekly_change(&self.stats_data()) {
+            let trend = self.localize(format!("7-day change: {:+.1}", change));
+            lines.push(Line::from(trend));
+        }
+        lines.push(Line::from(""));
+        if self.staging.is_some() && self.is_modified() {
+            let count = self.pending_changes().len();
+            lines.push(Line::from(format!("{} staged changes", count)).light_red());
+            lines.push(Line::from("will be discarded").light_red());
+        }
+        lines.push(Line::from("Quit the app? [Y/n]"));
+        if today_entry.is_none() && due_today {
+            lines.push(Line::from("[l] log today first").light_yellow());
+        }
+        let area = frame.area();
+        let vertical =
+            Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Length(26)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let block = Block::bordered();
+        let text = Paragraph::new(lines).centered().block(block);
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+
+    pub fn render_import_path_popup(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        self.import_path_area.set_cursor_line_style(Style::default());
+        self.import_path_area.set_block(Block::bordered().title(" Import CSV file or URL "));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(&self.import_path_area, area);
+    }
+
+    pub fn render_filter_popup(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        self.filter_area.set_cursor_line_style(Style::default());
+        self.filter_area.set_placeholder_text(">=90 <=01-06-2024");
+        self.filter_area.set_block(Block::bordered().title(" Filter rows "));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(&self.filter_area, area);
+    }
+
+    pub fn render_import_preview(&self, frame: &mut Frame) {
+        let Some(job) = &self.import_job else {
+            return;
+        };
+        let preview = &job.preview;
+        let mut lines = vec![
+            Line::from(format!("Entries to add: {}", preview.added)).light_green(),
+            Line::from(format!("Duplicates to skip: {}", preview.duplicates)).light_yellow(),
+            Line::from(format!("Lines failing to parse: {}", preview.failed.len())).light_red(),
+        ];
+        for (line_no, reason) in preview.failed.iter().take(IMPORT_SUMMARY_MAX_FAILED) {
+            lines.push(Line::from(format!("  line {}: {}", line_no, reason)));
+        }
+        lines.extend(repair_lines(preview));
+        let format = format!("Date format: {}", job.format.name());
+        if job.formats.len() > 1 {
+            let others = job.formats.iter().filter(|x| **x != job.format).map(|x| x.name());
+            let others = others.collect::<Vec<_>>().join(", ");
+            lines.push(Line::from(format!("{} (also fits {}, f: switch)", format, others)).light_yellow());
+        } else {
+            lines.push(Line::from(format));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from("Import? [Enter/Esc]"));
+        let area = frame.area();
+        let vertical =
+            Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let text = Paragraph::new(lines).block(Block::bordered().title(format!(" Preview {} ", job.path)));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+
+    pub fn render_import_progress(&self, frame: &mut Frame) {
+        let Some(job) = &self.import_job else {
+            return;
+        };
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let ratio = if job.lines.is_empty() {
+            1f64
+        } else {
+            job.pos as f64 / job.lines.len() as f64
+        };
+        let gauge = Gauge::default()
+            .block(Block::bordered().title(format!(" Importing {} ", job.path)))
+            .gauge_style(Style::new().blue())
+            .label(format!("{}/{} lines", job.pos, job.lines.len()))
+            .ratio(ratio);
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(gauge, area);
+    }
+
+    pub fn render_import_summary(&self, frame: &mut Frame) {
+        let Some(job) = &self.import_job else {
+            return;
+        };
+        let report = &job.report;
+        let mut lines = vec![
+            Line::from(format!("Added: {}", report.added)).light_green(),
+            Line::from(format!("Skipped duplicates: {}", report.duplicates)).light_yellow(),
+            Line::from(format!("Failed lines: {}", report.failed.len())).light_red(),
+        ];
+        for (line_no, reason) in report.failed.iter().take(IMPORT_SUMMARY_MAX_FAILED) {
+            lines.push(Line::from(format!("  line {}: {}", line_no, reason)));
+        }
+        if report.failed.len() > IMPORT_SUMMARY_MAX_FAILED {
+            lines.push(Line::from(format!(
+                "  ... and {} more",
+                report.failed.len() - IMPORT_SUMMARY_MAX_FAILED
+            )));
+        }
+        lines.extend(repair_lines(report));
+        let area = frame.area();
+        let vertical =
+            Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let text = Paragraph::new(lines).block(Block::bordered().title(" Import summary "));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+
+    /// Everything stored for the selected entry, including where it came from.
+    pub fn render_entry_details(&self, frame: &mut Frame) {
+        let data_ref = self.data.borrow();
+        let Some(entry) = self.table_state.selected().and_then(|idx| data_ref.get(idx)) else {
+            return;
+        };
+        let flags = [(entry.excluded, FLAG_EXCLUDED), (entry.estimated, FLAG_ESTIMATED)]
+            .iter()
+            .filter(|x| x.0)
+            .map(|x| x.1)
+            .collect::<Vec<_>>();
+        let lines = vec![
+            Line::from(format!("Weight: {}", self.format_weight(entry.weight))),
+            Line::from(format!(
+                "Resting heart rate: {}",
+                entry.rhr.map_or(String::from("-"), |x| RHR.format(x))
+            )),
+            Line::from(format!(
+                "Flags: {}",
+                if flags.is_empty() { String::from("-") } else { flags.join(", ") }
+            )),
+            match &entry.source {
+                Some(source) => Line::from(format!("Source: {}", source)),
+                None => Line::from("Source: unknown").dark_gray(),
+            },
+            match &entry.device {
+                Some(device) => Line::from(format!(
+                    "Device: {} ({})",
+                    device,
+                    self.localize(format!("{:+.1}", self.device_offset(entry)))
+                )),
+                None => Line::from("Device: -").dark_gray(),
+            },
+            Line::from(format!(
+                "Modified: {}",
+                entry.modified.map_or(String::from("-"), |x| {
+                    x.with_timezone(&Local).format("%d-%m-%Y %H:%M").to_string()
+                })
+            )),
+        ];
+        let area = frame.area();
+        let vertical =
+            Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let title = format!(" {} ", entry.date);
+        let text = Paragraph::new(lines).block(Block::bordered().title(title));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+
+    /// Deleted entries with the day they were deleted, newest last.
+    pub fn render_trash(&mut self, frame: &mut Frame) {
+        let rows = self
+            .trash
+            .iter()
+            .map(|x| {
+                let (date, weight) = match Entry::from_fields(&x.entry) {
+                    Some(mut e) => {
+                        e.normalize(self.config.display.unit);
+                        (e.date, self.format_weight(e.weight))
+                    }
+                    None => (x.entry.clone(), String::from("-")),
+                };
+                Row::new(vec![center_text(date), center_text(weight), center_text(x.deleted.clone())])
+            })
+            .collect::<Vec<_>>();
+        let area = frame.area();
+        let height = rows.len().max(1) as u16 + 4;
+        let vertical = Layout::vertical([Constraint::Max(height)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Length(42)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let title = format!(" Trash ({}) ", self.trash.len());
+        frame.render_widget(Clear, area); //this clears out the background
+        if rows.is_empty() {
+            let text = Paragraph::new(Line::from("Trash is empty").dark_gray().centered())
+                .block(Block::bordered().title(title));
+            frame.render_widget(text, area);
+            return;
+        }
+        let header = Row::new(vec![
+            center_text(String::from("Date")),
+            center_text(String::from("Weight")),
+            center_text(String::from("Deleted")),
+        ])
+        .bottom_margin(1)
+        .style(Style::default().on_blue().dark_gray());
+        let widths = [Constraint::Length(12), Constraint::Length(8), Constraint::Length(12)];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().on_dark_gray().white())
+            .highlight_symbol("→");
+        frame.render_stateful_widget(table, area, &mut self.trash_state);
+    }
+
+    /// Review screen of a staged session: every pending change, to commit or
+    /// abort together.
+    pub fn render_staged_changes(&mut self, frame: &mut Frame) {
+        let changes = self.pending_changes();
+        let title = format!(" Staged changes ({}) ", changes.len());
+        let mut state = std::mem::take(&mut self.staged_state);
+        self.render_changes(frame, &changes, title, "Nothing to commit", &mut state);
+        self.staged_state = state;
+    }
+
+    /// Common options of the config file, changed in place with `O`.
+    pub fn render_settings(&mut self, frame: &mut Frame) {
+        let rows = Setting::ALL
+            .iter()
+            .map(|setting| {
+                let value = self.setting_value(*setting);
+                let value = if value.is_empty() {
+                    Line::from("not set").dark_gray()
+                } else {
+                    Line::from(value)
+                };
+                Row::new(vec![Line::from(format!(" {}", setting.name())), value])
+            })
+            .collect::<Vec<_>>();
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Length(rows.len() as u16 + 4)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Length(40)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let header = Row::new(vec![Line::from(" Setting"), Line::from("Value")])
+            .bottom_margin(1)
+            .style(Style::default().on_blue().dark_gray());
+        let widths = [Constraint::Length(20), Constraint::Length(16)];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(" Settings "))
+            .row_highlight_style(Style::new().on_dark_gray().white())
+            .highlight_symbol("→");
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_stateful_widget(table, area, &mut self.settings_state);
+        if let (Some(editor), Some(idx)) = (&self.settings_area, self.settings_state.selected()) {
+            // Below the header and its margin; the value column follows the
+            // highlight symbol, the name column and the column spacing.
+            let inner = area.inner(Margin::new(1, 1));
+            let cell = Rect::new(inner.x + 22, inner.y + 2 + idx as u16, 16, 1).intersection(inner);
+            frame.render_widget(Clear, cell);
+            frame.render_widget(editor, cell);
+        }
+    }
+
+    pub fn render_diff_path_popup(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        self.diff_path_area.set_cursor_line_style(Style::default());
+        self.diff_path_area.set_block(Block::bordered().title(" Compare with data file or backup "));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(&self.diff_path_area, area);
+    }
+
+    /// What the file picked with `D` has that the data doesn't: "before" is
+    /// the data, "after" the file.
+    pub fn render_diff(&mut self, frame: &mut Frame) {
+        let Some((path, changes)) = self.diff.take() else {
+            return;
+        };
+        let name = Path::new(&path).file_name().map_or(path.clone(), |x| x.to_string_lossy().into_owned());
+        let title = format!(" {} ({}) ", name, changes.len());
+        let mut state = std::mem::take(&mut self.diff_state);
+        self.render_changes(frame, &changes, title, "No differences", &mut state);
+        self.diff_state = state;
+        self.diff = Some((path, changes));
+    }
+
+    /// Table of changed entries with their weight before and after.
+    fn render_changes(
+        &self,
+        frame: &mut Frame,
+        changes: &[PendingChange],
+        title: String,
+        empty: &str,
+        state: &mut TableState,
+    ) {
+        let weight = |x: &Entry| self.format_weight(x.weight);
+        let rows = changes
+            .iter()
+            .map(|x| {
+                let (kind, before, after) = match x {
+                    PendingChange::Added(e) => ("added", String::from("-"), weight(e)),
+                    PendingChange::Edited(a, b) if changed_fields(a, b).contains(&"weight") => {
+                        ("edited", weight(a), weight(b))
+                    }
+                    PendingChange::Edited(a, b) => (changed_fields(a, b)[0], weight(a), weight(b)),
+                    PendingChange::Deleted(e) => ("deleted", weight(e), String::from("-")),
+                };
+                let row = Row::new(vec![
+                    center_text(x.date().to_string()),
+                    center_text(kind.to_string()),
+                    center_text(before),
+                    center_text(after),
+                ]);
+                match x {
+                    PendingChange::Added(_) => row.light_green(),
+                    PendingChange::Edited(..) => row.light_yellow(),
+                    PendingChange::Deleted(_) => row.light_red(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let area = frame.area();
+        let height = rows.len().max(1) as u16 + 4;
+        let vertical = Layout::vertical([Constraint::Max(height)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Length(46)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        frame.render_widget(Clear, area); //this clears out the background
+        if rows.is_empty() {
+            let text = Paragraph::new(Line::from(empty).dark_gray().centered())
+                .block(Block::bordered().title(title));
+            frame.render_widget(text, area);
+            return;
+        }
+        let header = Row::new(vec![
+            center_text(String::from("Date")),
+            center_text(String::from("Change")),
+            center_text(String::from("Before")),
+            center_text(String::from("After")),
+        ])
+        .bottom_margin(1)
+        .style(Style::default().on_blue().dark_gray());
+        let widths = [
+            Constraint::Length(12),
+            Constraint::Length(9),
+            Constraint::Length(8),
+            Constraint::Length(8),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().on_dark_gray().white())
+            .highlight_symbol("→");
+        frame.render_stateful_widget(table, area, state);
+    }
+
+    pub fn render_weekly_summary(&self, frame: &mut Frame) {
+        let summary = weekly_summary(&self.stats_data(), &self.schedule, self.today());
+        let average = match summary.average {
+            Some(avg) => self.localize(format!("{:.1}", avg)),
+            None => String::from("-"),
+        };
+        let change = match summary.change {
+            Some(change) => self.localize(format!("{:+.1}", change)),
+            None => String::from("-"),
+        };
+        let adherence = Line::from(format!("Weigh-ins: {}/{}", summary.logged, summary.scheduled));
+        let lines = vec![
+            Line::from(format!("Average: {}", average)),
+            Line::from(format!("Change vs previous week: {}", change)),
+            if summary.logged < summary.scheduled {
+                adherence.light_yellow()
+            } else {
+                adherence.light_green()
+            },
+        ];
+        let area = frame.area();
+        let vertical =
+            Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let title = format!(" Week {} ", week_label(summary.week));
+        let text = Paragraph::new(lines).centered().block(Block::bordered().title(title));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+
+    pub fn render_achievement_unlocked(&self, frame: &mut Frame) {
+        let Some(achievement) = self.pending_achievements.front().map(|i| &ACHIEVEMENTS[*i]) else {
+            return;
+        };
+        let lines = vec![
+            Line::from("*** Achievement unlocked! ***").light_yellow().bold(),
+            Line::from(""),
+            Line::from(achievement.name).bold(),
+            Line::from(achievement.description),
+        ];
+        let area = frame.area();
+        let vertical =
+            Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let text = Paragraph::new(lines)
+            .centered()
+            .block(Block::bordered().border_style(Style::new().light_yellow()));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+
+    pub fn render_achievements(&self, frame: &mut Frame) {
+        let lines = ACHIEVEMENTS
+            .iter()
+            .map(|a| match self.achievements.get(a.id) {
+                Some(date) => Line::from(format!("[x] {} - {} ({})", a.name, a.description, date))
+                    .light_green(),
+                None => Line::from(format!("[ ] {} - {}", a.name, a.description)).dark_gray(),
+            })
+            .collect::<Vec<_>>();
+        let area = frame.area();
+        let vertical =
+            Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(70)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let title = format!(" Achievements {}/{} ", self.achievements.len(), ACHIEVEMENTS.len());
+        let text = Paragraph::new(lines).block(Block::bordered().title(title));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+
+    /// The year as a grid of days, colored by whether there is a weigh-in or
+    /// by the change to the previous one.
+    pub fn render_heatmap(&self, frame: &mut Frame) {
+        const LABEL_WIDTH: usize = 4;
+        let today = self.today();
+        let year = self.heatmap_year;
+        let columns = year_heatmap(&self.stats_data(), year, today);
+        let area = frame.area();
+        // Square-ish cells of two characters where they fit.
+        let cell_width = if LABEL_WIDTH + columns.len() * 2 + 2 <= usize::from(area.width) { 2 } else { 1 };
+        let cell = |day: &HeatDay| {
+            let (glyph, style) = match *day {
+                HeatDay::Outside => (" ", Style::default()),
+                HeatDay::Missing => ("·", Style::default().dark_gray()),
+                HeatDay::Logged(_) if !self.heatmap_deltas => ("■", Style::default().light_green()),
+                HeatDay::Logged(Some(delta)) if delta <= -HEATMAP_STEADY => {
+                    let strong = delta <= -HEATMAP_STRONG;
+                    ("▼", if strong { Style::default().light_green() } else { Style::default().green() })
+                }
+                HeatDay::Logged(Some(delta)) if delta >= HEATMAP_STEADY => {
+                    let strong = delta >= HEATMAP_STRONG;
+                    ("▲", if strong { Style::default().light_red() } else { Style::default().red() })
+                }
+                HeatDay::Logged(_) => ("■", Style::default().gray()),
+            };
+            Span::styled(format!("{:<1$}", glyph, cell_width), style)
+        };
+        // Month names above the first week each month starts in.
+        let mut months = " ".repeat(LABEL_WIDTH);
+        let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or(today);
+        let monday = first - Days::new(u64::from(first.weekday().num_days_from_monday()));
+        for idx in 0..columns.len() {
+            let start = (0..7)
+                .map(|i| monday + Days::new((idx * 7 + i) as u64))
+                .find(|date| date.year() == year && date.day() == 1);
+            let pos = LABEL_WIDTH + idx * cell_width;
+            if let Some(date) = start.filter(|_| months.chars().count() <= pos) {
+                months += &" ".repeat(pos - months.chars().count());
+                months += &date.format("%b").to_string();
+            }
+        }
+        let mut lines = vec![Line::from(months).dark_gray()];
+        for (weekday, label) in ["Mon", "", "Wed", "", "Fri", "", "Sun"].iter().enumerate() {
+            let label = Span::styled(format!("{:<1$}", label, LABEL_WIDTH), Style::default().dark_gray());
+            let mut spans = vec![label];
+            spans.extend(columns.iter().map(|column| cell(&column[weekday])));
+            lines.push(Line::from(spans));
+        }
+        let legend = if self.heatmap_deltas {
+            "▼ loss  ▲ gain  ■ steady  · no weigh-in"
+        } else {
+            "■ weigh-in  · no weigh-in"
+        };
+        lines.push(Line::from(""));
+        lines.push(Line::from(legend).dark_gray());
+        let logged = columns.iter().flatten().filter(|x| matches!(x, HeatDay::Logged(_))).count();
+        let days = columns.iter().flatten().filter(|x| **x != HeatDay::Outside).count();
+        let title = format!(" {}: {} of {} days ", year, logged, days);
+        let width = (LABEL_WIDTH + columns.len() * cell_width + 2) as u16;
+        let vertical = Layout::vertical([Constraint::Length(lines.len() as u16 + 2)]).flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Length(width)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+    }
+
+    /// Running and upcoming challenges with their pace, followed by archived results.
+    pub fn render_challenges(&self, frame: &mut Frame) {
+        let today = self.today();
+        let data = self.stats_data();
+        let fmt = |x: Option<f64>, sign: bool| match x {
+            Some(v) if sign => self.localize(format!("{:+.1}", v)),
+            Some(v) => self.localize(format!("{:.2}", v)),
+            None => String::from("-"),
+        };
+        let mut lines = Vec::new();
+        for challenge in self.challenges.iter().filter(|c| c.end >= today) {
+            let period = format!(
+                "{} ({} to {})",
+                challenge.name,
+                challenge.start.format("%d-%m-%Y"),
+                challenge.end.format("%d-%m-%Y")
+            );
+            lines.push(Line::from(period).bold());
+            if challenge.start > today {
+                let days = (challenge.start - today).num_days();
+                lines.push(Line::from(format!("  Starts in {} days", days)).dark_gray());
+                lines.push(Line::from(""));
+                continue;
+            }
+            let progress = challenge_progress(challenge, &data, today);
+            let change = progress.change();
+            let actual_pace = change
+                .filter(|_| progress.elapsed >= 7)
+                .map(|c| c / (progress.elapsed as f64 / 7.0));
+            lines.push(Line::from(format!(
+                "  Day {} of {}, {} days remaining",
+                progress.elapsed,
+                progress.total,
+                progress.remaining()
+            )));
+            let change_line = Line::from(format!(
+                "  Change: {} of {}",
+                fmt(change, true),
+                fmt(Some(challenge.change), true)
+            ));
+            lines.push(if change.is_some_and(|c| challenge.reached(c)) {
+                change_line.light_green()
+            } else {
+                change_line
+            });
+            lines.push(Line::from(format!(
+                "  Pace: {}/week actual vs {}/week required",
+                fmt(actual_pace, false),
+                fmt(Some(challenge.required_pace(&progress)), false)
+            )));
+            lines.push(Line::from(""));
+        }
+        if !self.challenge_results.is_empty() {
+            lines.push(Line::from("Finished").bold());
+            for result in self.challenge_results.iter().rev() {
+                let line = Line::from(format!(
+                    "  [{}] {}: {} of {} ({})",
+                    if result.success { "x" } else { " " },
+                    result.name,
+                    fmt(result.change, true),
+                    fmt(Some(result.target), true),
+                    result.end
+                ));
+                lines.push(if result.success { line.light_green() } else { line.light_red() });
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Line::from("No challenges yet. Add a [[challenge]] to config.toml."));
+        }
+        let area = frame.area();
+        let vertical = Layout::vertical([Constraint::Length(lines.len() as u16 + 2)])
+            .flex(Flex::Center);
+        let horizontal = Layout::horizontal([Constraint::Percentage(70)]).flex(Flex::Center);
+        let [area] = vertical.areas(area);
+        let [area] = horizontal.areas(area);
+        let text = Paragraph::new(lines).block(Block::bordered().title(" Challenges "));
+        frame.render_widget(Clear, area); //this clears out the background
+        frame.render_widget(text, area);
+    }
+}
+
+/// Changes of more than this percentage of the previous weight are highlighted
+/// in the input popup, as they are more likely typos than real changes.
+const INPUT_CHANGE_WARNING: f64 = 2.0;
+
+/// Minimum width of the input popup, so the context below it fits.
+const INPUT_CONTEXT_WIDTH: u16 = 35;
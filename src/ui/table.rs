@@ -0,0 +1,291 @@
+use chrono::{Datelike, Months, NaiveDate};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Margin, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph, Row, Table, TableState},
+};
+use std::collections::HashMap;
+
+use crate::app::{App, CompareMode, FrameType};
+use crate::data::{Entry, RHR, coverage, detect_outliers, rolling_averages};
+use super::center_text;
+
+const AVERAGE_DAYS: u64 = 7;
+
+const INLINE_EDIT_OFFSET_X: u16 = 14;
+
+const DERIVED_WIDTH: u16 = 9u16;
+
+const SOURCE_WIDTH: u16 = 16u16;
+
+impl App<'_> {
+    pub fn table_width(&self) -> u16 {
+        let mut extra = self.derived.iter().filter(|d| d.table).count() as u16;
+        if self.config.display.average_column {
+            extra += 1;
+        }
+        if self.has_rhr() {
+            extra += 1;
+        }
+        let mut width = 14 + self.weight_width() + extra * DERIVED_WIDTH;
+        if self.config.display.source_column {
+            width += SOURCE_WIDTH;
+        }
+        width
+    }
+
+    /// `12 st 10.4 lb` needs more room than a plain weight.
+    fn weight_width(&self) -> u16 {
+        if self.stones() { 13 } else { 7 }
+    }
+
+    /// Whether any entry has a resting heart rate, which adds its table column.
+    fn has_rhr(&self) -> bool {
+        self.data.borrow().iter().any(|x| x.rhr.is_some())
+    }
+
+    pub fn render_table(&mut self, area: Rect, frame: &mut Frame) {
+        let style = match self.current_frame {
+            FrameType::Table => Style::default(),
+            _ => Style::default().dark_gray(),
+        };
+        let mut table_block = Block::default().borders(Borders::ALL).style(style);
+        if let Some(count) = self.pending_count.filter(|_| self.current_frame == FrameType::Table) {
+            table_block = table_block.title_bottom(Line::from(format!(" {} ", count)).right_aligned());
+        }
+        if self.data.borrow().is_empty() {
+            let mut lines = vec![Line::from(""); (area.height / 2).saturating_sub(3) as usize];
+            lines.extend([
+                Line::from("No entries yet").bold(),
+                Line::from(""),
+                Line::from("a: add your first"),
+                Line::from("I: import a CSV"),
+            ]);
+            frame.render_widget(Paragraph::new(lines).centered().block(table_block), area);
+            return;
+        }
+        let table_columns = self.derived.iter().filter(|d| d.table).collect::<Vec<_>>();
+        let show_average = self.config.display.average_column;
+        let mut widths = vec![Constraint::Length(12), Constraint::Length(self.weight_width())];
+        let show_rhr = self.has_rhr();
+        if show_average {
+            widths.push(Constraint::Length(DERIVED_WIDTH - 1));
+        }
+        if show_rhr {
+            widths.push(Constraint::Length(DERIVED_WIDTH - 1));
+        }
+        widths.extend(table_columns.iter().map(|_| Constraint::Length(DERIVED_WIDTH - 1)));
+        let show_source = self.config.display.source_column;
+        if show_source {
+            widths.push(Constraint::Length(SOURCE_WIDTH - 1));
+        }
+        let cloned_data = self.stats_data();
+        let filtered = cloned_data.iter().filter(|x| self.shows_row(x)).cloned().collect::<Vec<_>>();
+        if let Some(filter) = self.filter_label() {
+            table_block = table_block.title_top(Line::from(filter).light_yellow());
+        }
+        if let Some((count, share, first)) = coverage(&filtered, self.today()) {
+            let noun = if count == 1 { "entry" } else { "entries" };
+            let short = format!(" {} {} · {:.0}% ", count, noun, share * 100.0);
+            let full = format!("{}of days since {} ", short, first.format("%d-%m-%Y"));
+            // The table is narrow next to the chart, so drop the date if needed.
+            let footer = if full.chars().count() + 2 <= area.width as usize { full } else { short };
+            table_block = table_block.title_bottom(Line::from(footer));
+        }
+        if show_average && self.averages.is_none() {
+            self.averages = Some(rolling_averages(&cloned_data, AVERAGE_DAYS));
+        }
+        let averages = self.averages.as_deref().unwrap_or_default();
+        let outliers = detect_outliers(&cloned_data, &self.config.outliers);
+        let shown = cloned_data.iter().map(|x| self.shows_row(x)).collect::<Vec<_>>();
+        // A staged session marks what it would change, added rows in green
+        // and edited ones in yellow.
+        let saved = self.staging.as_ref().map(|_| {
+            self.saved_data.iter().map(|x| (x.date.as_str(), x)).collect::<HashMap<_, _>>()
+        });
+        let staged = |x: &Entry| match saved.as_ref()?.get(x.date.as_str()) {
+            None => Some(Color::LightGreen),
+            Some(y) if *y != x => Some(Color::LightYellow),
+            Some(_) => None,
+        };
+        let rows = cloned_data.iter().zip(outliers).enumerate().filter(|(i, _)| shown[*i]);
+        let rows = rows.map(|(i, (x, outlier))| {
+            let scheduled = !self.schedule.is_empty()
+                && NaiveDate::parse_from_str(&x.date, "%d-%m-%Y").is_ok_and(|d| self.is_scheduled(d));
+            let date_text = if scheduled {
+                center_text(x.date.clone()).bold()
+            } else {
+                center_text(x.date.clone())
+            };
+            let mut cells = vec![
+                date_text,
+                center_text(self.format_weight(x.weight)),
+            ];
+            if show_average {
+                cells.push(match averages.get(i).copied().flatten() {
+                    Some(v) => center_text(self.localize(format!("{:.1}", v))),
+                    None => center_text(String::from("-")),
+                });
+            }
+            if show_rhr {
+                cells.push(center_text(x.rhr.map_or(String::from("-"), |v| RHR.format(v))));
+            }
+            cells.extend(table_columns.iter().map(|d| {
+                match d.expr.eval(&|name| self.lookup_var(name, x.weight)) {
+                    Some(v) => center_text(self.localize(format!("{:.1}", v))),
+                    None => center_text(String::from("-")),
+                }
+            }));
+            if show_source {
+                // Long sources are cut at the end so the `import:` prefix stays visible.
+                let source = x.source.as_deref().unwrap_or("-");
+                cells.push(center_text(source.chars().take(SOURCE_WIDTH as usize - 1).collect()));
+            }
+            if let Some(color) = staged(x) {
+                Row::new(cells).fg(color)
+            } else if x.excluded {
+                Row::new(cells).dark_gray()
+            } else if x.estimated {
+                Row::new(cells).italic().gray()
+            } else if outlier {
+                Row::new(cells).light_red()
+            } else {
+                Row::new(cells)
+            }
+        });
+        let mut header = vec![
+            center_text(String::from("Date")),
+            center_text(String::from("Weight")),
+        ];
+        if show_average {
+            header.push(center_text(String::from("7d avg")));
+        }
+        if show_rhr {
+            header.push(center_text(String::from("RHR")));
+        }
+        header.extend(table_columns.iter().map(|d| center_text(d.name.clone())));
+        if show_source {
+            header.push(center_text(String::from("Source")));
+        }
+        let table = Table::new(rows, widths)
+            .header(
+                Row::new(header)
+                .bottom_margin(1)
+                .style(Style::default().on_blue().dark_gray()),
+            )
+            .block(table_block)
+            .row_highlight_style(Style::new().on_dark_gray().white())
+            .highlight_symbol("→");
+        let (selected, offset) = if self.table_filter.is_none() {
+            frame.render_stateful_widget(table, area, &mut self.table_state);
+            (self.table_state.selected(), self.table_state.offset())
+        } else {
+            // The rows are a subset of the data, so a separate state indexes them.
+            let selected = self
+                .table_state
+                .selected()
+                .map(|idx| shown[..idx.min(shown.len())].iter().filter(|x| **x).count())
+                .filter(|_| self.table_state.selected().is_some_and(|idx| shown.get(idx) == Some(&true)));
+            let mut state = TableState::default().with_offset(self.filter_offset).with_selected(selected);
+            frame.render_stateful_widget(table, area, &mut state);
+            self.filter_offset = state.offset();
+            (state.selected(), state.offset())
+        };
+        if let (Some(editor), Some(idx)) = (&self.inline_edit, selected) {
+            // Header plus its bottom margin, then the rows scrolled into view;
+            // the weight column follows the highlight symbol and the date.
+            let inner = area.inner(Margin::new(1, 1));
+            let row = idx.saturating_sub(offset) as u16;
+            let cell = Rect::new(inner.x + INLINE_EDIT_OFFSET_X, inner.y + 2 + row, self.weight_width(), 1)
+                .intersection(inner);
+            frame.render_widget(Clear, cell);
+            frame.render_widget(editor, cell);
+        }
+    }
+
+    /// Entries of the selected month next to the same days of the previous
+    /// month or year, with per-day and average deltas.
+    pub fn render_compare_table(&self, area: Rect, frame: &mut Frame, mode: CompareMode) {
+        let style = match self.current_frame {
+            FrameType::Table => Style::default().dark_gray(),
+            _ => Style::default(),
+        };
+        let month = self.selected_date_m.with_day(1).unwrap();
+        let reference = match mode {
+            CompareMode::PrevMonth => month.checked_sub_months(Months::new(1)).unwrap(),
+            CompareMode::PrevYear => month.checked_sub_months(Months::new(12)).unwrap(),
+        };
+        let data_ref = self.data.borrow();
+        let lookup = |date: Option<NaiveDate>| {
+            let date = date?.format("%d-%m-%Y").to_string();
+            data_ref.iter().find(|x| x.date == date)
+        };
+        let mut rows = Vec::new();
+        let (mut sum, mut ref_sum) = ((0f64, 0usize), (0f64, 0usize));
+        for day in 1..=31 {
+            let current = lookup(month.with_day(day));
+            let previous = lookup(reference.with_day(day));
+            if current.is_none() && previous.is_none() {
+                continue;
+            }
+            let cell = |entry: Option<&Entry>| match entry {
+                Some(e) if e.excluded => center_text(self.format_weight(e.weight)).dark_gray(),
+                Some(e) => center_text(self.format_weight(e.weight)),
+                None => center_text(String::from("-")),
+            };
+            let delta = match (current, previous) {
+                (Some(c), Some(p)) => self.localize(format!("{:+.1}", c.weight - p.weight)),
+                _ => String::from("-"),
+            };
+            for (entry, acc) in [(current, &mut sum), (previous, &mut ref_sum)] {
+                if let Some(e) = entry.filter(|e| !e.excluded) {
+                    acc.0 += e.weight;
+                    acc.1 += 1;
+                }
+            }
+            rows.push(Row::new(vec![
+                center_text(format!("{:02}", day)),
+                cell(current),
+                cell(previous),
+                center_text(delta),
+            ]));
+        }
+        let avg = |(total, n): (f64, usize)| (n > 0).then(|| total / n as f64);
+        let fmt_avg = |x: Option<f64>| x.map_or(String::from("-"), |v| self.localize(format!("{:.1}", v)));
+        let avg_delta = match (avg(sum), avg(ref_sum)) {
+            (Some(c), Some(p)) => self.localize(format!("{:+.1}", c - p)),
+            _ => String::from("-"),
+        };
+        let footer = Row::new(vec![
+            center_text(String::from("Avg")),
+            center_text(fmt_avg(avg(sum))),
+            center_text(fmt_avg(avg(ref_sum))),
+            center_text(avg_delta),
+        ])
+        .top_margin(1)
+        .bold();
+        let header = Row::new(vec![
+            center_text(String::from("Day")),
+            center_text(month.format("%b %Y").to_string()),
+            center_text(reference.format("%b %Y").to_string()),
+            center_text(String::from("Change")),
+        ])
+        .bottom_margin(1)
+        .style(Style::default().on_blue().dark_gray());
+        let title = match mode {
+            CompareMode::PrevMonth => "vs previous month",
+            CompareMode::PrevYear => "vs previous year",
+        };
+        let table = Table::new(rows, [Constraint::Length(5), Constraint::Fill(1), Constraint::Fill(1), Constraint::Fill(1)])
+            .header(header)
+            .footer(footer)
+            .block(
+                Block::bordered()
+                    .title_top(Line::from(title).cyan().bold().centered())
+                    .style(style),
+            );
+        frame.render_widget(table, area);
+    }
+}
@@ -0,0 +1,28 @@
+use std::{fs, path::Path, process::Command};
+
+/// Runs the binary with its config and data under `home`.
+fn weight_tracker(home: &Path, args: &[&str]) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_weight-tracker"));
+    cmd.args(args)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"));
+    cmd
+}
+
+#[test]
+fn deletes_add_up_in_the_trash() {
+    let home = std::env::temp_dir().join(format!("weight-tracker-cli-trash-{}", std::process::id()));
+    let data_dir = home.join("data/weight-tracker");
+    fs::create_dir_all(&data_dir).unwrap();
+    let data = "Date, Weight, Flags, version=3\n01-05-2024, 80.0\n02-05-2024, 79.8\n";
+    fs::write(data_dir.join("weight-tracker.csv"), data).unwrap();
+    for date in ["01-05-2024", "02-05-2024"] {
+        let output = weight_tracker(&home, &["delete", date, "--yes"]).output().unwrap();
+        assert!(output.status.success());
+    }
+    let state = fs::read_to_string(data_dir.join("state.toml")).unwrap();
+    let trash = state.parse::<toml::Table>().unwrap()["trash"].as_array().unwrap().len();
+    assert_eq!(trash, 2);
+    fs::remove_dir_all(&home).unwrap();
+}